@@ -23,10 +23,35 @@ async fn main() {
         eprintln!(
             r#"Invalid arugment, please use:
  * mozimc ping
- * mozimc start <iface_name>
+ * mozimc start <iface_name> [key=value ...]
  * mozimc stop <iface_name>
  * mozimc query <iface_name>
  * mozimc dump
+
+Options of start, repeat extra_option, user_class and vendor_specific for
+more values, see src/daemon/DEVEL.md for detail:
+ * applier=netlink|none|hook:<path>
+ * route_metric=<u32>
+ * dns_priority=<i32>
+ * script=<path>
+ * script_timeout=<seconds>
+ * timeout=<seconds>
+ * arp_probe_count=<u32>
+ * arp_probe_timeout=<seconds>
+ * link_local_timeout=<seconds>
+ * release_policy=release|keep_address|keep_lease
+ * rapid_commit=true|false
+ * inform=<ipv4>
+ * inform_interval=<seconds>
+ * client_id=duid|mac|none|<hex>
+ * hostname=<name>|none
+ * fqdn=<name>
+ * fqdn_update=server|client|none
+ * vendor_class=<string>
+ * user_class=<string>
+ * vendor_specific=<enterprise_number>:<hex>
+ * parameter_request_list=<code>,<code>,...|none
+ * extra_option=<code>:<hex>
         "#
         );
         std::process::exit(1);
@@ -0,0 +1,533 @@
+// Copyright 2020 Red Hat, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::net::Ipv4Addr;
+use std::time::Duration;
+
+use mozim::{
+    DhcpApplierType, DhcpDuidType, DhcpOption, DhcpViVendorSpecific,
+    MozimError, DHCP_DEFAULT_ARP_PROBE_TIMEOUT, DHCP_DEFAULT_INFORM_INTERVAL,
+    DHCP_DEFAULT_PARAMETER_REQUEST_LIST, DHCP_DEFAULT_ROUTE_METRIC,
+    DHCP_DEFAULT_TIMEOUT, DHCP_OPTION_CLIENT_FQDN,
+    DHCP_OPTION_CLIENT_IDENTIFIER, DHCP_OPTION_HOST_NAME,
+    DHCP_OPTION_MESSAGE_TYPE, DHCP_OPTION_PARAMETER_REQUEST_LIST,
+    DHCP_OPTION_RAPID_COMMIT, DHCP_OPTION_REQUESTED_IP,
+    DHCP_OPTION_SERVER_IDENTIFIER, DHCP_OPTION_USER_CLASS,
+    DHCP_OPTION_VENDOR_CLASS, DHCP_OPTION_VI_VENDOR_SPECIFIC,
+};
+use serde_derive::{Deserialize, Serialize};
+
+use crate::dns::MozimDnsBackendType;
+use crate::hook::DEFAULT_SCRIPT_TIMEOUT;
+use crate::identity::parse_hex;
+use crate::lease_db::DEFAULT_LEASE_DIR;
+use crate::resolv_conf::{DEFAULT_DNS_PRIORITY, DEFAULT_RESOLV_CONF_PATH};
+
+const DEFAULT_CONFIG_PATH: &str = "/etc/mozim/mozimd.json";
+// Environment variable to use alternate config file, for testing
+const CONFIG_PATH_ENV: &str = "MOZIM_CONFIG";
+// RFC 5227 PROBE_NUM
+const DEFAULT_ARP_PROBE_COUNT: u32 = 3;
+
+// Daemon wide config stored in JSON, missing file means all default.
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Eq)]
+#[serde(default, deny_unknown_fields)]
+pub(crate) struct MozimDaemonConfig {
+    pub(crate) dns_backend: MozimDnsBackendType,
+    pub(crate) resolv_conf_path: String,
+    // D-Bus address for `systemd_resolved` DNS backend, system bus if unset
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) dbus_address: Option<String>,
+    // Folder holding `<iface_name>.lease` files and `identity.json`
+    pub(crate) lease_dir: String,
+    // Type of DUID generated for RFC 4361 client identifier
+    pub(crate) duid_type: DhcpDuidType,
+    // IANA private enterprise number, required by `en` DUID type
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) duid_enterprise_number: Option<u32>,
+}
+
+impl Default for MozimDaemonConfig {
+    fn default() -> Self {
+        MozimDaemonConfig {
+            dns_backend: MozimDnsBackendType::default(),
+            resolv_conf_path: DEFAULT_RESOLV_CONF_PATH.to_string(),
+            dbus_address: None,
+            lease_dir: DEFAULT_LEASE_DIR.to_string(),
+            duid_type: DhcpDuidType::default(),
+            duid_enterprise_number: None,
+        }
+    }
+}
+
+impl MozimDaemonConfig {
+    pub(crate) fn load() -> Result<Self, MozimError> {
+        let path = std::env::var(CONFIG_PATH_ENV)
+            .unwrap_or_else(|_| DEFAULT_CONFIG_PATH.to_string());
+        let content = match std::fs::read_to_string(&path) {
+            Ok(c) => c,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                return Ok(MozimDaemonConfig::default())
+            }
+            Err(e) => {
+                return Err(MozimError::file_error(format!(
+                    "Failed to read config file {}: {}",
+                    path, e
+                )))
+            }
+        };
+        serde_json::from_str(&content).map_err(|e| {
+            MozimError::invalid_argument(format!(
+                "Invalid config file {}: {}",
+                path, e
+            ))
+        })
+    }
+}
+
+// What to do with the lease on daemon shutdown. The `stop` command always
+// releases.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub(crate) enum MozimReleasePolicy {
+    // Send DHCPRELEASE and remove applied address, the interface is resumed
+    // with DHCPDISCOVER on next daemon start.
+    Release,
+    // Leave the address applied and the lease file untouched
+    #[default]
+    KeepAddress,
+    // Remove applied address without DHCPRELEASE, the lease is requested
+    // again by INIT-REBOOT on next daemon start.
+    KeepLease,
+}
+
+impl std::str::FromStr for MozimReleasePolicy {
+    type Err = MozimError;
+    fn from_str(s: &str) -> Result<Self, MozimError> {
+        match s {
+            "release" => Ok(MozimReleasePolicy::Release),
+            "keep_address" => Ok(MozimReleasePolicy::KeepAddress),
+            "keep_lease" => Ok(MozimReleasePolicy::KeepLease),
+            _ => Err(MozimError::invalid_argument(format!(
+                "Invalid release_policy '{}', should be release, \
+                 keep_address or keep_lease",
+                s
+            ))),
+        }
+    }
+}
+
+// The client identifier option sent to server
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub(crate) enum MozimClientId {
+    // RFC 4361 form with the persistent DUID and IAID of interface
+    #[default]
+    Duid,
+    // Hardware type and MAC address of interface
+    Mac,
+    // Option data as is, starting with the type byte
+    Raw(Vec<u8>),
+    // No client identifier, server uses `chaddr`
+    None,
+}
+
+impl std::str::FromStr for MozimClientId {
+    type Err = MozimError;
+    fn from_str(s: &str) -> Result<Self, MozimError> {
+        match s {
+            "duid" => Ok(MozimClientId::Duid),
+            "mac" => Ok(MozimClientId::Mac),
+            "none" => Ok(MozimClientId::None),
+            _ => match parse_hex(s) {
+                // RFC 2132 section 9.14: minimum length is 2
+                Some(data) if data.len() >= 2 => Ok(MozimClientId::Raw(data)),
+                _ => Err(MozimError::invalid_argument(format!(
+                    "Invalid client_id '{}', should be duid, mac, none or \
+                     hex string of at least 2 bytes",
+                    s
+                ))),
+            },
+        }
+    }
+}
+
+// Host name sent when `fqdn` is not set
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub(crate) enum MozimHostname {
+    // Host name of system at start, sent as FQDN if it contains dot
+    #[default]
+    System,
+    Name(String),
+    None,
+}
+
+// Who updates the A RR of `fqdn` in DNS
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub(crate) enum MozimFqdnUpdate {
+    // Server updates both A and PTR RR
+    #[default]
+    Server,
+    // Server updates only PTR RR, client takes care of A RR
+    Client,
+    // Server should not update DNS
+    None,
+}
+
+impl std::str::FromStr for MozimFqdnUpdate {
+    type Err = MozimError;
+    fn from_str(s: &str) -> Result<Self, MozimError> {
+        match s {
+            "server" => Ok(MozimFqdnUpdate::Server),
+            "client" => Ok(MozimFqdnUpdate::Client),
+            "none" => Ok(MozimFqdnUpdate::None),
+            _ => Err(MozimError::invalid_argument(format!(
+                "Invalid fqdn_update '{}', should be server, client or none",
+                s
+            ))),
+        }
+    }
+}
+
+// Per interface options of `start <iface_name> [key=value ...]`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct MozimIfaceConfig {
+    // The `key=value` options as is, stored in lease file
+    pub(crate) options: Vec<String>,
+    pub(crate) applier: DhcpApplierType,
+    // Metric of routes added by `netlink` applier
+    pub(crate) route_metric: u32,
+    // Smaller value places the DNS of this interface earlier in resolv.conf
+    pub(crate) dns_priority: i32,
+    // dhclient-script compatible hook run on lease events
+    pub(crate) script: Option<String>,
+    pub(crate) script_timeout: Duration,
+    // Give up acquiring lease after this long
+    pub(crate) timeout: Duration,
+    // ARP probes before using the address, 0 to disable
+    pub(crate) arp_probe_count: u32,
+    pub(crate) arp_probe_timeout: Duration,
+    // Claim IPv4 link-local address if no lease after this long, None to
+    // disable.
+    pub(crate) link_local_timeout: Option<Duration>,
+    pub(crate) release_policy: MozimReleasePolicy,
+    // RFC 4039 two-message exchange
+    pub(crate) rapid_commit: bool,
+    // Static address to send DHCPINFORM from instead of acquiring a lease
+    pub(crate) inform: Option<Ipv4Addr>,
+    pub(crate) inform_interval: Duration,
+    pub(crate) client_id: MozimClientId,
+    pub(crate) hostname: MozimHostname,
+    // Send client FQDN option instead of host name
+    pub(crate) fqdn: Option<String>,
+    pub(crate) fqdn_update: MozimFqdnUpdate,
+    pub(crate) vendor_class: Option<String>,
+    pub(crate) user_class: Vec<String>,
+    // Option 125 data sent to server
+    pub(crate) vendor_specific: Vec<DhcpViVendorSpecific>,
+    // Option codes requested in option 55
+    pub(crate) parameter_request_list: Vec<u8>,
+    // Raw options appended to DHCPDISCOVER, DHCPREQUEST and DHCPINFORM
+    pub(crate) extra_options: Vec<DhcpOption>,
+}
+
+impl Default for MozimIfaceConfig {
+    fn default() -> Self {
+        MozimIfaceConfig {
+            options: Vec::new(),
+            applier: DhcpApplierType::default(),
+            route_metric: DHCP_DEFAULT_ROUTE_METRIC,
+            dns_priority: DEFAULT_DNS_PRIORITY,
+            script: None,
+            script_timeout: DEFAULT_SCRIPT_TIMEOUT,
+            timeout: DHCP_DEFAULT_TIMEOUT,
+            arp_probe_count: DEFAULT_ARP_PROBE_COUNT,
+            arp_probe_timeout: DHCP_DEFAULT_ARP_PROBE_TIMEOUT,
+            link_local_timeout: None,
+            release_policy: MozimReleasePolicy::default(),
+            rapid_commit: false,
+            inform: None,
+            inform_interval: DHCP_DEFAULT_INFORM_INTERVAL,
+            client_id: MozimClientId::default(),
+            hostname: MozimHostname::default(),
+            fqdn: None,
+            fqdn_update: MozimFqdnUpdate::default(),
+            vendor_class: None,
+            user_class: Vec::new(),
+            vendor_specific: Vec::new(),
+            parameter_request_list: DHCP_DEFAULT_PARAMETER_REQUEST_LIST
+                .to_vec(),
+            extra_options: Vec::new(),
+        }
+    }
+}
+
+impl MozimIfaceConfig {
+    pub(crate) fn parse(args: &[&str]) -> Result<Self, MozimError> {
+        let mut config = MozimIfaceConfig {
+            options: args.iter().map(|a| a.to_string()).collect(),
+            ..Default::default()
+        };
+        for arg in args {
+            let (key, value) = match arg.find('=') {
+                Some(i) => (&arg[..i], &arg[i + 1..]),
+                None => {
+                    return Err(MozimError::invalid_argument(format!(
+                        "Invalid option '{}', should be key=value",
+                        arg
+                    )))
+                }
+            };
+            match key {
+                "applier" => config.applier = value.parse()?,
+                "route_metric" => {
+                    config.route_metric = value.parse().map_err(|e| {
+                        MozimError::invalid_argument(format!(
+                            "Invalid route_metric '{}': {}",
+                            value, e
+                        ))
+                    })?
+                }
+                "dns_priority" => {
+                    config.dns_priority = value.parse().map_err(|e| {
+                        MozimError::invalid_argument(format!(
+                            "Invalid dns_priority '{}': {}",
+                            value, e
+                        ))
+                    })?
+                }
+                "script" => config.script = Some(value.to_string()),
+                "script_timeout" => {
+                    config.script_timeout = parse_seconds(key, value)?
+                }
+                "timeout" => config.timeout = parse_seconds(key, value)?,
+                "arp_probe_count" => {
+                    config.arp_probe_count = value.parse().map_err(|e| {
+                        MozimError::invalid_argument(format!(
+                            "Invalid arp_probe_count '{}': {}",
+                            value, e
+                        ))
+                    })?
+                }
+                "arp_probe_timeout" => {
+                    config.arp_probe_timeout = parse_seconds(key, value)?
+                }
+                "release_policy" => config.release_policy = value.parse()?,
+                "rapid_commit" => {
+                    config.rapid_commit = value.parse().map_err(|e| {
+                        MozimError::invalid_argument(format!(
+                            "Invalid rapid_commit '{}', should be true or \
+                             false: {}",
+                            value, e
+                        ))
+                    })?
+                }
+                "inform" => {
+                    config.inform = Some(value.parse().map_err(|e| {
+                        MozimError::invalid_argument(format!(
+                            "Invalid inform address '{}': {}",
+                            value, e
+                        ))
+                    })?)
+                }
+                "inform_interval" => {
+                    config.inform_interval = parse_seconds(key, value)?
+                }
+                "client_id" => config.client_id = value.parse()?,
+                "hostname" if value == "none" => {
+                    config.hostname = MozimHostname::None
+                }
+                "hostname" => {
+                    config.hostname =
+                        MozimHostname::Name(validate_hostname(key, value)?)
+                }
+                "fqdn" => config.fqdn = Some(validate_hostname(key, value)?),
+                "fqdn_update" => config.fqdn_update = value.parse()?,
+                "vendor_class" => {
+                    config.vendor_class =
+                        Some(validate_length(key, value)?.to_string())
+                }
+                // Repeat the option for more classes
+                "user_class" => config
+                    .user_class
+                    .push(validate_length(key, value)?.to_string()),
+                "parameter_request_list" => {
+                    config.parameter_request_list =
+                        parse_parameter_request_list(value)?
+                }
+                // Repeat the option for more options
+                "extra_option" => {
+                    let opt = parse_extra_option(value)?;
+                    if config
+                        .extra_options
+                        .iter()
+                        .any(|o| o.code() == opt.code())
+                    {
+                        return Err(MozimError::invalid_argument(format!(
+                            "Duplicate extra_option code {}",
+                            opt.code()
+                        )));
+                    }
+                    config.extra_options.push(opt)
+                }
+                "vendor_specific" => {
+                    config.vendor_specific.push(parse_vendor_specific(value)?)
+                }
+                "link_local_timeout" => {
+                    config.link_local_timeout = Some(parse_seconds(key, value)?)
+                }
+                _ => {
+                    return Err(MozimError::invalid_argument(format!(
+                        "Unknown option '{}'",
+                        key
+                    )))
+                }
+            }
+        }
+        Ok(config)
+    }
+}
+
+fn parse_seconds(key: &str, value: &str) -> Result<Duration, MozimError> {
+    match value.parse::<u64>() {
+        Ok(s) if s > 0 => Ok(Duration::from_secs(s)),
+        _ => Err(MozimError::invalid_argument(format!(
+            "Invalid {} '{}', should be positive seconds",
+            key, value
+        ))),
+    }
+}
+
+// RFC 1035 section 2.3.4: labels up to 63 and names up to 255 bytes.
+fn validate_hostname(key: &str, value: &str) -> Result<String, MozimError> {
+    let name = value.trim_end_matches('.');
+    if name.is_empty()
+        || name.len() > 255
+        || name.split('.').any(|l| l.is_empty() || l.len() > 63)
+    {
+        return Err(MozimError::invalid_argument(format!(
+            "Invalid {} '{}', should be domain name",
+            key, value
+        )));
+    }
+    Ok(name.to_string())
+}
+
+// Options of 1 byte length field
+fn validate_length<'a>(
+    key: &str,
+    value: &'a str,
+) -> Result<&'a str, MozimError> {
+    if value.is_empty() || value.len() > 255 {
+        Err(MozimError::invalid_argument(format!(
+            "Invalid {} '{}', should be 1 to 255 bytes",
+            key, value
+        )))
+    } else {
+        Ok(value)
+    }
+}
+
+// `<enterprise_number>:<hex>`
+fn parse_vendor_specific(
+    value: &str,
+) -> Result<DhcpViVendorSpecific, MozimError> {
+    let e = || {
+        MozimError::invalid_argument(format!(
+            "Invalid vendor_specific '{}', should be \
+             <enterprise_number>:<hex> of at most 255 bytes",
+            value
+        ))
+    };
+    let (enterprise_number, data) = value.split_once(':').ok_or_else(e)?;
+    let data = parse_hex(data).filter(|d| d.len() <= 255).ok_or_else(e)?;
+    Ok(DhcpViVendorSpecific {
+        enterprise_number: enterprise_number.parse().map_err(|_| e())?,
+        data,
+    })
+}
+
+// Comma separated unique option codes, `none` for not sending option 55.
+pub(crate) fn parse_parameter_request_list(
+    value: &str,
+) -> Result<Vec<u8>, MozimError> {
+    if value == "none" {
+        return Ok(Vec::new());
+    }
+    let mut codes = Vec::new();
+    for c in value.split(',') {
+        match c.trim().parse::<u8>() {
+            Ok(code) if code != 0 && code != u8::MAX => {
+                if codes.contains(&code) {
+                    return Err(MozimError::invalid_argument(format!(
+                        "Duplicate option code {} in \
+                         parameter_request_list '{}'",
+                        code, value
+                    )));
+                }
+                codes.push(code);
+            }
+            _ => {
+                return Err(MozimError::invalid_argument(format!(
+                    "Invalid parameter_request_list '{}', should be comma \
+                     separated option codes between 1 and 254, or none",
+                    value
+                )))
+            }
+        }
+    }
+    Ok(codes)
+}
+
+// `<code>:<hex>`, options driving the DHCP exchange are refused.
+pub(crate) fn parse_extra_option(
+    value: &str,
+) -> Result<DhcpOption, MozimError> {
+    let e = || {
+        MozimError::invalid_argument(format!(
+            "Invalid extra_option '{}', should be <code>:<hex>",
+            value
+        ))
+    };
+    let (code, data) = value.split_once(':').ok_or_else(e)?;
+    let code = code.parse::<u8>().map_err(|_| e())?;
+    let data = if data.is_empty() {
+        Vec::new()
+    } else {
+        parse_hex(data).ok_or_else(e)?
+    };
+    // Options generated by mozim itself, either to drive the exchange or
+    // from other `start` options.
+    if [
+        0,
+        u8::MAX,
+        DHCP_OPTION_HOST_NAME,
+        DHCP_OPTION_REQUESTED_IP,
+        DHCP_OPTION_MESSAGE_TYPE,
+        DHCP_OPTION_SERVER_IDENTIFIER,
+        DHCP_OPTION_PARAMETER_REQUEST_LIST,
+        DHCP_OPTION_VENDOR_CLASS,
+        DHCP_OPTION_CLIENT_IDENTIFIER,
+        DHCP_OPTION_USER_CLASS,
+        DHCP_OPTION_RAPID_COMMIT,
+        DHCP_OPTION_CLIENT_FQDN,
+        DHCP_OPTION_VI_VENDOR_SPECIFIC,
+    ]
+    .contains(&code)
+    {
+        return Err(MozimError::invalid_argument(format!(
+            "Option {} of extra_option '{}' is managed by mozim",
+            code, value
+        )));
+    }
+    Ok(DhcpOption::Raw(code, data))
+}
@@ -12,21 +12,66 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use mozim::{DhcpState, DhcpStatus, MozimError};
-use serde_json;
-use std::sync::mpsc::{Receiver, SyncSender};
+use std::net::Ipv4Addr;
+use std::sync::mpsc::{Receiver, RecvTimeoutError, SyncSender};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::{Duration, SystemTime};
+
+use mozim::{
+    gen_client_id_mac, get_iface_mac, DhcpApplierType, DhcpClientFqdn,
+    DhcpLease, DhcpLeaseApplier, DhcpState, DhcpStatus, DhcpV4ArpPacket,
+    DhcpV4Client, DhcpV4Config, DhcpV4Event, DhcpV4Message, MozimError,
+};
+
+use crate::config::{
+    MozimClientId, MozimFqdnUpdate, MozimHostname, MozimIfaceConfig,
+    MozimReleasePolicy,
+};
+use crate::dhcp_worker::{MozimDhcpSocketType, MozimDhcpWorker};
+use crate::dns::MozimDnsBackend;
+use crate::hook::{MozimHook, MozimHookReason};
+use crate::identity::MozimIdentityDb;
+use crate::lease_db::{MozimLeaseDb, MozimLeaseRecord};
+
+// How long to wait when nothing is scheduled.
+const IDLE_TIMEOUT: Duration = Duration::from_secs(3600);
 
 #[derive(Debug)]
 pub(crate) enum MozimDhcpCmd {
-    StartDhcp,
-    QueryDhcp,
-    StopDhcp,
+    Start(Box<MozimIfaceConfig>),
+    Query,
+    Stop,
+    // Daemon is exiting, honor `release_policy` of interface
+    Shutdown,
+    // BOOTREPLY received by `MozimDhcpWorker`
+    DhcpReply(DhcpV4Message),
+    // ARP packet received by `MozimDhcpWorker` of ARP socket
+    ArpPacket(DhcpV4ArpPacket),
 }
 
+// Drive the `DhcpV4Client` state machine with commands, received messages
+// and timers, doing the I/O it requests.
 pub(crate) struct MozimDhcpManager {
     iface_name: String,
     sender: SyncSender<Result<String, MozimError>>,
     recver: Receiver<MozimDhcpCmd>,
+    worker_sender: SyncSender<MozimDhcpCmd>,
+    worker: Option<MozimDhcpWorker>,
+    // ARP socket, exists only when `DhcpV4Client::needs_arp()`
+    arp_worker: Option<MozimDhcpWorker>,
+    client: Option<DhcpV4Client>,
+    config: MozimIfaceConfig,
+    applier: Option<Box<dyn DhcpLeaseApplier>>,
+    // The lease applied by `applier`
+    applied_lease: Option<DhcpLease>,
+    // Options of DHCPACK to DHCPINFORM, only DNS is applied
+    applied_options: Option<DhcpLease>,
+    // The IPv4 link-local address applied by `applier`
+    applied_link_local: Option<Ipv4Addr>,
+    dns: Arc<Mutex<Box<dyn MozimDnsBackend>>>,
+    hook: Option<MozimHook>,
+    lease_db: MozimLeaseDb,
+    identity_db: Arc<Mutex<MozimIdentityDb>>,
 }
 
 impl MozimDhcpManager {
@@ -38,56 +83,600 @@ impl MozimDhcpManager {
             );
         }
     }
+
     pub(crate) fn run(
         iface_name: String,
         sender: SyncSender<Result<String, MozimError>>,
-        // TODO: _dhcp_worker_sender will moved to DHCP worker thread
-        // where MozimDhcpManager could get DHCP status update from.
-        _dhcp_worker_sender: SyncSender<MozimDhcpCmd>,
+        // Cloned into `MozimDhcpWorker` where `MozimDhcpManager` gets the
+        // DHCP replies from.
+        worker_sender: SyncSender<MozimDhcpCmd>,
         recver: Receiver<MozimDhcpCmd>,
+        dns: Arc<Mutex<Box<dyn MozimDnsBackend>>>,
+        lease_db: MozimLeaseDb,
+        identity_db: Arc<Mutex<MozimIdentityDb>>,
     ) {
-        let mgr = MozimDhcpManager {
+        let mut mgr = MozimDhcpManager {
             iface_name,
             sender,
             recver,
+            worker_sender,
+            worker: None,
+            arp_worker: None,
+            client: None,
+            config: MozimIfaceConfig::default(),
+            applier: None,
+            applied_lease: None,
+            applied_options: None,
+            applied_link_local: None,
+            dns,
+            hook: None,
+            lease_db,
+            identity_db,
         };
         loop {
-            if let Ok(cmd) = mgr.recver.recv() {
-                match cmd {
-                    MozimDhcpCmd::StartDhcp => {
-                        mgr.reply_request(mgr.start());
+            let timeout = mgr.next_timeout();
+            match mgr.recver.recv_timeout(timeout) {
+                Ok(MozimDhcpCmd::Start(config)) => {
+                    let result = mgr.start(&config);
+                    mgr.reply_request(result);
+                }
+                Ok(MozimDhcpCmd::Query) => {
+                    mgr.reply_request(mgr.query());
+                }
+                Ok(MozimDhcpCmd::Stop) => {
+                    let result = mgr.stop();
+                    mgr.reply_request(result);
+                    break;
+                }
+                Ok(MozimDhcpCmd::Shutdown) => {
+                    mgr.shutdown();
+                    mgr.reply_request(mgr.query());
+                    break;
+                }
+                Ok(MozimDhcpCmd::DhcpReply(msg)) => {
+                    if let Some(client) = mgr.client.as_mut() {
+                        client.handle_message(&msg, SystemTime::now());
                     }
-                    MozimDhcpCmd::QueryDhcp => {
-                        mgr.reply_request(mgr.query());
+                }
+                Ok(MozimDhcpCmd::ArpPacket(packet)) => {
+                    if let Some(client) = mgr.client.as_mut() {
+                        client.handle_arp(&packet, SystemTime::now());
                     }
-                    MozimDhcpCmd::StopDhcp => {
-                        mgr.reply_request(mgr.stop());
-                        break;
-                    }
-                };
+                }
+                Err(RecvTimeoutError::Timeout) => (),
+                Err(RecvTimeoutError::Disconnected) => break,
+            };
+            // Checked after every input, as ARP or DHCP packets arriving
+            // faster than the deadline would never let `recv_timeout()`
+            // time out.
+            if let Some(client) = mgr.client.as_mut() {
+                let now = SystemTime::now();
+                if client.poll_timeout().map(|t| t <= now) == Some(true) {
+                    client.handle_timeout(now);
+                }
             }
+            mgr.process_client_output();
         }
     }
 
-    fn start(&self) -> Result<String, MozimError> {
-        dhcp_status_to_string(&DhcpStatus {
-            iface_name: self.iface_name.clone(),
-            state: DhcpState::Requesting,
-        })
+    // The `config` is ignored if DHCP is already started.
+    fn start(
+        &mut self,
+        config: &MozimIfaceConfig,
+    ) -> Result<String, MozimError> {
+        if self.client.is_none() {
+            self.config = config.clone();
+            self.applier =
+                Some(config.applier.new_applier(config.route_metric));
+            self.hook = config
+                .script
+                .as_ref()
+                .map(|p| MozimHook::new(p, config.script_timeout));
+            let mac = get_iface_mac(&self.iface_name)?;
+            let mut dhcp_config = DhcpV4Config::new(&self.iface_name, &mac);
+            dhcp_config.timeout = config.timeout;
+            dhcp_config.arp_probe_count = config.arp_probe_count;
+            dhcp_config.arp_probe_timeout = config.arp_probe_timeout;
+            dhcp_config.link_local_timeout = config.link_local_timeout;
+            dhcp_config.inform_interval = config.inform_interval;
+            dhcp_config.rapid_commit = config.rapid_commit;
+            dhcp_config.client_id = self.gen_client_id(&mac)?;
+            let (host_name, fqdn) = self.gen_host_name();
+            dhcp_config.host_name = host_name;
+            dhcp_config.fqdn = fqdn;
+            dhcp_config.vendor_class = config.vendor_class.clone();
+            dhcp_config.user_class = config.user_class.clone();
+            dhcp_config.vi_vendor_specific = config.vendor_specific.clone();
+            dhcp_config.parameter_request_list =
+                config.parameter_request_list.clone();
+            dhcp_config.extra_options = config.extra_options.clone();
+            dhcp_config.validate()?;
+            let mut client = DhcpV4Client::new(dhcp_config, gen_rand_seed());
+            // Request the address of last lease if not expired yet
+            let prev_lease = match self.lease_db.load(&self.iface_name) {
+                Ok(r) => r
+                    .and_then(|r| r.lease)
+                    .filter(|l| !l.is_expired(SystemTime::now())),
+                Err(e) => {
+                    eprintln!("{}: {}", self.iface_name, e);
+                    None
+                }
+            };
+            match (config.inform, &prev_lease) {
+                (Some(address), _) => {
+                    client.start_inform(SystemTime::now(), address)
+                }
+                (None, Some(lease)) => {
+                    client.start_init_reboot(SystemTime::now(), lease.address)
+                }
+                (None, None) => client.start(SystemTime::now()),
+            }
+            self.client = Some(client);
+            let prev_lease = prev_lease.filter(|_| config.inform.is_none());
+            self.save_lease(prev_lease.clone());
+            // Address of last lease might be kept on interface by
+            // `keep_address`, track it so it is removed if INIT-REBOOT fails.
+            self.applied_lease = prev_lease;
+            if let Err(e) = self.update_worker() {
+                self.client = None;
+                return Err(e);
+            }
+            self.process_client_output();
+        }
+        self.query()
     }
 
     fn query(&self) -> Result<String, MozimError> {
-        dhcp_status_to_string(&DhcpStatus {
-            iface_name: self.iface_name.clone(),
-            state: DhcpState::Requesting,
+        dhcp_status_to_string(&match &self.client {
+            Some(c) => c.status(SystemTime::now()),
+            None => DhcpStatus::new(&self.iface_name, DhcpState::Stopped),
         })
     }
-    fn stop(&self) -> Result<String, MozimError> {
-        dhcp_status_to_string(&DhcpStatus {
-            iface_name: self.iface_name.clone(),
-            state: DhcpState::Stopped,
+
+    fn stop(&mut self) -> Result<String, MozimError> {
+        self.release(MozimReleasePolicy::Release);
+        if let Err(e) = self.lease_db.remove(&self.iface_name) {
+            eprintln!("{}: {}", self.iface_name, e);
+        }
+        self.query()
+    }
+
+    // The lease file is kept for resuming on next daemon start.
+    fn shutdown(&mut self) {
+        let policy = self.config.release_policy;
+        self.release(policy);
+        if policy == MozimReleasePolicy::Release {
+            self.save_lease(None);
+        }
+    }
+
+    // Stop the client and handle the lease as `policy` says. The link-local
+    // address is always removed as it is never stored.
+    fn release(&mut self, policy: MozimReleasePolicy) {
+        let client = match self.client.as_mut() {
+            Some(c) => c,
+            None => return,
+        };
+        let old = match &self.applied_lease {
+            Some(l) => Some(l.clone()),
+            None => self.applied_options.clone(),
+        };
+        match policy {
+            MozimReleasePolicy::Release => {
+                client.release();
+                self.send_transmits();
+                self.run_hook(MozimHookReason::Release, None, old.as_ref());
+            }
+            MozimReleasePolicy::KeepLease => {
+                client.stop();
+                self.run_hook(MozimHookReason::Stop, None, old.as_ref());
+            }
+            MozimReleasePolicy::KeepAddress => client.stop(),
+        }
+        self.worker = None;
+        self.arp_worker = None;
+        self.client = None;
+        if policy == MozimReleasePolicy::KeepAddress {
+            self.applied_lease = None;
+            self.applied_options = None;
+        } else {
+            self.remove_applied_lease();
+            self.remove_applied_options();
+        }
+        self.remove_applied_link_local();
+        self.applier = None;
+        self.hook = None;
+    }
+
+    fn next_timeout(&self) -> Duration {
+        match self.client.as_ref().and_then(|c| c.poll_timeout()) {
+            Some(t) => t
+                .duration_since(SystemTime::now())
+                .unwrap_or(Duration::from_secs(0)),
+            None => IDLE_TIMEOUT,
+        }
+    }
+
+    // Make sure the worker is using the socket suitable for current state
+    // and transaction ID.
+    fn update_worker(&mut self) -> Result<(), MozimError> {
+        let client = match self.client.as_ref() {
+            Some(c) => c,
+            None => {
+                self.worker = None;
+                self.arp_worker = None;
+                return Ok(());
+            }
+        };
+        if !client.needs_arp() {
+            self.arp_worker = None;
+        } else if self.arp_worker.is_none() {
+            self.arp_worker = Some(MozimDhcpWorker::new(
+                &self.iface_name,
+                MozimDhcpSocketType::Arp,
+                client.xid(),
+                self.worker_sender.clone(),
+            )?);
+        }
+        let socket_type = match (client.state(), client.leased_address()) {
+            (DhcpState::Stopped, _) => {
+                self.worker = None;
+                return Ok(());
+            }
+            // Unicast reply to the static address
+            (DhcpState::Inform, _) => match client.inform_address() {
+                Some(addr) => MozimDhcpSocketType::Udp(addr),
+                None => MozimDhcpSocketType::Raw,
+            },
+            // Unicast from the leased address
+            (DhcpState::Bound, Some(addr))
+            | (DhcpState::Renewing, Some(addr)) => {
+                MozimDhcpSocketType::Udp(addr)
+            }
+            // No IPv4 address is usable yet or the server is unreachable
+            // by unicast.
+            _ => MozimDhcpSocketType::Raw,
+        };
+        if let Some(worker) = &self.worker {
+            if worker.socket_type() == socket_type {
+                return worker.set_xid(client.xid());
+            }
+        }
+        // The receiving thread of old worker keeps its socket until the
+        // next poll timeout, binding the same port again works because of
+        // SO_REUSEADDR.
+        self.worker = None;
+        let xid = client.xid();
+        match MozimDhcpWorker::new(
+            &self.iface_name,
+            socket_type,
+            xid,
+            self.worker_sender.clone(),
+        ) {
+            Ok(worker) => {
+                self.worker = Some(worker);
+                Ok(())
+            }
+            Err(e) if socket_type != MozimDhcpSocketType::Raw => {
+                eprintln!(
+                    "{}: {}, falling back to raw socket",
+                    self.iface_name, e
+                );
+                self.worker = Some(MozimDhcpWorker::new(
+                    &self.iface_name,
+                    MozimDhcpSocketType::Raw,
+                    xid,
+                    self.worker_sender.clone(),
+                )?);
+                Ok(())
+            }
+            Err(e) => Err(e),
+        }
+    }
+
+    // Send out queued messages and log lease changes.
+    fn process_client_output(&mut self) {
+        if let Err(e) = self.update_worker() {
+            eprintln!("{}: {}", self.iface_name, e);
+        }
+        self.send_transmits();
+        let client = match self.client.as_mut() {
+            Some(c) => c,
+            None => return,
+        };
+        while let Some(packet) = client.poll_arp_transmit() {
+            if let Some(worker) = &self.arp_worker {
+                if let Err(e) = worker.send_arp(&packet) {
+                    eprintln!("{}: {}", self.iface_name, e);
+                }
+            }
+        }
+        let events: Vec<DhcpV4Event> =
+            std::iter::from_fn(|| client.poll_event()).collect();
+        // Not the previous state, as ARP probing sits between INIT-REBOOT
+        // and BOUND.
+        let lease_reused = client.lease_reused();
+        for event in events {
+            match &event {
+                DhcpV4Event::LeaseAcquired(lease)
+                | DhcpV4Event::LeaseRenewed(lease)
+                | DhcpV4Event::LeaseRebound(lease) => {
+                    eprintln!(
+                        "{}: Got DHCP lease {}/{} from {}",
+                        self.iface_name,
+                        lease.address,
+                        lease.prefix_length,
+                        lease.server_id
+                    );
+                    let reason = match event {
+                        DhcpV4Event::LeaseRenewed(_) => MozimHookReason::Renew,
+                        DhcpV4Event::LeaseRebound(_) => MozimHookReason::Rebind,
+                        _ if lease_reused => MozimHookReason::Reboot,
+                        _ => MozimHookReason::Bound,
+                    };
+                    let old = self.applied_lease.clone();
+                    self.save_lease(Some(lease.clone()));
+                    self.apply_lease(lease.clone());
+                    self.run_hook(reason, Some(lease), old.as_ref());
+                }
+                DhcpV4Event::OptionsAcquired(lease) => {
+                    eprintln!(
+                        "{}: Got DHCP options from {}",
+                        self.iface_name, lease.server_id
+                    );
+                    let old = self.applied_options.clone();
+                    self.apply_options(lease.clone());
+                    self.run_hook(
+                        MozimHookReason::Inform,
+                        Some(lease),
+                        old.as_ref(),
+                    );
+                }
+                DhcpV4Event::LeaseLost(lease) => {
+                    eprintln!(
+                        "{}: DHCP lease {} lost",
+                        self.iface_name, lease.address
+                    );
+                    self.save_lease(None);
+                    self.remove_applied_lease();
+                    self.run_hook(MozimHookReason::Expire, None, Some(lease));
+                }
+                DhcpV4Event::Failed => {
+                    eprintln!(
+                        "{}: Failed to acquire DHCP lease",
+                        self.iface_name
+                    );
+                    self.run_hook(MozimHookReason::Fail, None, None);
+                }
+                DhcpV4Event::AddressDeclined(address) => {
+                    eprintln!(
+                        "{}: Address {} is used by other host, declined",
+                        self.iface_name, address
+                    );
+                }
+                DhcpV4Event::LinkLocalAcquired(address) => {
+                    eprintln!(
+                        "{}: Got IPv4 link-local address {}",
+                        self.iface_name, address
+                    );
+                    self.apply_link_local(*address);
+                }
+                DhcpV4Event::LinkLocalLost(address) => {
+                    eprintln!(
+                        "{}: IPv4 link-local address {} removed",
+                        self.iface_name, address
+                    );
+                    self.remove_applied_link_local();
+                }
+            }
+        }
+        self.remove_stale_lease();
+    }
+
+    // The lease of last run applied before start is not confirmed once
+    // client leaves INIT-REBOOT without binding, for example refused by
+    // DHCPNAK.
+    fn remove_stale_lease(&mut self) {
+        let client = match self.client.as_ref() {
+            Some(c) => c,
+            None => return,
+        };
+        if self.applied_lease.is_none()
+            || client.lease().is_some()
+            || matches!(
+                client.state(),
+                DhcpState::Rebooting | DhcpState::Probing
+            )
+        {
+            return;
+        }
+        let old = self.applied_lease.clone();
+        if let Some(lease) = &old {
+            eprintln!(
+                "{}: DHCP lease {} of last run is not confirmed",
+                self.iface_name, lease.address
+            );
+        }
+        self.save_lease(None);
+        self.remove_applied_lease();
+        self.run_hook(MozimHookReason::Expire, None, old.as_ref());
+    }
+
+    fn send_transmits(&mut self) {
+        let client = match self.client.as_mut() {
+            Some(c) => c,
+            None => return,
+        };
+        while let Some(transmit) = client.poll_transmit() {
+            if let Some(worker) = &self.worker {
+                if let Err(e) = worker.send(&transmit.msg, transmit.dst) {
+                    eprintln!("{}: {}", self.iface_name, e);
+                }
+            }
+        }
+    }
+
+    fn save_lease(&self, lease: Option<DhcpLease>) {
+        let record = MozimLeaseRecord {
+            options: self.config.options.clone(),
+            lease,
+        };
+        if let Err(e) = self.lease_db.save(&self.iface_name, &record) {
+            eprintln!("{}: {}", self.iface_name, e);
+        }
+    }
+
+    fn run_hook(
+        &self,
+        reason: MozimHookReason,
+        new: Option<&DhcpLease>,
+        old: Option<&DhcpLease>,
+    ) {
+        if let Some(hook) = &self.hook {
+            if let Err(e) = hook.run(&self.iface_name, reason, new, old) {
+                eprintln!("{}: {}", self.iface_name, e);
+            }
+        }
+    }
+
+    fn apply_lease(&mut self, lease: DhcpLease) {
+        let applier = match self.applier.as_mut() {
+            Some(a) => a,
+            None => return,
+        };
+        let result = match &self.applied_lease {
+            Some(old) => applier.update_lease(&self.iface_name, old, &lease),
+            None => applier.apply_lease(&self.iface_name, &lease),
+        };
+        if let Err(e) = result {
+            eprintln!("{}: {}", self.iface_name, e);
+        }
+        self.update_dns(&lease);
+        self.applied_lease = Some(lease);
+    }
+
+    fn remove_applied_lease(&mut self) {
+        if let (Some(applier), Some(lease)) =
+            (self.applier.as_mut(), self.applied_lease.take())
+        {
+            if let Err(e) = applier.remove_lease(&self.iface_name, &lease) {
+                eprintln!("{}: {}", self.iface_name, e);
+            }
+            self.remove_dns();
+        }
+    }
+
+    // The static address is managed by others, only DNS is applied.
+    fn apply_options(&mut self, lease: DhcpLease) {
+        self.update_dns(&lease);
+        self.applied_options = Some(lease);
+    }
+
+    fn remove_applied_options(&mut self) {
+        if self.applied_options.take().is_some() {
+            self.remove_dns();
+        }
+    }
+
+    fn update_dns(&self, lease: &DhcpLease) {
+        if self.manage_dns() {
+            if let Err(e) = self.lock_dns().update(
+                &self.iface_name,
+                self.config.dns_priority,
+                lease,
+            ) {
+                eprintln!("{}: {}", self.iface_name, e);
+            }
+        }
+    }
+
+    fn remove_dns(&self) {
+        if self.manage_dns() {
+            if let Err(e) = self.lock_dns().remove(&self.iface_name) {
+                eprintln!("{}: {}", self.iface_name, e);
+            }
+        }
+    }
+
+    fn apply_link_local(&mut self, address: Ipv4Addr) {
+        self.remove_applied_link_local();
+        if let Some(applier) = self.applier.as_mut() {
+            if let Err(e) = applier.apply_link_local(&self.iface_name, address)
+            {
+                eprintln!("{}: {}", self.iface_name, e);
+            }
+            self.applied_link_local = Some(address);
+        }
+    }
+
+    fn remove_applied_link_local(&mut self) {
+        if let (Some(applier), Some(address)) =
+            (self.applier.as_mut(), self.applied_link_local.take())
+        {
+            if let Err(e) = applier.remove_link_local(&self.iface_name, address)
+            {
+                eprintln!("{}: {}", self.iface_name, e);
+            }
+        }
+    }
+
+    // Only touch system DNS when mozim is the one applying the lease.
+    fn manage_dns(&self) -> bool {
+        self.config.applier == DhcpApplierType::Netlink
+    }
+
+    fn lock_dns(&self) -> MutexGuard<'_, Box<dyn MozimDnsBackend>> {
+        // Other thread panicked while holding the lock, the data is still
+        // consistent as every update is done in one call.
+        match self.dns.lock() {
+            Ok(r) => r,
+            Err(e) => e.into_inner(),
+        }
+    }
+
+    fn gen_client_id(
+        &self,
+        mac: &[u8; 6],
+    ) -> Result<Option<Vec<u8>>, MozimError> {
+        Ok(match &self.config.client_id {
+            MozimClientId::Duid => Some(
+                match self.identity_db.lock() {
+                    Ok(r) => r,
+                    Err(e) => e.into_inner(),
+                }
+                .client_id(&self.iface_name, mac)?,
+            ),
+            MozimClientId::Mac => Some(gen_client_id_mac(mac)),
+            MozimClientId::Raw(data) => Some(data.clone()),
+            MozimClientId::None => None,
         })
     }
+
+    // The host name or client FQDN option to send, never both.
+    fn gen_host_name(&self) -> (Option<String>, Option<DhcpClientFqdn>) {
+        let new_fqdn = |name: &str| {
+            let mut fqdn = DhcpClientFqdn::new(
+                name,
+                self.config.fqdn_update == MozimFqdnUpdate::Server,
+            );
+            fqdn.flags.no_update =
+                self.config.fqdn_update == MozimFqdnUpdate::None;
+            fqdn
+        };
+        if let Some(name) = self.config.fqdn.as_ref() {
+            return (None, Some(new_fqdn(name)));
+        }
+        match &self.config.hostname {
+            MozimHostname::Name(name) => (Some(name.clone()), None),
+            MozimHostname::None => (None, None),
+            MozimHostname::System => match get_system_hostname() {
+                Some(name) if name.contains('.') => {
+                    (None, Some(new_fqdn(&name)))
+                }
+                name => (name, None),
+            },
+        }
+    }
 }
 
 fn dhcp_status_to_string(
@@ -101,3 +690,39 @@ fn dhcp_status_to_string(
         ))),
     }
 }
+
+// None if not set or still the default of distribution.
+fn get_system_hostname() -> Option<String> {
+    let mut buf = [0u8; 256];
+    let rc = unsafe {
+        libc::gethostname(buf.as_mut_ptr() as *mut libc::c_char, buf.len())
+    };
+    if rc != 0 {
+        return None;
+    }
+    let end = buf.iter().position(|c| *c == 0).unwrap_or(buf.len());
+    let name = std::str::from_utf8(&buf[..end]).ok()?.trim_end_matches('.');
+    match name {
+        "" | "(none)" | "localhost" | "localhost.localdomain" => None,
+        _ => Some(name.to_string()),
+    }
+}
+
+fn gen_rand_seed() -> u64 {
+    let mut seed = 0u64;
+    let rc = unsafe {
+        libc::getrandom(
+            &mut seed as *mut u64 as *mut libc::c_void,
+            std::mem::size_of::<u64>(),
+            0,
+        )
+    };
+    if rc != std::mem::size_of::<u64>() as isize {
+        // Fallback to current time which is good enough for transaction ID
+        seed = SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|d| d.as_nanos() as u64)
+            .unwrap_or(0);
+    }
+    seed
+}
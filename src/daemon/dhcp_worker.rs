@@ -0,0 +1,191 @@
+// Copyright 2020 Red Hat, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::net::Ipv4Addr;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::SyncSender;
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+use mozim::{
+    DhcpV4ArpPacket, DhcpV4ArpSocket, DhcpV4Message, DhcpV4Op, DhcpV4RawSocket,
+    DhcpV4UdpSocket, MozimError,
+};
+
+use crate::dhcp_manager::MozimDhcpCmd;
+
+// How often the worker thread checks whether it should quit.
+const WORKER_POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum MozimDhcpSocketType {
+    // AF_PACKET socket for interface without IPv4 address
+    Raw,
+    // UDP socket bound to port 68 of the leased address
+    Udp(Ipv4Addr),
+    // AF_PACKET socket for ARP probing the address in DHCPACK or the
+    // IPv4 link-local address
+    Arp,
+}
+
+impl std::fmt::Display for MozimDhcpSocketType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:?}", self)
+    }
+}
+
+#[derive(Debug)]
+enum MozimDhcpSocket {
+    Raw(DhcpV4RawSocket),
+    Udp(DhcpV4UdpSocket),
+    Arp(DhcpV4ArpSocket),
+}
+
+impl MozimDhcpSocket {
+    fn send(
+        &self,
+        msg: &DhcpV4Message,
+        dst: Ipv4Addr,
+    ) -> Result<(), MozimError> {
+        match self {
+            MozimDhcpSocket::Raw(s) => s.send(msg, dst),
+            MozimDhcpSocket::Udp(s) => s.send(msg, dst),
+            MozimDhcpSocket::Arp(_) => Err(MozimError::bug(format!(
+                "Cannot send DHCP message {:?} through ARP socket",
+                msg.message_type()
+            ))),
+        }
+    }
+
+    fn send_arp(&self, packet: &DhcpV4ArpPacket) -> Result<(), MozimError> {
+        match self {
+            MozimDhcpSocket::Arp(s) => s.send(packet),
+            _ => Err(MozimError::bug(
+                "Cannot send ARP packet through DHCP socket".to_string(),
+            )),
+        }
+    }
+
+    // Return None on timeout or message is not DHCP reply.
+    fn recv(&self) -> Result<Option<MozimDhcpCmd>, MozimError> {
+        let msg = match self {
+            MozimDhcpSocket::Raw(s) => s.recv(WORKER_POLL_INTERVAL)?,
+            MozimDhcpSocket::Udp(s) => s.recv(WORKER_POLL_INTERVAL)?,
+            MozimDhcpSocket::Arp(s) => {
+                let packet = s.recv(WORKER_POLL_INTERVAL)?;
+                return Ok(packet.map(MozimDhcpCmd::ArpPacket));
+            }
+        };
+        Ok(msg
+            .filter(|m| m.op == DhcpV4Op::BootReply)
+            .map(MozimDhcpCmd::DhcpReply))
+    }
+}
+
+// Listen on DHCP client port of specified interface and forward every
+// BOOTREPLY to `MozimDhcpManager` as `MozimDhcpCmd::DhcpReply`, or every ARP
+// packet as `MozimDhcpCmd::ArpPacket` for ARP socket.
+pub(crate) struct MozimDhcpWorker {
+    socket: Arc<MozimDhcpSocket>,
+    socket_type: MozimDhcpSocketType,
+    quit: Arc<AtomicBool>,
+}
+
+impl MozimDhcpWorker {
+    pub(crate) fn new(
+        iface_name: &str,
+        socket_type: MozimDhcpSocketType,
+        xid: u32,
+        sender: SyncSender<MozimDhcpCmd>,
+    ) -> Result<Self, MozimError> {
+        let socket = Arc::new(match socket_type {
+            MozimDhcpSocketType::Raw => {
+                MozimDhcpSocket::Raw(DhcpV4RawSocket::new(iface_name, xid)?)
+            }
+            MozimDhcpSocketType::Udp(addr) => {
+                MozimDhcpSocket::Udp(DhcpV4UdpSocket::new(iface_name, addr)?)
+            }
+            MozimDhcpSocketType::Arp => {
+                MozimDhcpSocket::Arp(DhcpV4ArpSocket::new(iface_name)?)
+            }
+        });
+        let recv_socket = socket.clone();
+        let quit = Arc::new(AtomicBool::new(false));
+        let quit_clone = quit.clone();
+        thread::Builder::new()
+            .name(format!("dhcp_worker_{}", iface_name))
+            .spawn(move || recv_loop(recv_socket, sender, quit_clone))?;
+        Ok(MozimDhcpWorker {
+            socket,
+            socket_type,
+            quit,
+        })
+    }
+
+    pub(crate) fn socket_type(&self) -> MozimDhcpSocketType {
+        self.socket_type
+    }
+
+    // Update the BPF filter of raw socket to new transaction ID.
+    pub(crate) fn set_xid(&self, xid: u32) -> Result<(), MozimError> {
+        match self.socket.as_ref() {
+            MozimDhcpSocket::Raw(s) => s.set_xid(xid),
+            MozimDhcpSocket::Udp(_) | MozimDhcpSocket::Arp(_) => Ok(()),
+        }
+    }
+
+    pub(crate) fn send(
+        &self,
+        msg: &DhcpV4Message,
+        dst: Ipv4Addr,
+    ) -> Result<(), MozimError> {
+        self.socket.send(msg, dst)
+    }
+
+    pub(crate) fn send_arp(
+        &self,
+        packet: &DhcpV4ArpPacket,
+    ) -> Result<(), MozimError> {
+        self.socket.send_arp(packet)
+    }
+}
+
+impl Drop for MozimDhcpWorker {
+    fn drop(&mut self) {
+        self.quit.store(true, Ordering::Relaxed);
+    }
+}
+
+fn recv_loop(
+    socket: Arc<MozimDhcpSocket>,
+    sender: SyncSender<MozimDhcpCmd>,
+    quit: Arc<AtomicBool>,
+) {
+    while !quit.load(Ordering::Relaxed) {
+        match socket.recv() {
+            Ok(Some(cmd)) => {
+                // The manager might quit while we are waiting
+                if quit.load(Ordering::Relaxed) || sender.send(cmd).is_err() {
+                    break;
+                }
+            }
+            Ok(None) => (),
+            Err(e) => {
+                eprintln!("DHCP worker socket error: {}", e);
+                thread::sleep(WORKER_POLL_INTERVAL);
+            }
+        }
+    }
+}
@@ -0,0 +1,83 @@
+// Copyright 2020 Red Hat, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use mozim::{DhcpLease, MozimError};
+use serde_derive::{Deserialize, Serialize};
+
+use crate::config::MozimDaemonConfig;
+use crate::resolv_conf::MozimResolvConf;
+use crate::resolved::MozimResolved;
+
+// Where the DNS servers and search domains of lease go, shared by all
+// interfaces.
+pub(crate) trait MozimDnsBackend: Send + std::fmt::Debug {
+    fn update(
+        &mut self,
+        iface_name: &str,
+        priority: i32,
+        lease: &DhcpLease,
+    ) -> Result<(), MozimError>;
+
+    fn remove(&mut self, iface_name: &str) -> Result<(), MozimError>;
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum MozimDnsBackendType {
+    #[default]
+    ResolvConf,
+    SystemdResolved,
+    None,
+}
+
+pub(crate) fn new_dns_backend(
+    config: &MozimDaemonConfig,
+) -> Box<dyn MozimDnsBackend> {
+    match config.dns_backend {
+        MozimDnsBackendType::ResolvConf => {
+            Box::new(MozimResolvConf::new(&config.resolv_conf_path))
+        }
+        MozimDnsBackendType::SystemdResolved => {
+            Box::new(MozimResolved::new(config.dbus_address.as_deref()))
+        }
+        MozimDnsBackendType::None => Box::new(MozimNoneDns),
+    }
+}
+
+// Search domains of lease, fallback to domain name.
+pub(crate) fn get_lease_search(lease: &DhcpLease) -> Vec<String> {
+    if lease.search.is_empty() {
+        lease.domain.iter().cloned().collect()
+    } else {
+        lease.search.clone()
+    }
+}
+
+#[derive(Debug)]
+struct MozimNoneDns;
+
+impl MozimDnsBackend for MozimNoneDns {
+    fn update(
+        &mut self,
+        _: &str,
+        _: i32,
+        _: &DhcpLease,
+    ) -> Result<(), MozimError> {
+        Ok(())
+    }
+
+    fn remove(&mut self, _: &str) -> Result<(), MozimError> {
+        Ok(())
+    }
+}
@@ -0,0 +1,34 @@
+// Copyright 2020 Red Hat, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::path::{Path, PathBuf};
+
+const TMP_SUFFIX: &str = ".mozim-tmp";
+
+// Write to temporary file in the same folder then rename, so reader never
+// see partial file.
+pub(crate) fn write_file_atomic(
+    path: &Path,
+    content: &str,
+) -> std::io::Result<()> {
+    let tmp_path = append_suffix(path, TMP_SUFFIX);
+    std::fs::write(&tmp_path, content)
+        .and_then(|_| std::fs::rename(&tmp_path, path))
+}
+
+pub(crate) fn append_suffix(path: &Path, suffix: &str) -> PathBuf {
+    let mut path = path.as_os_str().to_os_string();
+    path.push(suffix);
+    PathBuf::from(path)
+}
@@ -0,0 +1,235 @@
+// Copyright 2020 Red Hat, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+// Run dhclient-script compatible hook on lease events.
+
+use std::io::{BufRead, BufReader};
+use std::os::unix::process::CommandExt;
+use std::process::{Child, Command, Stdio};
+use std::time::{Duration, Instant};
+
+use mozim::{join_addrs, to_hex, DhcpLease, DhcpOption, MozimError};
+
+pub(crate) const DEFAULT_SCRIPT_TIMEOUT: Duration = Duration::from_secs(30);
+const SCRIPT_POLL_INTERVAL: Duration = Duration::from_millis(50);
+// How long to keep logging stderr after script exited, background process
+// of script might hold the pipe forever.
+const SCRIPT_LOG_DRAIN_TIMEOUT: Duration = Duration::from_millis(200);
+
+// The `reason` environment variable of dhclient-script
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum MozimHookReason {
+    Bound,
+    Renew,
+    Rebind,
+    Reboot,
+    Expire,
+    Fail,
+    Stop,
+    Release,
+    // Not in dhclient-script, named after dhcpcd
+    Inform,
+}
+
+impl std::fmt::Display for MozimHookReason {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                MozimHookReason::Bound => "BOUND",
+                MozimHookReason::Renew => "RENEW",
+                MozimHookReason::Rebind => "REBIND",
+                MozimHookReason::Reboot => "REBOOT",
+                MozimHookReason::Expire => "EXPIRE",
+                MozimHookReason::Fail => "FAIL",
+                MozimHookReason::Stop => "STOP",
+                MozimHookReason::Release => "RELEASE",
+                MozimHookReason::Inform => "INFORM",
+            }
+        )
+    }
+}
+
+#[derive(Debug, Clone)]
+pub(crate) struct MozimHook {
+    path: String,
+    timeout: Duration,
+}
+
+impl MozimHook {
+    pub(crate) fn new(path: &str, timeout: Duration) -> Self {
+        MozimHook {
+            path: path.to_string(),
+            timeout,
+        }
+    }
+
+    // The stderr of script is logged line by line against the interface.
+    // Script still running after timeout is killed along with its process
+    // group.
+    pub(crate) fn run(
+        &self,
+        iface_name: &str,
+        reason: MozimHookReason,
+        new: Option<&DhcpLease>,
+        old: Option<&DhcpLease>,
+    ) -> Result<(), MozimError> {
+        let mut cmd = Command::new(&self.path);
+        cmd.process_group(0)
+            .stdin(Stdio::null())
+            .stdout(Stdio::null())
+            .stderr(Stdio::piped())
+            .env("reason", reason.to_string())
+            .env("interface", iface_name);
+        if let Some(lease) = new {
+            cmd.envs(gen_lease_envs("new_", lease));
+        }
+        if let Some(lease) = old {
+            cmd.envs(gen_lease_envs("old_", lease));
+        }
+        let mut child = cmd.spawn().map_err(|e| {
+            MozimError::hook_error(format!(
+                "Failed to execute script {}: {}",
+                self.path, e
+            ))
+        })?;
+        let logger = child.stderr.take().map(|stderr| {
+            let iface_name = iface_name.to_string();
+            std::thread::spawn(move || {
+                for line in BufReader::new(stderr).lines() {
+                    match line {
+                        Ok(l) => eprintln!("{}: {}", iface_name, l),
+                        Err(_) => break,
+                    }
+                }
+            })
+        });
+        let result = self.wait(&mut child, reason);
+        // Detach the logger if stderr is still open by background process
+        if let Some(logger) = logger {
+            let drain_deadline = Instant::now() + SCRIPT_LOG_DRAIN_TIMEOUT;
+            while !logger.is_finished() && Instant::now() < drain_deadline {
+                std::thread::sleep(SCRIPT_POLL_INTERVAL);
+            }
+            if logger.is_finished() {
+                logger.join().ok();
+            }
+        }
+        result
+    }
+
+    fn wait(
+        &self,
+        child: &mut Child,
+        reason: MozimHookReason,
+    ) -> Result<(), MozimError> {
+        let deadline = Instant::now() + self.timeout;
+        loop {
+            match child.try_wait() {
+                Ok(Some(status)) if status.success() => return Ok(()),
+                Ok(Some(status)) => {
+                    return Err(MozimError::hook_error(format!(
+                        "Script {} {} failed: {}",
+                        self.path, reason, status
+                    )))
+                }
+                Ok(None) if Instant::now() >= deadline => {
+                    // The script is leader of its own process group
+                    unsafe {
+                        libc::kill(-(child.id() as libc::pid_t), libc::SIGKILL);
+                    }
+                    child.wait().ok();
+                    return Err(MozimError::hook_error(format!(
+                        "Script {} {} killed after {} seconds timeout",
+                        self.path,
+                        reason,
+                        self.timeout.as_secs()
+                    )));
+                }
+                Ok(None) => std::thread::sleep(SCRIPT_POLL_INTERVAL),
+                Err(e) => {
+                    return Err(MozimError::hook_error(format!(
+                        "Failed to wait script {}: {}",
+                        self.path, e
+                    )))
+                }
+            }
+        }
+    }
+}
+
+// Same variable names as dhclient-script, prefixed by `new_` or `old_`.
+fn gen_lease_envs(prefix: &str, lease: &DhcpLease) -> Vec<(String, String)> {
+    let mut envs = vec![
+        ("ip_address", lease.address.to_string()),
+        ("subnet_mask", lease.subnet_mask().to_string()),
+        ("network_number", lease.network_address().to_string()),
+        ("broadcast_address", lease.broadcast_address().to_string()),
+        ("dhcp_server_identifier", lease.server_id.to_string()),
+        ("dhcp_lease_time", lease.lease_time.to_string()),
+    ];
+    if !lease.is_infinite() {
+        envs.push(("dhcp_renewal_time", lease.t1.to_string()));
+        envs.push(("dhcp_rebinding_time", lease.t2.to_string()));
+        envs.push((
+            "expiry",
+            (lease.acquired_at + u64::from(lease.lease_time)).to_string(),
+        ));
+    }
+    if !lease.gateways.is_empty() {
+        envs.push(("routers", join_addrs(&lease.gateways)));
+    }
+    if !lease.dns_servers.is_empty() {
+        envs.push(("domain_name_servers", join_addrs(&lease.dns_servers)));
+    }
+    if !lease.ntp_servers.is_empty() {
+        envs.push(("ntp_servers", join_addrs(&lease.ntp_servers)));
+    }
+    if let Some(domain) = &lease.domain {
+        envs.push(("domain_name", domain.clone()));
+    }
+    // dhclient shows domain search in fully qualified form
+    if !lease.search.is_empty() {
+        let search: Vec<String> =
+            lease.search.iter().map(|d| format!("{}.", d)).collect();
+        envs.push(("domain_search", search.join(" ")));
+    }
+    if let Some(mtu) = lease.mtu {
+        envs.push(("interface_mtu", mtu.to_string()));
+    }
+    for opt in &lease.options {
+        match opt {
+            DhcpOption::HostName(name) => {
+                envs.push(("host_name", name.clone()))
+            }
+            // dhclient shows the raw option data in decimal
+            DhcpOption::ClasslessStaticRoute(_) => envs.push((
+                "rfc3442_classless_static_routes",
+                opt.emit_data()
+                    .iter()
+                    .map(|b| b.to_string())
+                    .collect::<Vec<String>>()
+                    .join(" "),
+            )),
+            DhcpOption::VendorSpecific(data) => {
+                envs.push(("vendor_encapsulated_options", to_hex(data)))
+            }
+            _ => (),
+        }
+    }
+    envs.into_iter()
+        .map(|(k, v)| (format!("{}{}", prefix, k), v))
+        .collect()
+}
@@ -0,0 +1,226 @@
+// Copyright 2020 Red Hat, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+// Persistent DUID of the host and IAID of each interface, used to build
+// the RFC 4361 client identifier. Both outlive the lease and the NIC, so a
+// replaced NIC keeps getting the same lease.
+
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+use std::time::SystemTime;
+
+use mozim::{
+    gen_client_id_rfc4361, to_hex, DhcpDuid, DhcpDuidType, MozimError,
+};
+use serde_derive::{Deserialize, Serialize};
+
+use crate::file::write_file_atomic;
+
+const IDENTITY_FILE_NAME: &str = "identity.json";
+const MACHINE_ID_PATH: &str = "/etc/machine-id";
+// Length of random identifier in DUID-EN
+const DUID_EN_IDENTIFIER_LEN: usize = 8;
+
+// Content of `<lease_dir>/identity.json`
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Eq, Default)]
+pub(crate) struct MozimIdentity {
+    // DUID in colon separated hex
+    #[serde(default)]
+    pub(crate) duid: String,
+    // IAID of each interface name
+    #[serde(default)]
+    pub(crate) iaids: BTreeMap<String, u32>,
+}
+
+#[derive(Debug, Clone)]
+pub(crate) struct MozimIdentityDb {
+    path: PathBuf,
+    duid_type: DhcpDuidType,
+    // IANA private enterprise number, required by DUID-EN
+    enterprise_number: Option<u32>,
+}
+
+impl MozimIdentityDb {
+    pub(crate) fn new(
+        dir: &str,
+        duid_type: DhcpDuidType,
+        enterprise_number: Option<u32>,
+    ) -> Self {
+        MozimIdentityDb {
+            path: PathBuf::from(dir).join(IDENTITY_FILE_NAME),
+            duid_type,
+            enterprise_number,
+        }
+    }
+
+    // The client identifier of interface. DUID and IAID are generated and
+    // saved on first use, the DUID is regenerated only when `duid_type`
+    // changed. The `mac` is only used for generating DUID-LLT and DUID-LL.
+    pub(crate) fn client_id(
+        &self,
+        iface_name: &str,
+        mac: &[u8; 6],
+    ) -> Result<Vec<u8>, MozimError> {
+        let mut identity = self.load()?;
+        let mut changed = false;
+        let duid = match parse_hex(&identity.duid) {
+            Some(d)
+                if d.len() > 2
+                    && u16::from_be_bytes([d[0], d[1]])
+                        == self.duid_type.code() =>
+            {
+                d
+            }
+            _ => {
+                let d = self.gen_duid(mac)?.emit();
+                identity.duid = to_hex(&d);
+                changed = true;
+                d
+            }
+        };
+        let iaid = match identity.iaids.get(iface_name) {
+            Some(i) => *i,
+            None => {
+                let i = gen_iaid(iface_name, &identity.iaids);
+                identity.iaids.insert(iface_name.to_string(), i);
+                changed = true;
+                i
+            }
+        };
+        if changed {
+            self.save(&identity)?;
+        }
+        Ok(gen_client_id_rfc4361(iaid, &duid))
+    }
+
+    // Missing file means nothing generated yet.
+    pub(crate) fn load(&self) -> Result<MozimIdentity, MozimError> {
+        let content = match std::fs::read_to_string(&self.path) {
+            Ok(c) => c,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                return Ok(MozimIdentity::default())
+            }
+            Err(e) => {
+                return Err(MozimError::file_error(format!(
+                    "Failed to read identity file {}: {}",
+                    self.path.display(),
+                    e
+                )))
+            }
+        };
+        serde_json::from_str(&content).map_err(|e| {
+            MozimError::file_error(format!(
+                "Invalid identity file {}: {}",
+                self.path.display(),
+                e
+            ))
+        })
+    }
+
+    fn save(&self, identity: &MozimIdentity) -> Result<(), MozimError> {
+        let content = serde_json::to_string_pretty(identity).map_err(|e| {
+            MozimError::bug(format!(
+                "serde_json::to_string_pretty() error: {}",
+                e
+            ))
+        })?;
+        let result = match self.path.parent() {
+            Some(dir) => std::fs::create_dir_all(dir),
+            None => Ok(()),
+        };
+        result
+            .and_then(|_| write_file_atomic(&self.path, &content))
+            .map_err(|e| {
+                MozimError::file_error(format!(
+                    "Failed to write identity file {}: {}",
+                    self.path.display(),
+                    e
+                ))
+            })
+    }
+
+    fn gen_duid(&self, mac: &[u8; 6]) -> Result<DhcpDuid, MozimError> {
+        Ok(match self.duid_type {
+            DhcpDuidType::Llt => DhcpDuid::new_llt(mac, SystemTime::now()),
+            DhcpDuidType::Ll => DhcpDuid::Ll { mac: *mac },
+            DhcpDuidType::En => DhcpDuid::En {
+                enterprise_number: self.enterprise_number.ok_or_else(|| {
+                    MozimError::invalid_argument(
+                        "duid_enterprise_number is required for \
+                             DUID type en"
+                            .to_string(),
+                    )
+                })?,
+                identifier: get_random_bytes(DUID_EN_IDENTIFIER_LEN)?,
+            },
+            // Prefer machine ID, so reinstalling mozim keeps the DUID
+            DhcpDuidType::Uuid => {
+                let mut uuid = [0u8; 16];
+                match std::fs::read_to_string(MACHINE_ID_PATH)
+                    .ok()
+                    .and_then(|s| parse_hex(s.trim()))
+                    .filter(|d| d.len() == uuid.len())
+                {
+                    Some(d) => uuid.copy_from_slice(&d),
+                    None => {
+                        let data = get_random_bytes(uuid.len())?;
+                        uuid.copy_from_slice(&data);
+                        // RFC 4122 section 4.4: version 4 and variant bits
+                        uuid[6] = (uuid[6] & 0x0f) | 0x40;
+                        uuid[8] = (uuid[8] & 0x3f) | 0x80;
+                    }
+                }
+                DhcpDuid::Uuid(uuid)
+            }
+        })
+    }
+}
+
+// FNV-1a hash of interface name, so the IAID does not depend on the NIC.
+// Taken value of other interface is skipped.
+fn gen_iaid(iface_name: &str, iaids: &BTreeMap<String, u32>) -> u32 {
+    let mut iaid = iface_name.bytes().fold(0x811c_9dc5u32, |h, b| {
+        (h ^ u32::from(b)).wrapping_mul(0x0100_0193)
+    });
+    while iaids.values().any(|i| *i == iaid) {
+        iaid = iaid.wrapping_add(1);
+    }
+    iaid
+}
+
+fn get_random_bytes(len: usize) -> Result<Vec<u8>, MozimError> {
+    let mut data = vec![0u8; len];
+    let rc = unsafe {
+        libc::getrandom(data.as_mut_ptr() as *mut libc::c_void, len, 0)
+    };
+    if rc != len as isize {
+        return Err(MozimError::bug(format!(
+            "getrandom() failed: {}",
+            std::io::Error::last_os_error()
+        )));
+    }
+    Ok(data)
+}
+
+// Colon separated or plain hex string.
+pub(crate) fn parse_hex(s: &str) -> Option<Vec<u8>> {
+    let s = s.replace(':', "");
+    if s.is_empty() || !s.len().is_multiple_of(2) {
+        return None;
+    }
+    (0..s.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(s.get(i..i + 2)?, 16).ok())
+        .collect()
+}
@@ -0,0 +1,149 @@
+// Copyright 2020 Red Hat, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+// Per interface lease file surviving daemon restart.
+
+use std::path::PathBuf;
+
+use mozim::{DhcpLease, MozimError};
+use serde_derive::{Deserialize, Serialize};
+
+use crate::file::write_file_atomic;
+
+pub(crate) const DEFAULT_LEASE_DIR: &str = "/var/lib/mozim";
+const LEASE_FILE_SUFFIX: &str = ".lease";
+
+// Content of `<lease_dir>/<iface_name>.lease`
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Eq, Default)]
+pub(crate) struct MozimLeaseRecord {
+    // Options of `start` command, used to restart DHCP on daemon start
+    #[serde(default)]
+    pub(crate) options: Vec<String>,
+    // The last lease, None if lost
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub(crate) lease: Option<DhcpLease>,
+}
+
+#[derive(Debug, Clone)]
+pub(crate) struct MozimLeaseDb {
+    dir: PathBuf,
+}
+
+impl MozimLeaseDb {
+    pub(crate) fn new(dir: &str) -> Self {
+        MozimLeaseDb {
+            dir: PathBuf::from(dir),
+        }
+    }
+
+    fn path(&self, iface_name: &str) -> PathBuf {
+        self.dir
+            .join(format!("{}{}", iface_name, LEASE_FILE_SUFFIX))
+    }
+
+    // Missing file means no record.
+    pub(crate) fn load(
+        &self,
+        iface_name: &str,
+    ) -> Result<Option<MozimLeaseRecord>, MozimError> {
+        let path = self.path(iface_name);
+        let content = match std::fs::read_to_string(&path) {
+            Ok(c) => c,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                return Ok(None)
+            }
+            Err(e) => {
+                return Err(MozimError::file_error(format!(
+                    "Failed to read lease file {}: {}",
+                    path.display(),
+                    e
+                )))
+            }
+        };
+        serde_json::from_str(&content).map(Some).map_err(|e| {
+            MozimError::file_error(format!(
+                "Invalid lease file {}: {}",
+                path.display(),
+                e
+            ))
+        })
+    }
+
+    // Written to temporary file then renamed, so a crash never leaves
+    // partial file behind.
+    pub(crate) fn save(
+        &self,
+        iface_name: &str,
+        record: &MozimLeaseRecord,
+    ) -> Result<(), MozimError> {
+        let path = self.path(iface_name);
+        let content = serde_json::to_string_pretty(record).map_err(|e| {
+            MozimError::bug(format!(
+                "serde_json::to_string_pretty() error: {}",
+                e
+            ))
+        })?;
+        std::fs::create_dir_all(&self.dir)
+            .and_then(|_| write_file_atomic(&path, &content))
+            .map_err(|e| {
+                MozimError::file_error(format!(
+                    "Failed to write lease file {}: {}",
+                    path.display(),
+                    e
+                ))
+            })
+    }
+
+    pub(crate) fn remove(&self, iface_name: &str) -> Result<(), MozimError> {
+        let path = self.path(iface_name);
+        match std::fs::remove_file(&path) {
+            Ok(()) => Ok(()),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(()),
+            Err(e) => Err(MozimError::file_error(format!(
+                "Failed to remove lease file {}: {}",
+                path.display(),
+                e
+            ))),
+        }
+    }
+
+    // Interface names of all lease files, sorted.
+    pub(crate) fn list(&self) -> Result<Vec<String>, MozimError> {
+        let entries = match std::fs::read_dir(&self.dir) {
+            Ok(e) => e,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                return Ok(Vec::new())
+            }
+            Err(e) => {
+                return Err(MozimError::file_error(format!(
+                    "Failed to read lease folder {}: {}",
+                    self.dir.display(),
+                    e
+                )))
+            }
+        };
+        let mut iface_names: Vec<String> = entries
+            .flatten()
+            .filter_map(|entry| {
+                entry
+                    .file_name()
+                    .to_str()
+                    .and_then(|n| n.strip_suffix(LEASE_FILE_SUFFIX))
+                    .map(|n| n.to_string())
+            })
+            .collect();
+        iface_names.sort();
+        Ok(iface_names)
+    }
+}
@@ -12,19 +12,29 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-mod threads_manager;
-mod thread_manager;
+mod config;
 mod dhcp_manager;
+mod dhcp_worker;
+mod dns;
+mod file;
+mod hook;
+mod identity;
+mod lease_db;
+mod resolv_conf;
+mod resolved;
+mod thread_manager;
+mod threads_manager;
 
+use crate::config::{MozimDaemonConfig, MozimIfaceConfig};
 use crate::threads_manager::MozimThreadsManager;
 use mozim::{
     ipc_bind, ipc_recv, ipc_send, DhcpStatus, ErrorKind, MozimError,
     MozimResult,
 };
-use serde_json;
 use std::convert::TryFrom;
 use std::convert::TryInto;
 use tokio::net::UnixStream;
+use tokio::signal::unix::{signal, SignalKind};
 
 #[derive(Debug, Clone)]
 enum MozimAction {
@@ -72,20 +82,37 @@ impl std::fmt::Display for MozimCmd {
 
 #[tokio::main]
 async fn main() {
+    let config = match MozimDaemonConfig::load() {
+        Ok(c) => c,
+        Err(e) => {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+    };
     let listener = ipc_bind().unwrap();
-    let mut threads_mgr = MozimThreadsManager::new();
+    let mut threads_mgr = MozimThreadsManager::new(&config);
+    threads_mgr.resume();
+    let mut sigterm = signal(SignalKind::terminate()).unwrap();
+    let mut sigint = signal(SignalKind::interrupt()).unwrap();
 
     loop {
-        match listener.accept().await {
-            Ok((mut stream, _)) => {
-                process_socket_connection(&mut threads_mgr, &mut stream).await;
-                if let Err(e) = stream.shutdown(std::net::Shutdown::Both) {
-                    eprintln!("Faield to shutdown stream {}", e);
+        tokio::select! {
+            result = listener.accept() => match result {
+                Ok((mut stream, _)) => {
+                    process_socket_connection(&mut threads_mgr, &mut stream)
+                        .await;
+                    if let Err(e) = stream.shutdown(std::net::Shutdown::Both) {
+                        eprintln!("Faield to shutdown stream {}", e);
+                    }
                 }
-            }
-            Err(e) => eprintln!("IPC error {}", e),
+                Err(e) => eprintln!("IPC error {}", e),
+            },
+            _ = sigterm.recv() => break,
+            _ = sigint.recv() => break,
         }
     }
+    eprintln!("Shutting down");
+    threads_mgr.shutdown();
 }
 
 async fn process_socket_connection(
@@ -144,14 +171,15 @@ fn process_cmd_start(
     cmd: &MozimCmd,
     threads_mgr: &mut MozimThreadsManager,
 ) -> Result<MozimResult, MozimError> {
-    let iface_name = &cmd.arguments;
-    if iface_name.len() == 0 {
+    let args: Vec<&str> = cmd.arguments.split_whitespace().collect();
+    if args.is_empty() {
         Err(MozimError {
             kind: ErrorKind::InvalidIpcCommand,
-            msg: format!("start command missing interface name"),
+            msg: "start command missing interface name".to_string(),
         })
     } else {
-        dhcp_status_to_mozim_result(&threads_mgr.start_dhcp(iface_name)?)
+        let config = MozimIfaceConfig::parse(&args[1..])?;
+        dhcp_status_to_mozim_result(&threads_mgr.start_dhcp(args[0], config)?)
     }
 }
 
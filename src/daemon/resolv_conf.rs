@@ -0,0 +1,192 @@
+// Copyright 2020 Red Hat, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::HashMap;
+use std::net::Ipv4Addr;
+use std::path::PathBuf;
+
+use mozim::{DhcpLease, MozimError};
+
+use crate::dns::{get_lease_search, MozimDnsBackend};
+use crate::file::{append_suffix, write_file_atomic};
+
+pub(crate) const DEFAULT_RESOLV_CONF_PATH: &str = "/etc/resolv.conf";
+const BACKUP_SUFFIX: &str = ".mozim-backup";
+const HEADER: &str = "# Generated by mozim";
+pub(crate) const DEFAULT_DNS_PRIORITY: i32 = 100;
+
+#[derive(Debug, Clone)]
+struct MozimDnsEntry {
+    priority: i32,
+    servers: Vec<Ipv4Addr>,
+    search: Vec<String>,
+}
+
+// Merge the DNS servers and search domains of all bound interfaces into
+// resolv.conf. Interfaces are sorted by priority(smaller first) and then by
+// name. The original file is backed up on first write and restored when no
+// interface with DNS server is left.
+#[derive(Debug)]
+pub(crate) struct MozimResolvConf {
+    path: PathBuf,
+    entries: HashMap<String, MozimDnsEntry>,
+}
+
+impl MozimResolvConf {
+    pub(crate) fn new(path: &str) -> Self {
+        MozimResolvConf {
+            path: PathBuf::from(path),
+            entries: HashMap::new(),
+        }
+    }
+
+    fn backup_path(&self) -> PathBuf {
+        append_suffix(&self.path, BACKUP_SUFFIX)
+    }
+
+    fn gen_content(&self) -> String {
+        let mut entries: Vec<(&String, &MozimDnsEntry)> =
+            self.entries.iter().collect();
+        entries.sort_by(|a, b| (a.1.priority, a.0).cmp(&(b.1.priority, b.0)));
+        let mut servers: Vec<Ipv4Addr> = Vec::new();
+        let mut search: Vec<&str> = Vec::new();
+        for (_, entry) in entries {
+            for server in &entry.servers {
+                if !servers.contains(server) {
+                    servers.push(*server);
+                }
+            }
+            for domain in &entry.search {
+                if !search.contains(&domain.as_str()) {
+                    search.push(domain);
+                }
+            }
+        }
+        let mut content = format!(
+            "{}, original file is saved as {}\n",
+            HEADER,
+            self.backup_path().display()
+        );
+        if !search.is_empty() {
+            content += &format!("search {}\n", search.join(" "));
+        }
+        for server in servers {
+            content += &format!("nameserver {}\n", server);
+        }
+        content
+    }
+
+    // Empty nameserver list would break the name resolution of host, keep
+    // the original file instead.
+    fn sync(&self) -> Result<(), MozimError> {
+        if self.entries.values().all(|e| e.servers.is_empty()) {
+            self.restore()
+        } else {
+            self.write()
+        }
+    }
+
+    fn write(&self) -> Result<(), MozimError> {
+        self.backup()?;
+        write_file_atomic(&self.path, &self.gen_content()).map_err(|e| {
+            MozimError::file_error(format!(
+                "Failed to write {}: {}",
+                self.path.display(),
+                e
+            ))
+        })
+    }
+
+    // Keep the original file, symbolic link is preserved. Existing backup
+    // is from previous run, should not be overridden.
+    fn backup(&self) -> Result<(), MozimError> {
+        let backup_path = self.backup_path();
+        if backup_path.symlink_metadata().is_ok() || self.is_generated() {
+            return Ok(());
+        }
+        let result = match self.path.symlink_metadata() {
+            Err(_) => return Ok(()),
+            Ok(m) if m.file_type().is_symlink() => {
+                std::fs::read_link(&self.path)
+                    .and_then(|t| std::os::unix::fs::symlink(t, &backup_path))
+            }
+            Ok(_) => std::fs::copy(&self.path, &backup_path).map(|_| ()),
+        };
+        result.map_err(|e| {
+            MozimError::file_error(format!(
+                "Failed to backup {} to {}: {}",
+                self.path.display(),
+                backup_path.display(),
+                e
+            ))
+        })
+    }
+
+    // Move the backup back, or remove our file if there was no original.
+    fn restore(&self) -> Result<(), MozimError> {
+        let backup_path = self.backup_path();
+        let result = if backup_path.symlink_metadata().is_ok() {
+            std::fs::rename(&backup_path, &self.path)
+        } else if self.is_generated() {
+            std::fs::remove_file(&self.path)
+        } else {
+            Ok(())
+        };
+        result.map_err(|e| {
+            MozimError::file_error(format!(
+                "Failed to restore {}: {}",
+                self.path.display(),
+                e
+            ))
+        })
+    }
+
+    fn is_generated(&self) -> bool {
+        match std::fs::symlink_metadata(&self.path) {
+            Ok(m) if m.file_type().is_file() => {
+                std::fs::read_to_string(&self.path)
+                    .map(|c| c.starts_with(HEADER))
+                    .unwrap_or(false)
+            }
+            _ => false,
+        }
+    }
+}
+
+impl MozimDnsBackend for MozimResolvConf {
+    fn update(
+        &mut self,
+        iface_name: &str,
+        priority: i32,
+        lease: &DhcpLease,
+    ) -> Result<(), MozimError> {
+        self.entries.insert(
+            iface_name.to_string(),
+            MozimDnsEntry {
+                priority,
+                servers: lease.dns_servers.clone(),
+                search: get_lease_search(lease),
+            },
+        );
+        self.sync()
+    }
+
+    fn remove(&mut self, iface_name: &str) -> Result<(), MozimError> {
+        if self.entries.remove(iface_name).is_some() {
+            self.sync()
+        } else {
+            Ok(())
+        }
+    }
+}
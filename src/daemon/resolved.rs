@@ -0,0 +1,146 @@
+// Copyright 2020 Red Hat, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+// Per link DNS configuration through the D-Bus API of systemd-resolved.
+
+use std::net::Ipv4Addr;
+
+use mozim::{get_iface_index, DhcpLease, MozimError};
+use zbus::blocking::{Connection, ConnectionBuilder};
+
+use crate::dns::{get_lease_search, MozimDnsBackend};
+
+const RESOLVED_BUS_NAME: &str = "org.freedesktop.resolve1";
+const RESOLVED_OBJ_PATH: &str = "/org/freedesktop/resolve1";
+const RESOLVED_MANAGER_IFACE: &str = "org.freedesktop.resolve1.Manager";
+
+#[derive(Debug)]
+pub(crate) struct MozimResolved {
+    // None for system bus
+    address: Option<String>,
+    connection: Option<Connection>,
+}
+
+impl MozimResolved {
+    pub(crate) fn new(address: Option<&str>) -> Self {
+        MozimResolved {
+            address: address.map(|a| a.to_string()),
+            connection: None,
+        }
+    }
+
+    pub(crate) fn set_link_dns(
+        &mut self,
+        iface_index: u32,
+        servers: &[Ipv4Addr],
+    ) -> Result<(), MozimError> {
+        let servers: Vec<(i32, Vec<u8>)> = servers
+            .iter()
+            .map(|s| (libc::AF_INET, s.octets().to_vec()))
+            .collect();
+        self.call("SetLinkDNS", &(iface_index as i32, servers))
+    }
+
+    // The domains are used as search domains, not routing only.
+    pub(crate) fn set_link_domains(
+        &mut self,
+        iface_index: u32,
+        domains: &[String],
+    ) -> Result<(), MozimError> {
+        let domains: Vec<(&str, bool)> =
+            domains.iter().map(|d| (d.as_str(), false)).collect();
+        self.call("SetLinkDomains", &(iface_index as i32, domains))
+    }
+
+    pub(crate) fn revert_link(
+        &mut self,
+        iface_index: u32,
+    ) -> Result<(), MozimError> {
+        self.call("RevertLink", &(iface_index as i32))
+    }
+
+    fn call<B>(&mut self, method: &str, body: &B) -> Result<(), MozimError>
+    where
+        B: serde::ser::Serialize + zbus::zvariant::DynamicType,
+    {
+        let result = self.connect().and_then(|c| {
+            c.call_method(
+                Some(RESOLVED_BUS_NAME),
+                RESOLVED_OBJ_PATH,
+                Some(RESOLVED_MANAGER_IFACE),
+                method,
+                body,
+            )
+            .map(|_| ())
+            .map_err(|e| {
+                MozimError::dbus_error(format!(
+                    "Failed to invoke {}.{}: {}",
+                    RESOLVED_MANAGER_IFACE, method, e
+                ))
+            })
+        });
+        // Reconnect on next call in case systemd-resolved or the bus
+        // restarted.
+        if result.is_err() {
+            self.connection = None;
+        }
+        result
+    }
+
+    fn connect(&mut self) -> Result<&Connection, MozimError> {
+        if self.connection.is_none() {
+            let connection = match &self.address {
+                Some(address) => ConnectionBuilder::address(address.as_str())
+                    .and_then(|b| b.build()),
+                None => Connection::system(),
+            }
+            .map_err(|e| {
+                MozimError::dbus_error(format!(
+                    "Failed to connect to D-Bus {}: {}",
+                    self.address.as_deref().unwrap_or("system bus"),
+                    e
+                ))
+            })?;
+            self.connection = Some(connection);
+        }
+        match &self.connection {
+            Some(c) => Ok(c),
+            None => Err(MozimError::bug(
+                "MozimResolved::connect(): connection is None".to_string(),
+            )),
+        }
+    }
+}
+
+// systemd-resolved sorts links by itself, the priority is not used.
+impl MozimDnsBackend for MozimResolved {
+    fn update(
+        &mut self,
+        iface_name: &str,
+        _priority: i32,
+        lease: &DhcpLease,
+    ) -> Result<(), MozimError> {
+        let iface_index = get_iface_index(iface_name)?;
+        self.set_link_dns(iface_index, &lease.dns_servers)?;
+        self.set_link_domains(iface_index, &get_lease_search(lease))
+    }
+
+    fn remove(&mut self, iface_name: &str) -> Result<(), MozimError> {
+        // The interface might be gone, systemd-resolved forgets it then.
+        match get_iface_index(iface_name) {
+            Ok(iface_index) => self.revert_link(iface_index),
+            Err(_) => Ok(()),
+        }
+    }
+}
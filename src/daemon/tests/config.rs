@@ -0,0 +1,160 @@
+// Copyright 2020 Red Hat, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+#[allow(dead_code)]
+#[path = "../config.rs"]
+mod config;
+#[allow(dead_code)]
+#[path = "../dns.rs"]
+mod dns;
+#[allow(dead_code)]
+#[path = "../file.rs"]
+mod file;
+#[allow(dead_code)]
+#[path = "../hook.rs"]
+mod hook;
+#[allow(dead_code)]
+#[path = "../identity.rs"]
+mod identity;
+#[allow(dead_code)]
+#[path = "../lease_db.rs"]
+mod lease_db;
+#[allow(dead_code)]
+#[path = "../resolv_conf.rs"]
+mod resolv_conf;
+#[allow(dead_code)]
+#[path = "../resolved.rs"]
+mod resolved;
+
+use mozim::{DhcpApplierType, DhcpOption, DHCP_DEFAULT_PARAMETER_REQUEST_LIST};
+
+use crate::config::{
+    parse_extra_option, parse_parameter_request_list, MozimIfaceConfig,
+};
+
+#[test]
+fn test_parse_extra_option() {
+    assert_eq!(
+        parse_extra_option("224:01ab").unwrap(),
+        DhcpOption::Raw(224, vec![0x01, 0xab])
+    );
+    assert_eq!(
+        parse_extra_option("225:").unwrap(),
+        DhcpOption::Raw(225, Vec::new())
+    );
+}
+
+#[test]
+fn test_parse_extra_option_invalid() {
+    for value in ["224", "224:0g", "224:abc", "256:01", "abc:01", ":01"] {
+        assert!(parse_extra_option(value).is_err(), "{}", value);
+    }
+}
+
+#[test]
+fn test_parse_extra_option_managed() {
+    for code in [0, 12, 50, 53, 54, 55, 60, 61, 77, 80, 81, 125, 255] {
+        assert!(
+            parse_extra_option(&format!("{}:01", code)).is_err(),
+            "{}",
+            code
+        );
+    }
+    // Option 124 is not sent by mozim
+    assert_eq!(
+        parse_extra_option("124:01").unwrap(),
+        DhcpOption::Raw(124, vec![0x01])
+    );
+}
+
+#[test]
+fn test_parse_parameter_request_list() {
+    assert_eq!(
+        parse_parameter_request_list("1, 3,6").unwrap(),
+        vec![1, 3, 6]
+    );
+    assert!(parse_parameter_request_list("none").unwrap().is_empty());
+}
+
+#[test]
+fn test_parse_parameter_request_list_invalid() {
+    for value in ["", "1,", "0", "255", "256", "1,a", "1,3,1"] {
+        assert!(parse_parameter_request_list(value).is_err(), "{}", value);
+    }
+}
+
+#[test]
+fn test_parse_iface_config() {
+    let args = [
+        "applier=none",
+        "route_metric=100",
+        "timeout=30",
+        "user_class=a",
+        "user_class=b",
+        "extra_option=224:01",
+        "extra_option=225:02",
+        "vendor_specific=4491:0102",
+    ];
+    let config = MozimIfaceConfig::parse(&args).unwrap();
+    assert_eq!(config.options, args.to_vec());
+    assert_eq!(config.applier, DhcpApplierType::None);
+    assert_eq!(config.route_metric, 100);
+    assert_eq!(config.timeout, std::time::Duration::from_secs(30));
+    assert_eq!(config.user_class, vec!["a".to_string(), "b".to_string()]);
+    assert_eq!(
+        config.extra_options,
+        vec![DhcpOption::Raw(224, vec![1]), DhcpOption::Raw(225, vec![2])]
+    );
+    assert_eq!(config.vendor_specific.len(), 1);
+    assert_eq!(config.vendor_specific[0].enterprise_number, 4491);
+    assert_eq!(config.vendor_specific[0].data, vec![1, 2]);
+    assert_eq!(
+        config.parameter_request_list,
+        DHCP_DEFAULT_PARAMETER_REQUEST_LIST.to_vec()
+    );
+}
+
+#[test]
+fn test_parse_iface_config_default() {
+    assert_eq!(
+        MozimIfaceConfig::parse(&[]).unwrap(),
+        MozimIfaceConfig::default()
+    );
+}
+
+#[test]
+fn test_parse_iface_config_invalid() {
+    for arg in [
+        "applier",
+        "no_such_option=1",
+        "timeout=0",
+        "route_metric=-1",
+        "extra_option=224:zz",
+        "extra_option=53:01",
+        "parameter_request_list=1,1",
+        "vendor_specific=4491",
+        "hostname=a..b",
+    ] {
+        assert!(MozimIfaceConfig::parse(&[arg]).is_err(), "{}", arg);
+    }
+}
+
+#[test]
+fn test_parse_iface_config_duplicate_extra_option() {
+    assert!(MozimIfaceConfig::parse(&[
+        "extra_option=224:01",
+        "extra_option=224:02"
+    ])
+    .is_err());
+}
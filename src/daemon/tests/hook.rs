@@ -0,0 +1,148 @@
+// Copyright 2020 Red Hat, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+#[allow(dead_code)]
+#[path = "../hook.rs"]
+mod hook;
+
+use std::net::Ipv4Addr;
+use std::os::unix::fs::PermissionsExt;
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+
+use mozim::{DhcpClasslessRoute, DhcpLease, DhcpOption, ErrorKind};
+
+use crate::hook::{MozimHook, MozimHookReason};
+
+fn gen_script(name: &str, content: &str) -> PathBuf {
+    let path = std::env::temp_dir().join(format!(
+        "mozim-hook-test-{}-{}",
+        std::process::id(),
+        name
+    ));
+    std::fs::write(&path, format!("#!/bin/sh\n{}", content)).unwrap();
+    std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o755))
+        .unwrap();
+    path
+}
+
+fn gen_lease(address: Ipv4Addr) -> DhcpLease {
+    let route = DhcpClasslessRoute {
+        dst: Ipv4Addr::new(198, 51, 100, 0),
+        prefix_length: 24,
+        gateway: Ipv4Addr::new(192, 0, 2, 1),
+    };
+    DhcpLease {
+        address,
+        prefix_length: 24,
+        gateways: vec![Ipv4Addr::new(192, 0, 2, 1)],
+        dns_servers: vec![Ipv4Addr::new(192, 0, 2, 2)],
+        domain: Some("example.org".to_string()),
+        search: vec!["example.org".to_string(), "example.com".to_string()],
+        server_id: Ipv4Addr::new(192, 0, 2, 1),
+        lease_time: 1000,
+        t1: 500,
+        t2: 875,
+        acquired_at: 1_600_000_000,
+        remaining: None,
+        mtu: None,
+        ntp_servers: Vec::new(),
+        classless_routes: vec![route],
+        options: vec![
+            DhcpOption::HostName("host1".to_string()),
+            DhcpOption::ClasslessStaticRoute(vec![route]),
+        ],
+    }
+}
+
+#[test]
+fn test_hook_envs() {
+    let out_path = gen_script("envs.out", "");
+    let script = gen_script(
+        "envs",
+        &format!(
+            "env | grep -E '^(reason|interface|new_|old_)' | sort > {}\n",
+            out_path.display()
+        ),
+    );
+    let hook = MozimHook::new(script.to_str().unwrap(), Duration::from_secs(5));
+    hook.run(
+        "eth1",
+        MozimHookReason::Renew,
+        Some(&gen_lease(Ipv4Addr::new(192, 0, 2, 100))),
+        Some(&gen_lease(Ipv4Addr::new(192, 0, 2, 99))),
+    )
+    .unwrap();
+    let envs = std::fs::read_to_string(&out_path).unwrap();
+    std::fs::remove_file(&script).ok();
+    std::fs::remove_file(&out_path).ok();
+    for expected in [
+        "reason=RENEW",
+        "interface=eth1",
+        "new_ip_address=192.0.2.100",
+        "new_subnet_mask=255.255.255.0",
+        "new_network_number=192.0.2.0",
+        "new_broadcast_address=192.0.2.255",
+        "new_routers=192.0.2.1",
+        "new_domain_name_servers=192.0.2.2",
+        "new_domain_name=example.org",
+        "new_domain_search=example.org. example.com.",
+        "new_dhcp_lease_time=1000",
+        "new_dhcp_renewal_time=500",
+        "new_dhcp_rebinding_time=875",
+        "new_expiry=1600001000",
+        "new_host_name=host1",
+        "new_rfc3442_classless_static_routes=24 198 51 100 192 0 2 1",
+        "old_ip_address=192.0.2.99",
+    ] {
+        assert!(
+            envs.lines().any(|l| l == expected),
+            "{} not found in:\n{}",
+            expected,
+            envs
+        );
+    }
+}
+
+#[test]
+fn test_hook_timeout() {
+    let script = gen_script("timeout", "exec sleep 10\n");
+    let hook = MozimHook::new(script.to_str().unwrap(), Duration::from_secs(1));
+    let start = Instant::now();
+    let result = hook.run("eth1", MozimHookReason::Fail, None, None);
+    std::fs::remove_file(&script).ok();
+    assert!(matches!(result.unwrap_err().kind, ErrorKind::HookError));
+    assert!(start.elapsed() < Duration::from_secs(5));
+}
+
+#[test]
+fn test_hook_background_process() {
+    // Background process keeps the stderr pipe open after script exited
+    let script = gen_script("background", "sleep 100 &\n");
+    let hook = MozimHook::new(script.to_str().unwrap(), Duration::from_secs(2));
+    let start = Instant::now();
+    let result = hook.run("eth1", MozimHookReason::Bound, None, None);
+    std::fs::remove_file(&script).ok();
+    assert!(result.is_ok());
+    assert!(start.elapsed() < Duration::from_secs(2));
+
+    // Background process is killed along with timed out script
+    let script = gen_script("background_timeout", "sleep 100 &\nsleep 10\n");
+    let hook = MozimHook::new(script.to_str().unwrap(), Duration::from_secs(1));
+    let start = Instant::now();
+    let result = hook.run("eth1", MozimHookReason::Bound, None, None);
+    std::fs::remove_file(&script).ok();
+    assert!(matches!(result.unwrap_err().kind, ErrorKind::HookError));
+    assert!(start.elapsed() < Duration::from_secs(2));
+}
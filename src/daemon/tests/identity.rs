@@ -0,0 +1,62 @@
+// Copyright 2020 Red Hat, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+#[allow(dead_code)]
+#[path = "../file.rs"]
+mod file;
+#[allow(dead_code)]
+#[path = "../identity.rs"]
+mod identity;
+
+use mozim::{to_hex, DhcpDuidType};
+
+use crate::identity::MozimIdentityDb;
+
+const TEST_MAC: [u8; 6] = [0x00, 0x23, 0x45, 0x67, 0x89, 0x1a];
+const OTHER_MAC: [u8; 6] = [0x00, 0x23, 0x45, 0x67, 0x89, 0x1b];
+
+#[test]
+fn test_identity_persistent_client_id() {
+    let dir = std::env::temp_dir()
+        .join(format!("mozim-identity-test-{}", std::process::id()));
+    let dir = dir.to_str().unwrap();
+    let db = MozimIdentityDb::new(dir, DhcpDuidType::Ll, None);
+    let eth0_id = db.client_id("eth0", &TEST_MAC).unwrap();
+    assert_eq!(eth0_id[0], 0xff);
+    assert_eq!(
+        &eth0_id[5..],
+        &[0x00, 0x03, 0x00, 0x01, 0x00, 0x23, 0x45, 0x67, 0x89, 0x1a]
+    );
+
+    // Same DUID and IAID after NIC replaced and daemon restarted
+    let db = MozimIdentityDb::new(dir, DhcpDuidType::Ll, None);
+    assert_eq!(db.client_id("eth0", &OTHER_MAC).unwrap(), eth0_id);
+    let eth1_id = db.client_id("eth1", &OTHER_MAC).unwrap();
+    assert_ne!(&eth1_id[1..5], &eth0_id[1..5]);
+    assert_eq!(&eth1_id[5..], &eth0_id[5..]);
+    let identity = db.load().unwrap();
+    assert_eq!(identity.duid, to_hex(&eth0_id[5..]));
+    assert_eq!(identity.iaids.len(), 2);
+
+    // DUID regenerated on type change, IAID kept
+    let db = MozimIdentityDb::new(dir, DhcpDuidType::Uuid, None);
+    let uuid_id = db.client_id("eth0", &TEST_MAC).unwrap();
+    assert_eq!(&uuid_id[..5], &eth0_id[..5]);
+    assert_eq!(&uuid_id[5..7], &[0x00, 0x04]);
+    assert_eq!(uuid_id.len(), 5 + 18);
+
+    let db = MozimIdentityDb::new(dir, DhcpDuidType::En, None);
+    assert!(db.client_id("eth0", &TEST_MAC).is_err());
+    std::fs::remove_dir_all(dir).ok();
+}
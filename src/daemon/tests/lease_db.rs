@@ -0,0 +1,66 @@
+// Copyright 2020 Red Hat, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+#[allow(dead_code)]
+#[path = "../file.rs"]
+mod file;
+#[allow(dead_code)]
+#[path = "../lease_db.rs"]
+mod lease_db;
+
+use std::net::Ipv4Addr;
+
+use mozim::DhcpLease;
+
+use crate::lease_db::{MozimLeaseDb, MozimLeaseRecord};
+
+#[test]
+fn test_lease_db_save_load_remove() {
+    let dir = std::env::temp_dir()
+        .join(format!("mozim-lease-db-test-{}", std::process::id()));
+    let db = MozimLeaseDb::new(dir.to_str().unwrap());
+    assert_eq!(db.list().unwrap(), Vec::<String>::new());
+    assert_eq!(db.load("eth1").unwrap(), None);
+
+    let record = MozimLeaseRecord {
+        options: vec!["applier=none".to_string()],
+        lease: Some(DhcpLease {
+            address: Ipv4Addr::new(192, 0, 2, 100),
+            prefix_length: 24,
+            gateways: vec![Ipv4Addr::new(192, 0, 2, 1)],
+            dns_servers: Vec::new(),
+            domain: None,
+            search: Vec::new(),
+            server_id: Ipv4Addr::new(192, 0, 2, 1),
+            lease_time: 1000,
+            t1: 500,
+            t2: 875,
+            acquired_at: 1_600_000_000,
+            remaining: None,
+            mtu: None,
+            ntp_servers: Vec::new(),
+            classless_routes: Vec::new(),
+            options: Vec::new(),
+        }),
+    };
+    db.save("eth1", &record).unwrap();
+    db.save("eth0", &MozimLeaseRecord::default()).unwrap();
+    assert_eq!(db.list().unwrap(), vec!["eth0", "eth1"]);
+    assert_eq!(db.load("eth1").unwrap(), Some(record));
+
+    db.remove("eth1").unwrap();
+    db.remove("eth1").unwrap();
+    assert_eq!(db.list().unwrap(), vec!["eth0"]);
+    std::fs::remove_dir_all(&dir).ok();
+}
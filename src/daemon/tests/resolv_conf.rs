@@ -0,0 +1,224 @@
+// Copyright 2020 Red Hat, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+#[allow(dead_code)]
+#[path = "../config.rs"]
+mod config;
+#[allow(dead_code)]
+#[path = "../dns.rs"]
+mod dns;
+#[allow(dead_code)]
+#[path = "../file.rs"]
+mod file;
+#[allow(dead_code)]
+#[path = "../hook.rs"]
+mod hook;
+#[allow(dead_code)]
+#[path = "../identity.rs"]
+mod identity;
+#[allow(dead_code)]
+#[path = "../lease_db.rs"]
+mod lease_db;
+#[allow(dead_code)]
+#[path = "../resolv_conf.rs"]
+mod resolv_conf;
+#[allow(dead_code)]
+#[path = "../resolved.rs"]
+mod resolved;
+
+use std::net::Ipv4Addr;
+use std::path::{Path, PathBuf};
+
+use mozim::DhcpLease;
+
+use crate::dns::MozimDnsBackend;
+use crate::resolv_conf::MozimResolvConf;
+
+const ORIGINAL: &str = "nameserver 198.51.100.1\n";
+
+fn gen_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!(
+        "mozim-resolv-conf-test-{}-{}",
+        std::process::id(),
+        name
+    ));
+    std::fs::remove_dir_all(&dir).ok();
+    std::fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+fn gen_lease(dns_servers: &[Ipv4Addr], search: &[&str]) -> DhcpLease {
+    DhcpLease {
+        address: Ipv4Addr::new(192, 0, 2, 100),
+        prefix_length: 24,
+        gateways: Vec::new(),
+        dns_servers: dns_servers.to_vec(),
+        domain: None,
+        search: search.iter().map(|s| s.to_string()).collect(),
+        server_id: Ipv4Addr::new(192, 0, 2, 1),
+        lease_time: 1000,
+        t1: 500,
+        t2: 875,
+        acquired_at: 1_600_000_000,
+        remaining: None,
+        mtu: None,
+        ntp_servers: Vec::new(),
+        classless_routes: Vec::new(),
+        options: Vec::new(),
+    }
+}
+
+fn read(path: &Path) -> String {
+    std::fs::read_to_string(path).unwrap()
+}
+
+fn backup_path(path: &Path) -> PathBuf {
+    PathBuf::from(format!("{}.mozim-backup", path.display()))
+}
+
+fn gen_content(path: &Path, body: &str) -> String {
+    format!(
+        "# Generated by mozim, original file is saved as {}\n{}",
+        backup_path(path).display(),
+        body
+    )
+}
+
+#[test]
+fn test_resolv_conf_merge_and_restore() {
+    let dir = gen_dir("merge");
+    let path = dir.join("resolv.conf");
+    std::fs::write(&path, ORIGINAL).unwrap();
+    let mut resolv_conf = MozimResolvConf::new(path.to_str().unwrap());
+
+    resolv_conf
+        .update(
+            "eth1",
+            100,
+            &gen_lease(&[Ipv4Addr::new(192, 0, 2, 2)], &["example.org"]),
+        )
+        .unwrap();
+    assert_eq!(read(&backup_path(&path)), ORIGINAL);
+    resolv_conf
+        .update(
+            "eth0",
+            100,
+            &gen_lease(&[Ipv4Addr::new(192, 0, 2, 3)], &["example.com"]),
+        )
+        .unwrap();
+    resolv_conf
+        .update(
+            "eth2",
+            50,
+            &gen_lease(
+                &[Ipv4Addr::new(192, 0, 2, 4), Ipv4Addr::new(192, 0, 2, 2)],
+                &[],
+            ),
+        )
+        .unwrap();
+    // Sorted by priority then interface name, duplicates removed
+    assert_eq!(
+        read(&path),
+        gen_content(
+            &path,
+            "search example.com example.org\n\
+             nameserver 192.0.2.4\n\
+             nameserver 192.0.2.2\n\
+             nameserver 192.0.2.3\n"
+        )
+    );
+    // Backup is not overridden by later writes
+    assert_eq!(read(&backup_path(&path)), ORIGINAL);
+
+    resolv_conf.remove("eth2").unwrap();
+    resolv_conf.remove("eth0").unwrap();
+    assert_eq!(
+        read(&path),
+        gen_content(&path, "search example.org\nnameserver 192.0.2.2\n")
+    );
+    // Unknown interface is ignored
+    resolv_conf.remove("eth3").unwrap();
+    resolv_conf.remove("eth1").unwrap();
+    assert_eq!(read(&path), ORIGINAL);
+    assert!(backup_path(&path).symlink_metadata().is_err());
+    std::fs::remove_dir_all(&dir).ok();
+}
+
+#[test]
+fn test_resolv_conf_keep_existing_backup() {
+    let dir = gen_dir("existing_backup");
+    let path = dir.join("resolv.conf");
+    // Backup left by previous run which did not restore
+    std::fs::write(&path, gen_content(&path, "nameserver 192.0.2.9\n"))
+        .unwrap();
+    std::fs::write(backup_path(&path), ORIGINAL).unwrap();
+    let mut resolv_conf = MozimResolvConf::new(path.to_str().unwrap());
+
+    resolv_conf
+        .update("eth1", 100, &gen_lease(&[Ipv4Addr::new(192, 0, 2, 2)], &[]))
+        .unwrap();
+    assert_eq!(read(&backup_path(&path)), ORIGINAL);
+    resolv_conf.remove("eth1").unwrap();
+    assert_eq!(read(&path), ORIGINAL);
+    std::fs::remove_dir_all(&dir).ok();
+}
+
+#[test]
+fn test_resolv_conf_symlink() {
+    let dir = gen_dir("symlink");
+    let target = dir.join("stub-resolv.conf");
+    let path = dir.join("resolv.conf");
+    std::fs::write(&target, ORIGINAL).unwrap();
+    std::os::unix::fs::symlink(&target, &path).unwrap();
+    let mut resolv_conf = MozimResolvConf::new(path.to_str().unwrap());
+
+    resolv_conf
+        .update("eth1", 100, &gen_lease(&[Ipv4Addr::new(192, 0, 2, 2)], &[]))
+        .unwrap();
+    // The link is replaced instead of writing through it
+    assert!(path.symlink_metadata().unwrap().file_type().is_file());
+    assert_eq!(read(&target), ORIGINAL);
+    assert_eq!(std::fs::read_link(backup_path(&path)).unwrap(), target);
+
+    resolv_conf.remove("eth1").unwrap();
+    assert_eq!(std::fs::read_link(&path).unwrap(), target);
+    assert!(backup_path(&path).symlink_metadata().is_err());
+    std::fs::remove_dir_all(&dir).ok();
+}
+
+#[test]
+fn test_resolv_conf_no_dns_server() {
+    let dir = gen_dir("no_dns");
+    let path = dir.join("resolv.conf");
+    std::fs::write(&path, ORIGINAL).unwrap();
+    let mut resolv_conf = MozimResolvConf::new(path.to_str().unwrap());
+
+    resolv_conf
+        .update("eth0", 100, &gen_lease(&[], &["example.org"]))
+        .unwrap();
+    assert_eq!(read(&path), ORIGINAL);
+    assert!(backup_path(&path).symlink_metadata().is_err());
+
+    resolv_conf
+        .update("eth1", 100, &gen_lease(&[Ipv4Addr::new(192, 0, 2, 2)], &[]))
+        .unwrap();
+    assert_eq!(
+        read(&path),
+        gen_content(&path, "search example.org\nnameserver 192.0.2.2\n")
+    );
+    // Interface left has no DNS server
+    resolv_conf.remove("eth1").unwrap();
+    assert_eq!(read(&path), ORIGINAL);
+    std::fs::remove_dir_all(&dir).ok();
+}
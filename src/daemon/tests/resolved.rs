@@ -0,0 +1,160 @@
+// Copyright 2020 Red Hat, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+// Run `MozimResolved` against mock systemd-resolved on a private bus
+// started by `dbus-daemon`, skipped if `dbus-daemon` is not installed.
+
+#[allow(dead_code)]
+#[path = "../config.rs"]
+mod config;
+#[allow(dead_code)]
+#[path = "../dns.rs"]
+mod dns;
+#[allow(dead_code)]
+#[path = "../file.rs"]
+mod file;
+#[allow(dead_code)]
+#[path = "../hook.rs"]
+mod hook;
+#[allow(dead_code)]
+#[path = "../identity.rs"]
+mod identity;
+#[allow(dead_code)]
+#[path = "../lease_db.rs"]
+mod lease_db;
+#[allow(dead_code)]
+#[path = "../resolv_conf.rs"]
+mod resolv_conf;
+#[allow(dead_code)]
+#[path = "../resolved.rs"]
+mod resolved;
+
+use std::io::{BufRead, BufReader};
+use std::net::Ipv4Addr;
+use std::process::{Child, Command, Stdio};
+use std::sync::{Arc, Mutex};
+
+use zbus::blocking::ConnectionBuilder;
+use zbus::dbus_interface;
+
+use crate::resolved::MozimResolved;
+
+struct MockResolved {
+    calls: Arc<Mutex<Vec<String>>>,
+}
+
+#[dbus_interface(name = "org.freedesktop.resolve1.Manager")]
+impl MockResolved {
+    #[dbus_interface(name = "SetLinkDNS")]
+    fn set_link_dns(&self, iface_index: i32, servers: Vec<(i32, Vec<u8>)>) {
+        let servers: Vec<String> = servers
+            .iter()
+            .map(|(family, addr)| format!("{}:{:?}", family, addr))
+            .collect();
+        self.calls.lock().unwrap().push(format!(
+            "SetLinkDNS {} {}",
+            iface_index,
+            servers.join(",")
+        ));
+    }
+
+    fn set_link_domains(&self, iface_index: i32, domains: Vec<(String, bool)>) {
+        let domains: Vec<String> = domains
+            .iter()
+            .map(|(domain, route_only)| format!("{}:{}", domain, route_only))
+            .collect();
+        self.calls.lock().unwrap().push(format!(
+            "SetLinkDomains {} {}",
+            iface_index,
+            domains.join(",")
+        ));
+    }
+
+    fn revert_link(&self, iface_index: i32) {
+        self.calls
+            .lock()
+            .unwrap()
+            .push(format!("RevertLink {}", iface_index));
+    }
+}
+
+struct DbusDaemon(Child);
+
+impl Drop for DbusDaemon {
+    fn drop(&mut self) {
+        self.0.kill().ok();
+        self.0.wait().ok();
+    }
+}
+
+fn start_dbus_daemon() -> Option<(DbusDaemon, String)> {
+    let child = Command::new("dbus-daemon")
+        .args(["--session", "--nofork", "--print-address=1"])
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()
+        .ok()?;
+    let mut daemon = DbusDaemon(child);
+    let mut address = String::new();
+    BufReader::new(daemon.0.stdout.as_mut()?)
+        .read_line(&mut address)
+        .ok()?;
+    Some((daemon, address.trim().to_string()))
+}
+
+#[test]
+fn test_resolved_set_link_dns() {
+    let (_daemon, address) = match start_dbus_daemon() {
+        Some(d) => d,
+        None => {
+            eprintln!("dbus-daemon not found, skipping");
+            return;
+        }
+    };
+    let calls = Arc::new(Mutex::new(Vec::new()));
+    let _server = ConnectionBuilder::address(address.as_str())
+        .unwrap()
+        .name("org.freedesktop.resolve1")
+        .unwrap()
+        .serve_at(
+            "/org/freedesktop/resolve1",
+            MockResolved {
+                calls: calls.clone(),
+            },
+        )
+        .unwrap()
+        .build()
+        .unwrap();
+
+    let mut resolved = MozimResolved::new(Some(&address));
+    resolved
+        .set_link_dns(
+            2,
+            &[Ipv4Addr::new(192, 0, 2, 53), Ipv4Addr::new(192, 0, 2, 54)],
+        )
+        .unwrap();
+    resolved
+        .set_link_domains(2, &["example.com".to_string()])
+        .unwrap();
+    resolved.revert_link(2).unwrap();
+
+    assert_eq!(
+        *calls.lock().unwrap(),
+        vec![
+            "SetLinkDNS 2 2:[192, 0, 2, 53],2:[192, 0, 2, 54]".to_string(),
+            "SetLinkDomains 2 example.com:false".to_string(),
+            "RevertLink 2".to_string(),
+        ]
+    );
+}
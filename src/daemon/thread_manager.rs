@@ -12,10 +12,14 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+use crate::config::MozimIfaceConfig;
 use crate::dhcp_manager::{MozimDhcpCmd, MozimDhcpManager};
+use crate::dns::MozimDnsBackend;
+use crate::identity::MozimIdentityDb;
+use crate::lease_db::MozimLeaseDb;
 use mozim::{DhcpStatus, MozimError};
-use serde_json;
 use std::sync::mpsc::{sync_channel, Receiver, SyncSender};
+use std::sync::{Arc, Mutex};
 use std::thread;
 
 #[derive(Debug)]
@@ -26,7 +30,12 @@ pub(crate) struct MozimThreadManager {
 }
 
 impl MozimThreadManager {
-    pub(crate) fn new(iface_name: &str) -> Result<Self, MozimError> {
+    pub(crate) fn new(
+        iface_name: &str,
+        dns: Arc<Mutex<Box<dyn MozimDnsBackend>>>,
+        lease_db: MozimLeaseDb,
+        identity_db: Arc<Mutex<MozimIdentityDb>>,
+    ) -> Result<Self, MozimError> {
         // Create rust sync rendezvous channel for thread communication to
         // simplfy things.
         let (to_thread_sender, to_thread_recver) =
@@ -43,6 +52,9 @@ impl MozimThreadManager {
                     from_thread_sender,
                     to_thread_sender_clone,
                     to_thread_recver,
+                    dns,
+                    lease_db,
+                    identity_db,
                 )
             })?;
         Ok(MozimThreadManager {
@@ -53,48 +65,82 @@ impl MozimThreadManager {
     }
 
     pub(crate) fn stop_dhcp(&self) -> Result<DhcpStatus, MozimError> {
-        handle_send_result(self.sender.send(MozimDhcpCmd::StopDhcp))?;
-        string_to_dhcp_status(&handle_recv_result(self.recver.recv())?)
+        handle_send_result(
+            &self.iface_name,
+            self.sender.send(MozimDhcpCmd::Stop),
+        )?;
+        string_to_dhcp_status(&handle_recv_result(
+            &self.iface_name,
+            self.recver.recv(),
+        )?)
     }
 
-    pub(crate) fn start_dhcp(&self) -> Result<DhcpStatus, MozimError> {
-        handle_send_result(self.sender.send(MozimDhcpCmd::StartDhcp))?;
-        string_to_dhcp_status(&handle_recv_result(self.recver.recv())?)
+    pub(crate) fn shutdown_dhcp(&self) -> Result<DhcpStatus, MozimError> {
+        handle_send_result(
+            &self.iface_name,
+            self.sender.send(MozimDhcpCmd::Shutdown),
+        )?;
+        string_to_dhcp_status(&handle_recv_result(
+            &self.iface_name,
+            self.recver.recv(),
+        )?)
+    }
+
+    pub(crate) fn start_dhcp(
+        &self,
+        config: MozimIfaceConfig,
+    ) -> Result<DhcpStatus, MozimError> {
+        handle_send_result(
+            &self.iface_name,
+            self.sender.send(MozimDhcpCmd::Start(Box::new(config))),
+        )?;
+        string_to_dhcp_status(&handle_recv_result(
+            &self.iface_name,
+            self.recver.recv(),
+        )?)
     }
 
     pub(crate) fn query_dhcp(&self) -> Result<DhcpStatus, MozimError> {
-        handle_send_result(self.sender.send(MozimDhcpCmd::QueryDhcp))?;
-        string_to_dhcp_status(&handle_recv_result(self.recver.recv())?)
+        handle_send_result(
+            &self.iface_name,
+            self.sender.send(MozimDhcpCmd::Query),
+        )?;
+        string_to_dhcp_status(&handle_recv_result(
+            &self.iface_name,
+            self.recver.recv(),
+        )?)
     }
 }
 
 fn handle_send_result(
+    iface_name: &str,
     send_result: Result<(), std::sync::mpsc::SendError<MozimDhcpCmd>>,
 ) -> Result<(), MozimError> {
     match send_result {
         Ok(_) => Ok(()),
         Err(e) => Err(MozimError::bug(format!(
-            "Thread communication send error: {}",
-            e
+            "Thread communication send error on {}: {}",
+            iface_name, e
         ))),
     }
 }
 
 fn handle_recv_result(
+    iface_name: &str,
     recv_result: Result<Result<String, MozimError>, std::sync::mpsc::RecvError>,
 ) -> Result<String, MozimError> {
     match recv_result {
         Ok(Ok(o)) => Ok(o),
         Ok(Err(e)) => Err(e),
         Err(e) => Err(MozimError::bug(format!(
-            "Thread communication recv error: {}",
-            e
+            "Thread communication recv error on {}: {}",
+            iface_name, e
         ))),
     }
 }
 
 fn string_to_dhcp_status(status_str: &str) -> Result<DhcpStatus, MozimError> {
-    match serde_json::from_str(&status_str) {
+    match serde_json::from_str(status_str) {
         Ok(s) => Ok(s),
         Err(e) => Err(MozimError::bug(format!(
             "serde_json::from_str() error: {}",
@@ -12,28 +12,77 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+use crate::config::{MozimDaemonConfig, MozimIfaceConfig};
+use crate::dns::{new_dns_backend, MozimDnsBackend};
+use crate::identity::MozimIdentityDb;
+use crate::lease_db::MozimLeaseDb;
 use crate::thread_manager::MozimThreadManager;
 use crate::MozimError;
 use mozim::{DhcpState, DhcpStatus};
 use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
 
 pub(crate) struct MozimThreadsManager {
     pub threads: HashMap<String, MozimThreadManager>,
+    // Shared by all threads for merging DNS of all interfaces
+    dns: Arc<Mutex<Box<dyn MozimDnsBackend>>>,
+    lease_db: MozimLeaseDb,
+    // Shared by all threads, serializing generation of DUID and IAID
+    identity_db: Arc<Mutex<MozimIdentityDb>>,
 }
 
 impl MozimThreadsManager {
-    pub(crate) fn new() -> Self {
+    pub(crate) fn new(config: &MozimDaemonConfig) -> Self {
         MozimThreadsManager {
             threads: HashMap::new(),
+            dns: Arc::new(Mutex::new(new_dns_backend(config))),
+            lease_db: MozimLeaseDb::new(&config.lease_dir),
+            identity_db: Arc::new(Mutex::new(MozimIdentityDb::new(
+                &config.lease_dir,
+                config.duid_type,
+                config.duid_enterprise_number,
+            ))),
+        }
+    }
+
+    // Start DHCP on interfaces found in lease database with their previous
+    // options, failures are only logged.
+    pub(crate) fn resume(&mut self) {
+        let iface_names = match self.lease_db.list() {
+            Ok(i) => i,
+            Err(e) => {
+                eprintln!("{}", e);
+                return;
+            }
+        };
+        for iface_name in iface_names {
+            let result = self
+                .lease_db
+                .load(&iface_name)
+                .and_then(|record| {
+                    let options = record.unwrap_or_default().options;
+                    let options: Vec<&str> =
+                        options.iter().map(|o| o.as_str()).collect();
+                    MozimIfaceConfig::parse(&options)
+                })
+                .and_then(|config| self.start_dhcp(&iface_name, config));
+            match result {
+                Ok(_) => eprintln!("{}: DHCP resumed", iface_name),
+                Err(e) => {
+                    eprintln!("{}: Failed to resume DHCP: {}", iface_name, e);
+                    self.threads.remove(&iface_name);
+                }
+            }
         }
     }
 
     pub(crate) fn start_dhcp(
         &mut self,
         iface_name: &str,
+        config: MozimIfaceConfig,
     ) -> Result<DhcpStatus, MozimError> {
         let thread = self.get_thread(iface_name)?;
-        thread.start_dhcp()
+        thread.start_dhcp(config)
     }
 
     pub(crate) fn query_dhcp(
@@ -59,13 +108,28 @@ impl MozimThreadsManager {
         }
     }
 
+    // Stop all threads on daemon exit, each following its
+    // `release_policy`.
+    pub(crate) fn shutdown(&mut self) {
+        for (iface_name, thread) in self.threads.drain() {
+            if let Err(e) = thread.shutdown_dhcp() {
+                eprintln!("{}: Failed to shutdown DHCP: {}", iface_name, e);
+            }
+        }
+    }
+
     // Get thread, if not found, create one
     fn get_thread(
         &mut self,
         iface_name: &str,
     ) -> Result<&mut MozimThreadManager, MozimError> {
         if !self.threads.contains_key(iface_name) {
-            let thread = MozimThreadManager::new(iface_name)?;
+            let thread = MozimThreadManager::new(
+                iface_name,
+                self.dns.clone(),
+                self.lease_db.clone(),
+                self.identity_db.clone(),
+            )?;
             self.threads.insert(iface_name.into(), thread);
         }
 
@@ -88,8 +152,5 @@ impl MozimThreadsManager {
 }
 
 fn gen_dhcp_stop_status(iface_name: &str) -> DhcpStatus {
-    DhcpStatus {
-        iface_name: iface_name.to_string(),
-        state: DhcpState::Stopped,
-    }
+    DhcpStatus::new(iface_name, DhcpState::Stopped)
 }
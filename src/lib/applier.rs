@@ -0,0 +1,381 @@
+// Copyright 2020 Red Hat, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::net::Ipv4Addr;
+use std::os::unix::process::CommandExt;
+use std::process::{Child, Command, Stdio};
+use std::time::{Duration, Instant, SystemTime};
+
+use serde_derive::{Deserialize, Serialize};
+
+use crate::netlink::{
+    apply_lease, apply_link_local, remove_lease, remove_link_local,
+};
+use crate::{join_addrs, DhcpLease, MozimError};
+
+pub const DHCP_DEFAULT_ROUTE_METRIC: u32 = 100;
+pub const DHCP_DEFAULT_HOOK_TIMEOUT: Duration = Duration::from_secs(30);
+const HOOK_POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+// How the lease lands on the system.
+pub trait DhcpLeaseApplier: Send + std::fmt::Debug {
+    fn apply_lease(
+        &mut self,
+        iface_name: &str,
+        lease: &DhcpLease,
+    ) -> Result<(), MozimError>;
+
+    // Called when lease is renewed or rebound. By default, the old lease is
+    // removed first if address or routers changed.
+    fn update_lease(
+        &mut self,
+        iface_name: &str,
+        old: &DhcpLease,
+        new: &DhcpLease,
+    ) -> Result<(), MozimError> {
+        if old.address != new.address
+            || old.prefix_length != new.prefix_length
+            || old.gateways != new.gateways
+            || old.classless_routes != new.classless_routes
+        {
+            self.remove_lease(iface_name, old)?;
+        }
+        self.apply_lease(iface_name, new)
+    }
+
+    fn remove_lease(
+        &mut self,
+        iface_name: &str,
+        lease: &DhcpLease,
+    ) -> Result<(), MozimError>;
+
+    // IPv4 link-local address(RFC 3927) claimed when no DHCP server
+    // answers, ignored by default.
+    fn apply_link_local(
+        &mut self,
+        _iface_name: &str,
+        _address: Ipv4Addr,
+    ) -> Result<(), MozimError> {
+        Ok(())
+    }
+
+    fn remove_link_local(
+        &mut self,
+        _iface_name: &str,
+        _address: Ipv4Addr,
+    ) -> Result<(), MozimError> {
+        Ok(())
+    }
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum DhcpApplierType {
+    // Set address and routes via rtnetlink
+    #[default]
+    Netlink,
+    // Only report lease in `DhcpStatus`
+    None,
+    // Executable invoked for each lease change
+    Hook(String),
+}
+
+impl std::fmt::Display for DhcpApplierType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DhcpApplierType::Netlink => write!(f, "netlink"),
+            DhcpApplierType::None => write!(f, "none"),
+            DhcpApplierType::Hook(path) => write!(f, "hook:{}", path),
+        }
+    }
+}
+
+impl std::str::FromStr for DhcpApplierType {
+    type Err = MozimError;
+    fn from_str(s: &str) -> Result<Self, MozimError> {
+        match s {
+            "netlink" => Ok(DhcpApplierType::Netlink),
+            "none" => Ok(DhcpApplierType::None),
+            _ => match s.strip_prefix("hook:") {
+                Some(path) if !path.is_empty() => {
+                    Ok(DhcpApplierType::Hook(path.to_string()))
+                }
+                _ => Err(MozimError::invalid_argument(format!(
+                    "Invalid applier '{}', should be netlink, none or \
+                     hook:<path>",
+                    s
+                ))),
+            },
+        }
+    }
+}
+
+impl DhcpApplierType {
+    // The `route_metric` is only used by `Netlink`.
+    pub fn new_applier(&self, route_metric: u32) -> Box<dyn DhcpLeaseApplier> {
+        match self {
+            DhcpApplierType::Netlink => {
+                Box::new(DhcpNetlinkApplier::new(route_metric))
+            }
+            DhcpApplierType::None => Box::new(DhcpNoneApplier),
+            DhcpApplierType::Hook(path) => Box::new(DhcpHookApplier::new(path)),
+        }
+    }
+}
+
+// Add address with lifetimes of lease and the routes.
+#[derive(Debug, Clone)]
+pub struct DhcpNetlinkApplier {
+    route_metric: u32,
+}
+
+impl DhcpNetlinkApplier {
+    pub fn new(route_metric: u32) -> Self {
+        DhcpNetlinkApplier { route_metric }
+    }
+}
+
+impl DhcpLeaseApplier for DhcpNetlinkApplier {
+    fn apply_lease(
+        &mut self,
+        iface_name: &str,
+        lease: &DhcpLease,
+    ) -> Result<(), MozimError> {
+        apply_lease(iface_name, lease, self.route_metric, SystemTime::now())
+    }
+
+    fn remove_lease(
+        &mut self,
+        iface_name: &str,
+        lease: &DhcpLease,
+    ) -> Result<(), MozimError> {
+        remove_lease(iface_name, lease, self.route_metric)
+    }
+
+    fn apply_link_local(
+        &mut self,
+        iface_name: &str,
+        address: Ipv4Addr,
+    ) -> Result<(), MozimError> {
+        apply_link_local(iface_name, address)
+    }
+
+    fn remove_link_local(
+        &mut self,
+        iface_name: &str,
+        address: Ipv4Addr,
+    ) -> Result<(), MozimError> {
+        remove_link_local(iface_name, address)
+    }
+}
+
+// Leave the system untouched, for hosts managed by other tools.
+#[derive(Debug, Clone)]
+pub struct DhcpNoneApplier;
+
+impl DhcpLeaseApplier for DhcpNoneApplier {
+    fn apply_lease(
+        &mut self,
+        _: &str,
+        _: &DhcpLease,
+    ) -> Result<(), MozimError> {
+        Ok(())
+    }
+
+    fn remove_lease(
+        &mut self,
+        _: &str,
+        _: &DhcpLease,
+    ) -> Result<(), MozimError> {
+        Ok(())
+    }
+}
+
+// Run `<path> <apply|update|remove> <iface_name>` with lease stored in
+// `MOZIM_*` environment variables, the old address of `update` is stored in
+// `MOZIM_OLD_ADDRESS`. The link-local address is handled by
+// `apply-link-local` and `remove-link-local` actions with only
+// `MOZIM_ADDRESS` and `MOZIM_PREFIX_LENGTH` defined. Non-zero exit is treated
+// as failure. The hook blocks the caller, it is killed along with its process
+// group if still running after timeout, `DHCP_DEFAULT_HOOK_TIMEOUT` by
+// default.
+#[derive(Debug, Clone)]
+pub struct DhcpHookApplier {
+    path: String,
+    timeout: Duration,
+}
+
+impl DhcpHookApplier {
+    pub fn new(path: &str) -> Self {
+        DhcpHookApplier {
+            path: path.to_string(),
+            timeout: DHCP_DEFAULT_HOOK_TIMEOUT,
+        }
+    }
+
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    fn run(
+        &self,
+        action: &str,
+        iface_name: &str,
+        envs: Vec<(&'static str, String)>,
+    ) -> Result<(), MozimError> {
+        let mut child = Command::new(&self.path)
+            .arg(action)
+            .arg(iface_name)
+            .process_group(0)
+            .stdin(Stdio::null())
+            .envs(envs)
+            .spawn()
+            .map_err(|e| {
+                MozimError::hook_error(format!(
+                    "Failed to execute hook {}: {}",
+                    self.path, e
+                ))
+            })?;
+        self.wait(&mut child, action, iface_name)
+    }
+
+    fn wait(
+        &self,
+        child: &mut Child,
+        action: &str,
+        iface_name: &str,
+    ) -> Result<(), MozimError> {
+        let deadline = Instant::now() + self.timeout;
+        loop {
+            match child.try_wait() {
+                Ok(Some(status)) if status.success() => return Ok(()),
+                Ok(Some(status)) => {
+                    return Err(MozimError::hook_error(format!(
+                        "Hook {} {} {} failed: {}",
+                        self.path, action, iface_name, status
+                    )))
+                }
+                Ok(None) if Instant::now() >= deadline => {
+                    // The hook is leader of its own process group
+                    unsafe {
+                        libc::kill(-(child.id() as libc::pid_t), libc::SIGKILL);
+                    }
+                    child.wait().ok();
+                    return Err(MozimError::hook_error(format!(
+                        "Hook {} {} {} killed after {} seconds timeout",
+                        self.path,
+                        action,
+                        iface_name,
+                        self.timeout.as_secs()
+                    )));
+                }
+                Ok(None) => std::thread::sleep(HOOK_POLL_INTERVAL),
+                Err(e) => {
+                    return Err(MozimError::hook_error(format!(
+                        "Failed to wait hook {}: {}",
+                        self.path, e
+                    )))
+                }
+            }
+        }
+    }
+}
+
+impl DhcpLeaseApplier for DhcpHookApplier {
+    fn apply_lease(
+        &mut self,
+        iface_name: &str,
+        lease: &DhcpLease,
+    ) -> Result<(), MozimError> {
+        self.run("apply", iface_name, gen_lease_envs(lease))
+    }
+
+    fn update_lease(
+        &mut self,
+        iface_name: &str,
+        old: &DhcpLease,
+        new: &DhcpLease,
+    ) -> Result<(), MozimError> {
+        let mut envs = gen_lease_envs(new);
+        envs.push(("MOZIM_OLD_ADDRESS", old.address.to_string()));
+        self.run("update", iface_name, envs)
+    }
+
+    fn remove_lease(
+        &mut self,
+        iface_name: &str,
+        lease: &DhcpLease,
+    ) -> Result<(), MozimError> {
+        self.run("remove", iface_name, gen_lease_envs(lease))
+    }
+
+    fn apply_link_local(
+        &mut self,
+        iface_name: &str,
+        address: Ipv4Addr,
+    ) -> Result<(), MozimError> {
+        self.run("apply-link-local", iface_name, gen_link_local_envs(address))
+    }
+
+    fn remove_link_local(
+        &mut self,
+        iface_name: &str,
+        address: Ipv4Addr,
+    ) -> Result<(), MozimError> {
+        self.run(
+            "remove-link-local",
+            iface_name,
+            gen_link_local_envs(address),
+        )
+    }
+}
+
+fn gen_link_local_envs(address: Ipv4Addr) -> Vec<(&'static str, String)> {
+    vec![
+        ("MOZIM_ADDRESS", address.to_string()),
+        ("MOZIM_PREFIX_LENGTH", "16".to_string()),
+    ]
+}
+
+fn gen_lease_envs(lease: &DhcpLease) -> Vec<(&'static str, String)> {
+    let mut envs = vec![
+        ("MOZIM_ADDRESS", lease.address.to_string()),
+        ("MOZIM_PREFIX_LENGTH", lease.prefix_length.to_string()),
+        ("MOZIM_SERVER_ID", lease.server_id.to_string()),
+        ("MOZIM_LEASE_TIME", lease.lease_time.to_string()),
+        ("MOZIM_GATEWAYS", join_addrs(&lease.gateways)),
+        ("MOZIM_DNS_SERVERS", join_addrs(&lease.dns_servers)),
+        ("MOZIM_NTP_SERVERS", join_addrs(&lease.ntp_servers)),
+        ("MOZIM_SEARCH", lease.search.join(" ")),
+        ("MOZIM_CLASSLESS_ROUTES", join_classless_routes(lease)),
+    ];
+    if let Some(domain) = &lease.domain {
+        envs.push(("MOZIM_DOMAIN", domain.clone()));
+    }
+    if let Some(mtu) = lease.mtu {
+        envs.push(("MOZIM_MTU", mtu.to_string()));
+    }
+    envs
+}
+
+// Space separated `<dst>/<prefix_length>,<gateway>`
+fn join_classless_routes(lease: &DhcpLease) -> String {
+    lease
+        .classless_routes
+        .iter()
+        .map(|r| format!("{}/{},{}", r.dst, r.prefix_length, r.gateway))
+        .collect::<Vec<String>>()
+        .join(" ")
+}
@@ -0,0 +1,114 @@
+// Copyright 2020 Red Hat, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::net::Ipv4Addr;
+
+use crate::msg::{ARP_HW_TYPE_ETHERNET, ETHERNET_HW_ADDR_LEN};
+use crate::MozimError;
+
+const ARP_PROTO_IPV4: u16 = 0x0800;
+const IPV4_ADDR_LEN: u8 = 4;
+pub const ARP_OP_REQUEST: u16 = 1;
+pub const ARP_OP_REPLY: u16 = 2;
+pub(crate) const ARP_PACKET_LEN: usize = 28;
+
+// ARP packet of Ethernet and IPv4 used for RFC 5227 address conflict
+// detection, without the Ethernet header.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DhcpV4ArpPacket {
+    pub op: u16,
+    pub sender_mac: [u8; 6],
+    pub sender_ip: Ipv4Addr,
+    pub target_mac: [u8; 6],
+    pub target_ip: Ipv4Addr,
+}
+
+impl DhcpV4ArpPacket {
+    // RFC 5227 section 2.1.1: ARP request with all zero sender IP and
+    // target MAC.
+    pub fn new_probe(mac: &[u8; 6], target_ip: Ipv4Addr) -> Self {
+        DhcpV4ArpPacket {
+            op: ARP_OP_REQUEST,
+            sender_mac: *mac,
+            sender_ip: Ipv4Addr::UNSPECIFIED,
+            target_mac: [0u8; 6],
+            target_ip,
+        }
+    }
+
+    // RFC 5227 section 2.3: ARP request with both sender and target IP set
+    // to the claimed address.
+    pub fn new_announcement(mac: &[u8; 6], address: Ipv4Addr) -> Self {
+        DhcpV4ArpPacket {
+            op: ARP_OP_REQUEST,
+            sender_mac: *mac,
+            sender_ip: address,
+            target_mac: [0u8; 6],
+            target_ip: address,
+        }
+    }
+
+    pub fn parse(data: &[u8]) -> Result<Self, MozimError> {
+        if data.len() < ARP_PACKET_LEN {
+            return Err(MozimError::invalid_argument(format!(
+                "ARP packet too short: {} bytes",
+                data.len()
+            )));
+        }
+        if u16::from_be_bytes([data[0], data[1]]) != ARP_HW_TYPE_ETHERNET as u16
+            || u16::from_be_bytes([data[2], data[3]]) != ARP_PROTO_IPV4
+            || data[4] != ETHERNET_HW_ADDR_LEN
+            || data[5] != IPV4_ADDR_LEN
+        {
+            return Err(MozimError::invalid_argument(
+                "ARP packet is not for Ethernet and IPv4".to_string(),
+            ));
+        }
+        let mut sender_mac = [0u8; 6];
+        let mut target_mac = [0u8; 6];
+        sender_mac.copy_from_slice(&data[8..14]);
+        target_mac.copy_from_slice(&data[18..24]);
+        Ok(DhcpV4ArpPacket {
+            op: u16::from_be_bytes([data[6], data[7]]),
+            sender_mac,
+            sender_ip: Ipv4Addr::new(data[14], data[15], data[16], data[17]),
+            target_mac,
+            target_ip: Ipv4Addr::new(data[24], data[25], data[26], data[27]),
+        })
+    }
+
+    pub fn emit(&self) -> Vec<u8> {
+        let mut data = Vec::with_capacity(ARP_PACKET_LEN);
+        data.extend_from_slice(&(ARP_HW_TYPE_ETHERNET as u16).to_be_bytes());
+        data.extend_from_slice(&ARP_PROTO_IPV4.to_be_bytes());
+        data.push(ETHERNET_HW_ADDR_LEN);
+        data.push(IPV4_ADDR_LEN);
+        data.extend_from_slice(&self.op.to_be_bytes());
+        data.extend_from_slice(&self.sender_mac);
+        data.extend_from_slice(&self.sender_ip.octets());
+        data.extend_from_slice(&self.target_mac);
+        data.extend_from_slice(&self.target_ip.octets());
+        data
+    }
+
+    // RFC 5227 section 2.1.1: The address is in use if another host sends
+    // any ARP packet from it, or probes it at the same time.
+    pub fn is_conflict(&self, own_mac: &[u8; 6], addr: Ipv4Addr) -> bool {
+        &self.sender_mac != own_mac
+            && (self.sender_ip == addr
+                || (self.op == ARP_OP_REQUEST
+                    && self.sender_ip.is_unspecified()
+                    && self.target_ip == addr))
+    }
+}
@@ -0,0 +1,960 @@
+// Copyright 2020 Red Hat, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+// I/O free DHCPv4 client state machine.
+//
+// The caller feeds received messages and the current time into
+// `DhcpV4Client`, then drains the messages to send with `poll_transmit()`,
+// the lease changes with `poll_event()` and calls `handle_timeout()` once
+// the time returned by `poll_timeout()` is reached. No socket, thread or
+// clock is used by this module, which makes the lease behaviour
+// deterministic for unit tests and reusable from any event loop.
+
+use std::collections::{BTreeMap, VecDeque};
+use std::net::Ipv4Addr;
+use std::time::{Duration, SystemTime};
+
+use crate::dhcp::to_timestamp;
+use crate::link_local::{LinkLocal, LinkLocalEvent};
+use crate::vendor::decode_vendor_info;
+use crate::{
+    DhcpClientFqdn, DhcpLease, DhcpOption, DhcpState, DhcpStatus,
+    DhcpV4ArpPacket, DhcpV4Message, DhcpV4MessageType, DhcpV4Op,
+    DhcpVendorDecoder, DhcpViVendorSpecific, MozimError, DHCP_FLAG_BROADCAST,
+    DHCP_OPTION_BROADCAST_ADDRESS, DHCP_OPTION_CLASSLESS_STATIC_ROUTE,
+    DHCP_OPTION_DNS_SERVER, DHCP_OPTION_DOMAIN_NAME, DHCP_OPTION_DOMAIN_SEARCH,
+    DHCP_OPTION_INTERFACE_MTU, DHCP_OPTION_MS_CLASSLESS_STATIC_ROUTE,
+    DHCP_OPTION_NTP_SERVER, DHCP_OPTION_ROUTER, DHCP_OPTION_SUBNET_MASK,
+    DHCP_OPTION_VENDOR_SPECIFIC, DHCP_OPTION_VI_VENDOR_SPECIFIC,
+};
+
+// RFC 2131 section 4.1: first retransmission after 4 seconds, doubling up
+// to 64 seconds, randomized by -1 to +1 second.
+const RETRANSMIT_INTERVAL_MIN: Duration = Duration::from_secs(4);
+const RETRANSMIT_INTERVAL_MAX: Duration = Duration::from_secs(64);
+const RETRANSMIT_JITTER_MS: u32 = 1000;
+// RFC 2131 section 4.4.5: retransmission in RENEWING and REBINDING waits
+// one-half of the remaining time, down to 60 seconds.
+const RENEW_RETRANSMIT_MIN: Duration = Duration::from_secs(60);
+// RFC 2131 section 3.2 leaves it to client how long to wait for reply in
+// INIT-REBOOT, use the same default as dhclient.
+const INIT_REBOOT_TIMEOUT: Duration = Duration::from_secs(10);
+// RFC 2131 section 3.1: wait at least 10 seconds before restarting
+// configuration after DHCPDECLINE.
+const DECLINE_BACKOFF: Duration = Duration::from_secs(10);
+pub const DHCP_DEFAULT_TIMEOUT: Duration = Duration::from_secs(60);
+pub const DHCP_DEFAULT_ARP_PROBE_TIMEOUT: Duration = Duration::from_secs(1);
+pub const DHCP_DEFAULT_INFORM_INTERVAL: Duration = Duration::from_secs(3600);
+// Options mozim knows how to use, classless static route before router as
+// some servers only reply options fitting in the order requested.
+pub const DHCP_DEFAULT_PARAMETER_REQUEST_LIST: [u8; 10] = [
+    DHCP_OPTION_SUBNET_MASK,
+    DHCP_OPTION_CLASSLESS_STATIC_ROUTE,
+    DHCP_OPTION_MS_CLASSLESS_STATIC_ROUTE,
+    DHCP_OPTION_ROUTER,
+    DHCP_OPTION_DNS_SERVER,
+    DHCP_OPTION_DOMAIN_NAME,
+    DHCP_OPTION_DOMAIN_SEARCH,
+    DHCP_OPTION_INTERFACE_MTU,
+    DHCP_OPTION_BROADCAST_ADDRESS,
+    DHCP_OPTION_NTP_SERVER,
+];
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DhcpV4Config {
+    pub iface_name: String,
+    pub mac: [u8; 6],
+    // `DhcpV4Event::Failed` is emitted if no lease is acquired within this
+    // time after start or lease lost.
+    pub timeout: Duration,
+    // How many ARP probes to send for the address in DHCPACK before using
+    // it, 0 to disable address conflict detection.
+    pub arp_probe_count: u32,
+    // Time to wait for conflicting ARP after each probe
+    pub arp_probe_timeout: Duration,
+    // Claim RFC 3927 IPv4 link-local address if no lease acquired within
+    // this time after start or lease lost, None to disable.
+    pub link_local_timeout: Option<Duration>,
+    // Request RFC 4039 rapid commit in DHCPDISCOVER and accept DHCPACK
+    // directly in SELECTING state.
+    pub rapid_commit: bool,
+    // How often DHCPINFORM is resent after DHCPACK in `Inform` state
+    pub inform_interval: Duration,
+    // Data of client identifier option included in every message, see
+    // `gen_client_id_rfc4361()` and `gen_client_id_mac()`. None to let
+    // server identify client by `chaddr`.
+    pub client_id: Option<Vec<u8>>,
+    // Host name option sent in DHCPDISCOVER, DHCPREQUEST and DHCPINFORM,
+    // ignored when `fqdn` is set as RFC 4702 section 3.7 required.
+    pub host_name: Option<String>,
+    // Client FQDN option asking server to update DNS
+    pub fqdn: Option<DhcpClientFqdn>,
+    // Vendor class identifier, option 43 is requested when set
+    pub vendor_class: Option<String>,
+    // RFC 3004 user classes, not sent if empty. Each class should be 1 to
+    // 255 bytes, see `validate()`.
+    pub user_class: Vec<String>,
+    // RFC 3925 vendor-identifying vendor-specific information sent to
+    // server, option 125 is requested when not empty. Data of each vendor
+    // should be at most 255 bytes, see `validate()`.
+    pub vi_vendor_specific: Vec<DhcpViVendorSpecific>,
+    // Option codes of parameter request list, option 43 and 125 are
+    // appended when needed by `vendor_class` and `vi_vendor_specific`.
+    pub parameter_request_list: Vec<u8>,
+    // Options appended as is to DHCPDISCOVER, DHCPREQUEST and DHCPINFORM.
+    // Options mozim sends by itself should not be included.
+    pub extra_options: Vec<DhcpOption>,
+}
+
+impl DhcpV4Config {
+    pub fn new(iface_name: &str, mac: &[u8; 6]) -> Self {
+        DhcpV4Config {
+            iface_name: iface_name.to_string(),
+            mac: *mac,
+            timeout: DHCP_DEFAULT_TIMEOUT,
+            arp_probe_count: 0,
+            arp_probe_timeout: DHCP_DEFAULT_ARP_PROBE_TIMEOUT,
+            link_local_timeout: None,
+            rapid_commit: false,
+            inform_interval: DHCP_DEFAULT_INFORM_INTERVAL,
+            client_id: None,
+            host_name: None,
+            fqdn: None,
+            vendor_class: None,
+            user_class: Vec::new(),
+            vi_vendor_specific: Vec::new(),
+            parameter_request_list: DHCP_DEFAULT_PARAMETER_REQUEST_LIST
+                .to_vec(),
+            extra_options: Vec::new(),
+        }
+    }
+
+    // Check items whose length is encoded in 1 byte, as they cannot be
+    // emitted otherwise.
+    pub fn validate(&self) -> Result<(), MozimError> {
+        if let Some(class) = self
+            .user_class
+            .iter()
+            .find(|c| c.is_empty() || c.len() > u8::MAX as usize)
+        {
+            return Err(MozimError::invalid_argument(format!(
+                "User class '{}' should be 1 to 255 bytes",
+                class
+            )));
+        }
+        if let Some(vendor) = self
+            .vi_vendor_specific
+            .iter()
+            .find(|v| v.data.len() > u8::MAX as usize)
+        {
+            return Err(MozimError::invalid_argument(format!(
+                "Vendor-specific data of enterprise {} should be at most \
+                 255 bytes, got {}",
+                vendor.enterprise_number,
+                vendor.data.len()
+            )));
+        }
+        Ok(())
+    }
+}
+
+// Message to send and its IPv4 destination, `Ipv4Addr::BROADCAST` for
+// broadcast.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DhcpV4Transmit {
+    pub msg: DhcpV4Message,
+    pub dst: Ipv4Addr,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DhcpV4Event {
+    // Holding the new lease
+    LeaseAcquired(DhcpLease),
+    // Holding the lease extended by the original server
+    LeaseRenewed(DhcpLease),
+    // Holding the lease extended by any server
+    LeaseRebound(DhcpLease),
+    // Holding the lease expired or refused by DHCPNAK, its address should
+    // be removed.
+    LeaseLost(DhcpLease),
+    // No lease acquired within `DhcpV4Config.timeout`, still retrying.
+    Failed,
+    // The address of DHCPACK is used by other host, DHCPDECLINE sent
+    AddressDeclined(Ipv4Addr),
+    // DHCPACK of DHCPINFORM received, the address of lease is the static
+    // one and only other options should be applied.
+    OptionsAcquired(DhcpLease),
+    // IPv4 link-local address claimed, DHCP is still retrying
+    LinkLocalAcquired(Ipv4Addr),
+    // IPv4 link-local address should be removed, either lease acquired or
+    // lost to other host.
+    LinkLocalLost(Ipv4Addr),
+}
+
+#[derive(Debug, Clone)]
+pub struct DhcpV4Client {
+    config: DhcpV4Config,
+    rand: XorShift64,
+    state: DhcpState,
+    xid: u32,
+    // The server identifier and address of the selected offer
+    server_id: Option<Ipv4Addr>,
+    offered_ip: Option<Ipv4Addr>,
+    lease: Option<DhcpLease>,
+    // Whether the lease was confirmed in INIT-REBOOT instead of negotiated
+    // by DHCPDISCOVER.
+    lease_reused: bool,
+    rapid_commit_used: bool,
+    fail_at: Option<SystemTime>,
+    // Give up INIT-REBOOT and fallback to DHCPDISCOVER
+    reboot_until: Option<SystemTime>,
+    // The lease waiting for ARP probes to finish
+    probing_lease: Option<DhcpLease>,
+    probes_left: u32,
+    probe_at: Option<SystemTime>,
+    // Restart DHCPDISCOVER after DHCPDECLINE backoff
+    restart_at: Option<SystemTime>,
+    link_local_at: Option<SystemTime>,
+    link_local: Option<LinkLocal>,
+    // Static address of `Inform` state
+    inform_address: Option<Ipv4Addr>,
+    inform_at: Option<SystemTime>,
+    // The last message sent, retransmitted until a reply is received
+    pending: Option<DhcpV4Transmit>,
+    retransmit_at: Option<SystemTime>,
+    retransmit_interval: Duration,
+    t1: Option<SystemTime>,
+    t2: Option<SystemTime>,
+    expiry: Option<SystemTime>,
+    transmits: VecDeque<DhcpV4Transmit>,
+    events: VecDeque<DhcpV4Event>,
+    arp_transmits: VecDeque<DhcpV4ArpPacket>,
+    vendor_decoders: BTreeMap<u32, DhcpVendorDecoder>,
+}
+
+impl DhcpV4Client {
+    // The `rand_seed` is used for transaction ID generation, it should be
+    // random for real world usage.
+    pub fn new(config: DhcpV4Config, rand_seed: u64) -> Self {
+        DhcpV4Client {
+            config,
+            rand: XorShift64::new(rand_seed),
+            state: DhcpState::Stopped,
+            xid: 0,
+            server_id: None,
+            offered_ip: None,
+            lease: None,
+            lease_reused: false,
+            rapid_commit_used: false,
+            fail_at: None,
+            reboot_until: None,
+            probing_lease: None,
+            probes_left: 0,
+            probe_at: None,
+            restart_at: None,
+            link_local_at: None,
+            link_local: None,
+            inform_address: None,
+            inform_at: None,
+            pending: None,
+            retransmit_at: None,
+            retransmit_interval: RETRANSMIT_INTERVAL_MIN,
+            t1: None,
+            t2: None,
+            expiry: None,
+            transmits: VecDeque::new(),
+            events: VecDeque::new(),
+            arp_transmits: VecDeque::new(),
+            vendor_decoders: BTreeMap::new(),
+        }
+    }
+
+    // Decode data of `enterprise_number` in option 125 with `decoder` for
+    // `vendor_info` of `DhcpStatus` instead of the default
+    // `decode_vendor_sub_options()`.
+    pub fn register_vendor_decoder(
+        &mut self,
+        enterprise_number: u32,
+        decoder: DhcpVendorDecoder,
+    ) {
+        self.vendor_decoders.insert(enterprise_number, decoder);
+    }
+
+    pub fn config(&self) -> &DhcpV4Config {
+        &self.config
+    }
+
+    // `LinkLocal` when link-local address is in use, as DHCP state is
+    // hidden behind.
+    pub fn state(&self) -> DhcpState {
+        match self.link_local_address() {
+            Some(_) => DhcpState::LinkLocal,
+            None => self.state,
+        }
+    }
+
+    // Whether current lease is the previous one confirmed by INIT-REBOOT.
+    pub fn lease_reused(&self) -> bool {
+        self.lease_reused
+    }
+
+    pub fn link_local_address(&self) -> Option<Ipv4Addr> {
+        self.link_local.as_ref().and_then(|l| l.address())
+    }
+
+    // Whether ARP packets of the interface should be fed to
+    // `handle_arp()`.
+    pub fn needs_arp(&self) -> bool {
+        self.state == DhcpState::Probing || self.link_local.is_some()
+    }
+
+    // The `now` is used to calculate the remaining time of lease.
+    pub fn status(&self, now: SystemTime) -> DhcpStatus {
+        let mut status = DhcpStatus::new(&self.config.iface_name, self.state());
+        status.link_local = self.link_local_address();
+        status.renew_at = match self.state {
+            DhcpState::Bound => self.t1,
+            DhcpState::Inform => self.inform_at,
+            DhcpState::Renewing | DhcpState::Rebinding => self.retransmit_at,
+            _ => None,
+        }
+        .map(to_timestamp);
+        if let Some(lease) = self.lease() {
+            status.rebind_at = self.t2.map(to_timestamp);
+            status.expire_at = self.expiry.map(to_timestamp);
+            status.fqdn_flags = lease.options.iter().find_map(|o| match o {
+                DhcpOption::ClientFqdn(fqdn) => Some(fqdn.flags),
+                _ => None,
+            });
+            status.vendor_info =
+                decode_vendor_info(&lease.options, &self.vendor_decoders);
+            let mut lease = lease.clone();
+            lease.remaining = self.expiry.map(|expiry| {
+                expiry.duration_since(now).map(|d| d.as_secs()).unwrap_or(0)
+            });
+            status.lease = Some(lease);
+            status.lease_reused = Some(self.lease_reused);
+            status.rapid_commit = Some(self.rapid_commit_used);
+        }
+        status
+    }
+
+    // The lease acquired or being renewed, None before DHCPACK received.
+    // In `Inform` state, it holds the options of last DHCPACK.
+    pub fn lease(&self) -> Option<&DhcpLease> {
+        match self.state {
+            DhcpState::Bound
+            | DhcpState::Renewing
+            | DhcpState::Rebinding
+            | DhcpState::Inform => self.lease.as_ref(),
+            _ => None,
+        }
+    }
+
+    pub fn leased_address(&self) -> Option<Ipv4Addr> {
+        match self.state {
+            DhcpState::Inform => None,
+            _ => self.lease().map(|l| l.address),
+        }
+    }
+
+    pub fn inform_address(&self) -> Option<Ipv4Addr> {
+        self.inform_address
+    }
+
+    // Transaction ID of current exchange.
+    pub fn xid(&self) -> u32 {
+        self.xid
+    }
+
+    pub fn start(&mut self, now: SystemTime) {
+        if self.state == DhcpState::Stopped {
+            self.fail_at = Some(now + self.config.timeout);
+            self.schedule_link_local(now);
+            self.enter_init(now);
+        }
+    }
+
+    // Acquire options for the statically configured `address` by
+    // broadcasting DHCPINFORM(RFC 2131 section 3.4), repeated every
+    // `inform_interval` after DHCPACK.
+    pub fn start_inform(&mut self, now: SystemTime, address: Ipv4Addr) {
+        if self.state == DhcpState::Stopped {
+            self.fail_at = Some(now + self.config.timeout);
+            self.inform_address = Some(address);
+            self.send_inform(now);
+        }
+    }
+
+    // Start with the address of previous lease by broadcasting DHCPREQUEST
+    // without server identifier(INIT-REBOOT), fallback to DHCPDISCOVER on
+    // DHCPNAK or no reply within 10 seconds.
+    pub fn start_init_reboot(&mut self, now: SystemTime, address: Ipv4Addr) {
+        if self.state == DhcpState::Stopped {
+            self.fail_at = Some(now + self.config.timeout);
+            self.schedule_link_local(now);
+            self.enter_init_reboot(now, address);
+        }
+    }
+
+    pub fn stop(&mut self) {
+        self.clear_timers();
+        self.lease = None;
+        self.probing_lease = None;
+        self.fail_at = None;
+        self.link_local_at = None;
+        self.link_local = None;
+        self.inform_address = None;
+        self.state = DhcpState::Stopped;
+    }
+
+    // Give up the lease by queuing DHCPRELEASE unicast to the server, then
+    // stop. Identical to `stop()` when no lease is held or in `Inform`
+    // state.
+    pub fn release(&mut self) {
+        let lease = self
+            .lease
+            .as_ref()
+            .filter(|_| self.state != DhcpState::Inform);
+        if let Some(lease) = lease {
+            self.xid = self.rand.next_u32();
+            let mut msg = self.new_message(DhcpV4MessageType::Release);
+            msg.ciaddr = lease.address;
+            msg.options
+                .push(DhcpOption::ServerIdentifier(lease.server_id));
+            self.transmits.push_back(DhcpV4Transmit {
+                msg,
+                dst: lease.server_id,
+            });
+        }
+        self.stop();
+    }
+
+    // The time `handle_timeout()` should be invoked.
+    pub fn poll_timeout(&self) -> Option<SystemTime> {
+        [
+            self.retransmit_at,
+            self.reboot_until,
+            self.probe_at,
+            self.restart_at,
+            self.t1,
+            self.t2,
+            self.expiry,
+            self.fail_at,
+            self.inform_at,
+            self.link_local_at,
+            self.link_local.as_ref().and_then(|l| l.poll_timeout()),
+        ]
+        .iter()
+        .flatten()
+        .min()
+        .copied()
+    }
+
+    pub fn poll_transmit(&mut self) -> Option<DhcpV4Transmit> {
+        self.transmits.pop_front()
+    }
+
+    pub fn poll_event(&mut self) -> Option<DhcpV4Event> {
+        self.events.pop_front()
+    }
+
+    // ARP packets to broadcast on the interface.
+    pub fn poll_arp_transmit(&mut self) -> Option<DhcpV4ArpPacket> {
+        self.arp_transmits
+            .pop_front()
+            .or_else(|| self.link_local.as_mut()?.poll_arp_transmit())
+    }
+
+    // Process an ARP packet received on the interface while `needs_arp()`.
+    pub fn handle_arp(&mut self, packet: &DhcpV4ArpPacket, now: SystemTime) {
+        if let Some(ll) = self.link_local.as_mut() {
+            let event = ll.handle_arp(packet, &mut self.rand, now);
+            self.push_link_local_event(event);
+        }
+        let lease = match (&self.probing_lease, self.state) {
+            (Some(l), DhcpState::Probing) => l,
+            _ => return,
+        };
+        if packet.is_conflict(&self.config.mac, lease.address) {
+            self.decline(now);
+        }
+    }
+
+    pub fn handle_timeout(&mut self, now: SystemTime) {
+        if is_due(self.fail_at, now) {
+            self.fail_at = None;
+            self.events.push_back(DhcpV4Event::Failed);
+        }
+        if is_due(self.link_local_at, now) {
+            self.link_local_at = None;
+            self.link_local =
+                Some(LinkLocal::new(&self.config.mac, &mut self.rand, now));
+        }
+        if let Some(ll) = self.link_local.as_mut() {
+            let event = ll.handle_timeout(&mut self.rand, now);
+            self.push_link_local_event(event);
+        }
+        if is_due(self.expiry, now) {
+            self.lose_lease(now);
+            self.enter_init(now);
+        } else if is_due(self.t2, now) {
+            self.t1 = None;
+            self.t2 = None;
+            self.state = DhcpState::Rebinding;
+            let msg = self.new_renew_request(true);
+            self.transmit(msg, Ipv4Addr::BROADCAST, now);
+        } else if is_due(self.t1, now) {
+            self.t1 = None;
+            self.state = DhcpState::Renewing;
+            let dst = self.server_id.unwrap_or(Ipv4Addr::BROADCAST);
+            let msg = self.new_renew_request(false);
+            self.transmit(msg, dst, now);
+        } else if is_due(self.reboot_until, now) || is_due(self.restart_at, now)
+        {
+            self.enter_init(now);
+        } else if is_due(self.inform_at, now) {
+            self.send_inform(now);
+        } else if is_due(self.probe_at, now) {
+            if self.probes_left > 0 {
+                self.send_arp_probe(now);
+            } else if let Some(lease) = self.probing_lease.take() {
+                self.probe_at = None;
+                self.bind(lease, DhcpState::Requesting);
+            }
+        } else if is_due(self.retransmit_at, now) {
+            match self.state {
+                // Give up on the offer and restart discovery
+                DhcpState::Requesting
+                    if self.retransmit_interval >= RETRANSMIT_INTERVAL_MAX =>
+                {
+                    self.enter_init(now)
+                }
+                _ => {
+                    if self.retransmit_interval < RETRANSMIT_INTERVAL_MAX {
+                        self.retransmit_interval *= 2;
+                    }
+                    self.send_pending(now);
+                }
+            }
+        }
+    }
+
+    // Process a received DHCP message, messages not for us are ignored.
+    pub fn handle_message(&mut self, msg: &DhcpV4Message, now: SystemTime) {
+        if msg.op != DhcpV4Op::BootReply
+            || msg.xid != self.xid
+            || msg.hw_addr() != self.config.mac
+        {
+            return;
+        }
+        let msg_type = match msg.message_type() {
+            Some(t) => t,
+            None => return,
+        };
+        match (self.state, msg_type) {
+            (DhcpState::Selecting, DhcpV4MessageType::Offer) => {
+                self.process_offer(msg, now)
+            }
+            // RFC 4039 section 4.1: DHCPACK with rapid commit option
+            (DhcpState::Selecting, DhcpV4MessageType::Ack)
+                if self.config.rapid_commit
+                    && msg.options.contains(&DhcpOption::RapidCommit) =>
+            {
+                self.process_ack(msg, now)
+            }
+            (
+                DhcpState::Requesting
+                | DhcpState::Rebooting
+                | DhcpState::Renewing
+                | DhcpState::Rebinding,
+                DhcpV4MessageType::Ack,
+            ) => self.process_ack(msg, now),
+            (DhcpState::Inform, DhcpV4MessageType::Ack) => {
+                self.process_inform_ack(msg, now)
+            }
+            (
+                DhcpState::Requesting
+                | DhcpState::Rebooting
+                | DhcpState::Renewing
+                | DhcpState::Rebinding,
+                DhcpV4MessageType::Nak,
+            ) if self.is_nak_expected(msg) => {
+                self.lose_lease(now);
+                self.enter_init(now);
+            }
+            _ => (),
+        }
+    }
+
+    // DHCPNAK must come from the server we talk to. Server is unknown in
+    // INIT-REBOOT, and any server could answer the broadcast DHCPREQUEST of
+    // REBINDING.
+    fn is_nak_expected(&self, nak: &DhcpV4Message) -> bool {
+        self.state == DhcpState::Rebinding
+            || self.server_id.is_none()
+            || nak.server_identifier() == self.server_id
+    }
+
+    fn lose_lease(&mut self, now: SystemTime) {
+        if let Some(lease) = self.lease.take() {
+            self.events.push_back(DhcpV4Event::LeaseLost(lease));
+            self.fail_at = Some(now + self.config.timeout);
+            self.schedule_link_local(now);
+        }
+    }
+
+    fn clear_timers(&mut self) {
+        self.pending = None;
+        self.retransmit_at = None;
+        self.reboot_until = None;
+        self.probe_at = None;
+        self.restart_at = None;
+        self.t1 = None;
+        self.t2 = None;
+        self.expiry = None;
+        self.inform_at = None;
+    }
+
+    // Restart the whole configuration process by sending DHCPDISCOVER.
+    fn enter_init(&mut self, now: SystemTime) {
+        self.state = DhcpState::Init;
+        self.clear_timers();
+        self.probing_lease = None;
+        self.server_id = None;
+        self.offered_ip = None;
+        self.xid = self.rand.next_u32();
+        let mut msg = self.new_request(DhcpV4MessageType::Discover);
+        if self.config.rapid_commit {
+            msg.options.push(DhcpOption::RapidCommit);
+        }
+        self.state = DhcpState::Selecting;
+        self.transmit(msg, Ipv4Addr::BROADCAST, now);
+    }
+
+    // The options of previous DHCPACK are kept till new one arrives.
+    fn send_inform(&mut self, now: SystemTime) {
+        let address = match self.inform_address {
+            Some(a) => a,
+            None => return,
+        };
+        self.state = DhcpState::Inform;
+        self.clear_timers();
+        self.xid = self.rand.next_u32();
+        let mut msg = self.new_request(DhcpV4MessageType::Inform);
+        msg.ciaddr = address;
+        self.transmit(msg, Ipv4Addr::BROADCAST, now);
+    }
+
+    fn process_inform_ack(&mut self, msg: &DhcpV4Message, now: SystemTime) {
+        let address = match self.inform_address {
+            Some(a) => a,
+            None => return,
+        };
+        // DHCPACK of DHCPINFORM has no yiaddr
+        let mut ack = msg.clone();
+        ack.yiaddr = address;
+        let lease = DhcpLease::new_from_ack(&ack, None, now);
+        self.clear_timers();
+        self.fail_at = None;
+        self.inform_at = Some(now + self.config.inform_interval);
+        self.lease = Some(lease.clone());
+        self.events.push_back(DhcpV4Event::OptionsAcquired(lease));
+    }
+
+    fn enter_init_reboot(&mut self, now: SystemTime, address: Ipv4Addr) {
+        self.state = DhcpState::InitReboot;
+        self.clear_timers();
+        self.server_id = None;
+        self.offered_ip = Some(address);
+        self.xid = self.rand.next_u32();
+        let mut msg = self.new_request(DhcpV4MessageType::Request);
+        msg.options.push(DhcpOption::RequestedIp(address));
+        self.state = DhcpState::Rebooting;
+        self.transmit(msg, Ipv4Addr::BROADCAST, now);
+        self.reboot_until = Some(now + INIT_REBOOT_TIMEOUT);
+    }
+
+    // RFC 2131 section 4.2: client identifier, once used, must be in all
+    // messages to the server.
+    fn new_message(&self, msg_type: DhcpV4MessageType) -> DhcpV4Message {
+        let mut msg = DhcpV4Message::new_request(self.xid, &self.config.mac);
+        msg.options.push(DhcpOption::MessageType(msg_type));
+        if let Some(client_id) = &self.config.client_id {
+            msg.options
+                .push(DhcpOption::ClientIdentifier(client_id.clone()));
+        }
+        msg
+    }
+
+    fn new_request(&self, msg_type: DhcpV4MessageType) -> DhcpV4Message {
+        let mut msg = self.new_message(msg_type);
+        match (&self.config.fqdn, &self.config.host_name) {
+            (Some(fqdn), _) => {
+                msg.options.push(DhcpOption::ClientFqdn(fqdn.clone()))
+            }
+            (None, Some(name)) => {
+                msg.options.push(DhcpOption::HostName(name.clone()))
+            }
+            (None, None) => (),
+        }
+        if let Some(vendor_class) = &self.config.vendor_class {
+            msg.options
+                .push(DhcpOption::VendorClass(vendor_class.clone()));
+        }
+        if !self.config.user_class.is_empty() {
+            msg.options
+                .push(DhcpOption::UserClass(self.config.user_class.clone()));
+        }
+        if !self.config.vi_vendor_specific.is_empty() {
+            msg.options.push(DhcpOption::ViVendorSpecific(
+                self.config.vi_vendor_specific.clone(),
+            ));
+        }
+        let mut codes = self.config.parameter_request_list.clone();
+        for (needed, code) in [
+            (
+                self.config.vendor_class.is_some(),
+                DHCP_OPTION_VENDOR_SPECIFIC,
+            ),
+            (
+                !self.config.vi_vendor_specific.is_empty(),
+                DHCP_OPTION_VI_VENDOR_SPECIFIC,
+            ),
+        ] {
+            if needed && !codes.contains(&code) {
+                codes.push(code);
+            }
+        }
+        if !codes.is_empty() {
+            msg.options.push(DhcpOption::ParameterRequestList(codes));
+        }
+        msg.options
+            .extend(self.config.extra_options.iter().cloned());
+        msg
+    }
+
+    fn new_renew_request(&mut self, broadcast: bool) -> DhcpV4Message {
+        self.xid = self.rand.next_u32();
+        let mut msg = self.new_request(DhcpV4MessageType::Request);
+        msg.ciaddr = self.leased_address().unwrap_or(Ipv4Addr::UNSPECIFIED);
+        if broadcast {
+            msg.flags |= DHCP_FLAG_BROADCAST;
+        }
+        msg
+    }
+
+    // Queue the message and schedule its retransmission.
+    fn transmit(&mut self, msg: DhcpV4Message, dst: Ipv4Addr, now: SystemTime) {
+        self.retransmit_interval = RETRANSMIT_INTERVAL_MIN;
+        self.pending = Some(DhcpV4Transmit { msg, dst });
+        self.send_pending(now);
+    }
+
+    fn send_pending(&mut self, now: SystemTime) {
+        if let Some(pending) = &self.pending {
+            self.transmits.push_back(pending.clone());
+        }
+        self.retransmit_at = Some(self.next_retransmit_time(now));
+    }
+
+    fn next_retransmit_time(&mut self, now: SystemTime) -> SystemTime {
+        let deadline = match self.state {
+            DhcpState::Renewing => self.t2,
+            DhcpState::Rebinding => self.expiry,
+            _ => {
+                let jitter =
+                    self.rand.next_u32() % (2 * RETRANSMIT_JITTER_MS + 1);
+                return now
+                    + self.retransmit_interval
+                    + Duration::from_millis(jitter as u64)
+                    - Duration::from_millis(RETRANSMIT_JITTER_MS as u64);
+            }
+        };
+        let wait = match deadline.and_then(|d| d.duration_since(now).ok()) {
+            Some(remain) => std::cmp::max(remain / 2, RENEW_RETRANSMIT_MIN),
+            None => RENEW_RETRANSMIT_MIN,
+        };
+        now + wait
+    }
+
+    fn process_offer(&mut self, offer: &DhcpV4Message, now: SystemTime) {
+        let server_id = match offer.server_identifier() {
+            Some(s) => s,
+            None => return,
+        };
+        self.server_id = Some(server_id);
+        self.offered_ip = Some(offer.yiaddr);
+        let mut msg = self.new_request(DhcpV4MessageType::Request);
+        msg.options.push(DhcpOption::RequestedIp(offer.yiaddr));
+        msg.options.push(DhcpOption::ServerIdentifier(server_id));
+        self.state = DhcpState::Requesting;
+        self.transmit(msg, Ipv4Addr::BROADCAST, now);
+    }
+
+    fn process_ack(&mut self, ack: &DhcpV4Message, now: SystemTime) {
+        let prev_state = self.state;
+        // RFC 2131 section 4.4.1: DHCPACK of the selected server for the
+        // offered address, or for the requested address in INIT-REBOOT,
+        // keep waiting otherwise.
+        let expected = match prev_state {
+            DhcpState::Requesting => {
+                ack.server_identifier() == self.server_id
+                    && Some(ack.yiaddr) == self.offered_ip
+            }
+            DhcpState::Rebooting => Some(ack.yiaddr) == self.offered_ip,
+            _ => true,
+        };
+        if ack.yiaddr.is_unspecified() || !expected {
+            return;
+        }
+        self.clear_timers();
+        let lease = DhcpLease::new_from_ack(ack, self.server_id, now);
+        if prev_state != DhcpState::Requesting {
+            self.server_id = Some(lease.server_id);
+            self.offered_ip = Some(lease.address);
+        }
+        match prev_state {
+            DhcpState::Rebooting => {
+                self.lease_reused = true;
+                self.rapid_commit_used = false;
+            }
+            DhcpState::Requesting | DhcpState::Selecting => {
+                self.lease_reused = false;
+                self.rapid_commit_used = prev_state == DhcpState::Selecting;
+            }
+            _ => (),
+        }
+        // Only new address needs conflict detection, renewed one is
+        // already in use by us.
+        if matches!(
+            prev_state,
+            DhcpState::Requesting | DhcpState::Rebooting | DhcpState::Selecting
+        ) && self.config.arp_probe_count > 0
+        {
+            self.state = DhcpState::Probing;
+            self.probing_lease = Some(lease);
+            self.probes_left = self.config.arp_probe_count;
+            self.send_arp_probe(now);
+        } else {
+            self.bind(lease, prev_state);
+        }
+    }
+
+    fn bind(&mut self, lease: DhcpLease, prev_state: DhcpState) {
+        self.fail_at = None;
+        // Real lease replaces the link-local address
+        self.link_local_at = None;
+        if let Some(address) = self.link_local.take().and_then(|l| l.address())
+        {
+            self.events.push_back(DhcpV4Event::LinkLocalLost(address));
+        }
+        if let Some((t1, t2, expiry)) = lease.timers() {
+            self.t1 = Some(t1);
+            self.t2 = Some(t2);
+            self.expiry = Some(expiry);
+        }
+        self.state = DhcpState::Bound;
+        self.lease = Some(lease.clone());
+        self.events.push_back(match prev_state {
+            DhcpState::Renewing => DhcpV4Event::LeaseRenewed(lease),
+            DhcpState::Rebinding => DhcpV4Event::LeaseRebound(lease),
+            _ => DhcpV4Event::LeaseAcquired(lease),
+        });
+    }
+
+    fn schedule_link_local(&mut self, now: SystemTime) {
+        if self.link_local.is_none() {
+            self.link_local_at =
+                self.config.link_local_timeout.map(|t| now + t);
+        }
+    }
+
+    fn push_link_local_event(&mut self, event: Option<LinkLocalEvent>) {
+        match event {
+            Some(LinkLocalEvent::Claimed(address)) => self
+                .events
+                .push_back(DhcpV4Event::LinkLocalAcquired(address)),
+            Some(LinkLocalEvent::Lost(address)) => {
+                self.events.push_back(DhcpV4Event::LinkLocalLost(address))
+            }
+            None => (),
+        }
+    }
+
+    fn send_arp_probe(&mut self, now: SystemTime) {
+        if let Some(lease) = &self.probing_lease {
+            self.arp_transmits.push_back(DhcpV4ArpPacket::new_probe(
+                &self.config.mac,
+                lease.address,
+            ));
+            self.probes_left = self.probes_left.saturating_sub(1);
+            self.probe_at = Some(now + self.config.arp_probe_timeout);
+        }
+    }
+
+    // Tell the server the address is in use, then restart configuration
+    // after backoff.
+    fn decline(&mut self, now: SystemTime) {
+        let lease = match self.probing_lease.take() {
+            Some(l) => l,
+            None => return,
+        };
+        self.clear_timers();
+        let mut msg = self.new_message(DhcpV4MessageType::Decline);
+        msg.options.push(DhcpOption::RequestedIp(lease.address));
+        msg.options
+            .push(DhcpOption::ServerIdentifier(lease.server_id));
+        self.transmits.push_back(DhcpV4Transmit {
+            msg,
+            dst: Ipv4Addr::BROADCAST,
+        });
+        self.events
+            .push_back(DhcpV4Event::AddressDeclined(lease.address));
+        self.state = DhcpState::Init;
+        self.server_id = None;
+        self.offered_ip = None;
+        self.restart_at = Some(now + DECLINE_BACKOFF);
+    }
+}
+
+fn is_due(deadline: Option<SystemTime>, now: SystemTime) -> bool {
+    deadline.map(|t| t <= now) == Some(true)
+}
+
+// Minimal xorshift64* PRNG, only used for transaction ID, not for security.
+#[derive(Debug, Clone)]
+pub(crate) struct XorShift64(u64);
+
+impl XorShift64 {
+    fn new(seed: u64) -> Self {
+        // Zero state will stuck at zero
+        XorShift64(if seed == 0 {
+            0x9e37_79b9_7f4a_7c15
+        } else {
+            seed
+        })
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        self.0 ^= self.0 >> 12;
+        self.0 ^= self.0 << 25;
+        self.0 ^= self.0 >> 27;
+        self.0.wrapping_mul(0x2545_f491_4f6c_dd1d)
+    }
+
+    pub(crate) fn next_u32(&mut self) -> u32 {
+        (self.next_u64() >> 32) as u32
+    }
+}
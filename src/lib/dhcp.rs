@@ -12,16 +12,113 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+use std::net::Ipv4Addr;
+use std::time::{SystemTime, UNIX_EPOCH};
+
 use serde_derive::{Deserialize, Serialize};
 
-#[derive(Deserialize, Serialize, Debug, Clone)]
+use crate::{DhcpFqdnFlags, DhcpLease, DhcpVendorInfo};
+
+// Client states defined in RFC 2131 section 4.4, plus `Probing` for RFC 5227
+// address conflict detection after DHCPACK, `LinkLocal` for RFC 3927 address
+// in use while DHCP retrying, `Inform` for statically addressed interface
+// acquiring options by DHCPINFORM and `Stopped` for interface not managed by
+// mozim.
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq)]
 pub enum DhcpState {
+    Init,
+    Selecting,
     Requesting,
+    Probing,
+    Bound,
+    Renewing,
+    Rebinding,
+    InitReboot,
+    Rebooting,
+    LinkLocal,
+    Inform,
     Stopped,
 }
 
-#[derive(Deserialize, Serialize, Debug, Clone)]
+impl std::fmt::Display for DhcpState {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:?}", self)
+    }
+}
+
+// The timestamps are seconds since UNIX epoch.
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Eq)]
 pub struct DhcpStatus {
     pub iface_name: String,
     pub state: DhcpState,
+    // When the next DHCPREQUEST for renewing lease will be sent
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub renew_at: Option<u64>,
+    // When to start rebinding(T2)
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub rebind_at: Option<u64>,
+    // When the lease expires
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub expire_at: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub lease: Option<DhcpLease>,
+    // Whether the lease of previous run was confirmed by INIT-REBOOT,
+    // false if negotiated by DHCPDISCOVER. Only set along with `lease`.
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub lease_reused: Option<bool>,
+    // Whether the lease was acquired by RFC 4039 two-message exchange
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub rapid_commit: Option<bool>,
+    // RFC 3927 IPv4 link-local address in use
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub link_local: Option<Ipv4Addr>,
+    // Flags of client FQDN option in DHCPACK, telling whether server
+    // updated DNS. Only set along with `lease`, None if server ignored the
+    // option.
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub fqdn_flags: Option<DhcpFqdnFlags>,
+    // Decoded option 43 and 125 of the lease
+    #[serde(skip_serializing_if = "Vec::is_empty", default)]
+    pub vendor_info: Vec<DhcpVendorInfo>,
+}
+
+impl DhcpStatus {
+    pub fn new(iface_name: &str, state: DhcpState) -> Self {
+        DhcpStatus {
+            iface_name: iface_name.to_string(),
+            state,
+            renew_at: None,
+            rebind_at: None,
+            expire_at: None,
+            lease: None,
+            lease_reused: None,
+            rapid_commit: None,
+            link_local: None,
+            fqdn_flags: None,
+            vendor_info: Vec::new(),
+        }
+    }
+}
+
+// Colon separated lower case hex string, like `01:00:23`.
+pub fn to_hex(data: &[u8]) -> String {
+    data.iter()
+        .map(|b| format!("{:02x}", b))
+        .collect::<Vec<String>>()
+        .join(":")
+}
+
+// Space separated addresses, like `192.0.2.1 192.0.2.2`.
+pub fn join_addrs(addrs: &[Ipv4Addr]) -> String {
+    addrs
+        .iter()
+        .map(|a| a.to_string())
+        .collect::<Vec<String>>()
+        .join(" ")
+}
+
+pub(crate) fn to_timestamp(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
 }
@@ -0,0 +1,119 @@
+// Copyright 2020 Red Hat, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+// DNS wire format(RFC 1035 section 3.1) of domain names used by DHCP
+// options.
+
+const DNS_POINTER_FLAG: u8 = 0xc0;
+const DNS_MAX_LABEL_LEN: usize = 63;
+
+// Parse list of domain names which might use compression pointers
+// referring to earlier data in the list, as RFC 3397 required for option
+// 119. Return None on malformed data.
+pub(crate) fn parse_dns_names(data: &[u8]) -> Option<Vec<String>> {
+    let mut names = Vec::new();
+    let mut pos = 0;
+    while pos < data.len() {
+        let (name, next) = parse_dns_name(data, pos)?;
+        names.push(name);
+        pos = next;
+    }
+    Some(names)
+}
+
+// Parse the name at `start`, return the name and the position after it.
+pub(crate) fn parse_dns_name(
+    data: &[u8],
+    start: usize,
+) -> Option<(String, usize)> {
+    let mut labels: Vec<String> = Vec::new();
+    let mut pos = start;
+    let mut next = None;
+    // Pointers must refer to earlier data, this also prevent loops.
+    let mut limit = start;
+    loop {
+        let len = *data.get(pos)? as usize;
+        if len == 0 {
+            pos += 1;
+            break;
+        }
+        if len as u8 & DNS_POINTER_FLAG == DNS_POINTER_FLAG {
+            let low = *data.get(pos + 1)? as usize;
+            let target = ((len & 0x3f) << 8) | low;
+            if target >= limit {
+                return None;
+            }
+            if next.is_none() {
+                next = Some(pos + 2);
+            }
+            limit = target;
+            pos = target;
+            continue;
+        }
+        if len > DNS_MAX_LABEL_LEN {
+            return None;
+        }
+        let label = data.get(pos + 1..pos + 1 + len)?;
+        labels.push(String::from_utf8_lossy(label).to_string());
+        pos += 1 + len;
+    }
+    Some((labels.join("."), next.unwrap_or(pos)))
+}
+
+// Encode name without compression, trailing dot is ignored.
+pub(crate) fn emit_dns_name(name: &str) -> Vec<u8> {
+    let mut buf = Vec::new();
+    for label in name.trim_end_matches('.').split('.') {
+        if label.is_empty() {
+            continue;
+        }
+        let label = &label.as_bytes()[..label.len().min(DNS_MAX_LABEL_LEN)];
+        buf.push(label.len() as u8);
+        buf.extend_from_slice(label);
+    }
+    buf.push(0);
+    buf
+}
+
+// RFC 4702 section 2.5: single name of client FQDN option without
+// compression, a partial name has no terminating zero length label.
+pub(crate) fn parse_fqdn_name(data: &[u8]) -> Option<String> {
+    let mut labels: Vec<String> = Vec::new();
+    let mut pos = 0;
+    while pos < data.len() {
+        let len = data[pos] as usize;
+        if len == 0 {
+            if pos + 1 != data.len() {
+                return None;
+            }
+            break;
+        }
+        if len > DNS_MAX_LABEL_LEN {
+            return None;
+        }
+        let label = data.get(pos + 1..pos + 1 + len)?;
+        labels.push(String::from_utf8_lossy(label).to_string());
+        pos += 1 + len;
+    }
+    Some(labels.join("."))
+}
+
+// Name with single label is encoded as partial name.
+pub(crate) fn emit_fqdn_name(name: &str) -> Vec<u8> {
+    let mut buf = emit_dns_name(name);
+    if !name.trim_end_matches('.').contains('.') {
+        buf.pop();
+    }
+    buf
+}
@@ -0,0 +1,155 @@
+// Copyright 2020 Red Hat, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+// DHCP Unique Identifier of RFC 8415 section 11 and the RFC 4361 client
+// identifier built from it. Generating and storing the DUID is left to
+// the caller, as it should stay the same across reboot and NIC change.
+
+use std::time::{Duration, SystemTime};
+
+use serde_derive::{Deserialize, Serialize};
+
+use crate::msg::ARP_HW_TYPE_ETHERNET;
+use crate::MozimError;
+
+const DUID_TYPE_LLT: u16 = 1;
+const DUID_TYPE_EN: u16 = 2;
+const DUID_TYPE_LL: u16 = 3;
+// RFC 6355
+const DUID_TYPE_UUID: u16 = 4;
+// RFC 8415 section 11.2: seconds since midnight UTC, January 1, 2000
+const DUID_TIME_EPOCH: Duration = Duration::from_secs(946_684_800);
+// RFC 4361 section 6.1
+const CLIENT_ID_TYPE_IAID_DUID: u8 = 255;
+
+#[derive(
+    Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq, Default,
+)]
+#[serde(rename_all = "snake_case")]
+pub enum DhcpDuidType {
+    #[default]
+    Llt,
+    Ll,
+    En,
+    Uuid,
+}
+
+impl DhcpDuidType {
+    pub fn code(&self) -> u16 {
+        match self {
+            DhcpDuidType::Llt => DUID_TYPE_LLT,
+            DhcpDuidType::Ll => DUID_TYPE_LL,
+            DhcpDuidType::En => DUID_TYPE_EN,
+            DhcpDuidType::Uuid => DUID_TYPE_UUID,
+        }
+    }
+}
+
+impl std::str::FromStr for DhcpDuidType {
+    type Err = MozimError;
+    fn from_str(s: &str) -> Result<Self, MozimError> {
+        match s {
+            "llt" => Ok(DhcpDuidType::Llt),
+            "ll" => Ok(DhcpDuidType::Ll),
+            "en" => Ok(DhcpDuidType::En),
+            "uuid" => Ok(DhcpDuidType::Uuid),
+            _ => Err(MozimError::invalid_argument(format!(
+                "Invalid DUID type {}, should be llt, ll, en or uuid",
+                s
+            ))),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DhcpDuid {
+    // Link-layer address plus time of generation
+    Llt {
+        time: u32,
+        mac: [u8; 6],
+    },
+    // Vendor assigned identifier with IANA private enterprise number
+    En {
+        enterprise_number: u32,
+        identifier: Vec<u8>,
+    },
+    // Link-layer address only
+    Ll {
+        mac: [u8; 6],
+    },
+    Uuid([u8; 16]),
+}
+
+impl DhcpDuid {
+    // DUID-LLT with the time field taken from `now`.
+    pub fn new_llt(mac: &[u8; 6], now: SystemTime) -> Self {
+        let time = now
+            .duration_since(SystemTime::UNIX_EPOCH + DUID_TIME_EPOCH)
+            .map(|d| d.as_secs() as u32)
+            .unwrap_or(0);
+        DhcpDuid::Llt { time, mac: *mac }
+    }
+
+    pub fn duid_type(&self) -> DhcpDuidType {
+        match self {
+            DhcpDuid::Llt { .. } => DhcpDuidType::Llt,
+            DhcpDuid::En { .. } => DhcpDuidType::En,
+            DhcpDuid::Ll { .. } => DhcpDuidType::Ll,
+            DhcpDuid::Uuid(_) => DhcpDuidType::Uuid,
+        }
+    }
+
+    pub fn emit(&self) -> Vec<u8> {
+        let mut data = self.duid_type().code().to_be_bytes().to_vec();
+        match self {
+            DhcpDuid::Llt { time, mac } => {
+                data.extend_from_slice(
+                    &(ARP_HW_TYPE_ETHERNET as u16).to_be_bytes(),
+                );
+                data.extend_from_slice(&time.to_be_bytes());
+                data.extend_from_slice(mac);
+            }
+            DhcpDuid::En {
+                enterprise_number,
+                identifier,
+            } => {
+                data.extend_from_slice(&enterprise_number.to_be_bytes());
+                data.extend_from_slice(identifier);
+            }
+            DhcpDuid::Ll { mac } => {
+                data.extend_from_slice(
+                    &(ARP_HW_TYPE_ETHERNET as u16).to_be_bytes(),
+                );
+                data.extend_from_slice(mac);
+            }
+            DhcpDuid::Uuid(uuid) => data.extend_from_slice(uuid),
+        }
+        data
+    }
+}
+
+// RFC 4361 section 6.1: type 255, 4 bytes IAID then the DUID.
+pub fn gen_client_id_rfc4361(iaid: u32, duid: &[u8]) -> Vec<u8> {
+    let mut data = vec![CLIENT_ID_TYPE_IAID_DUID];
+    data.extend_from_slice(&iaid.to_be_bytes());
+    data.extend_from_slice(duid);
+    data
+}
+
+// RFC 2132 section 9.14: hardware type followed by the MAC address.
+pub fn gen_client_id_mac(mac: &[u8; 6]) -> Vec<u8> {
+    let mut data = vec![ARP_HW_TYPE_ETHERNET];
+    data.extend_from_slice(mac);
+    data
+}
@@ -19,6 +19,13 @@ use serde_derive::{Deserialize, Serialize};
 pub enum ErrorKind {
     IpcError,
     InvalidIpcCommand,
+    InvalidArgument,
+    InvalidDhcpMessage,
+    SocketError,
+    NetlinkError,
+    HookError,
+    FileError,
+    DbusError,
     MozimBug,
 }
 
@@ -38,13 +45,55 @@ impl MozimError {
     pub fn bug(msg: String) -> MozimError {
         MozimError {
             kind: ErrorKind::MozimBug,
-            msg: msg,
+            msg,
         }
     }
     pub fn invalid_ipc_command(msg: String) -> MozimError {
         MozimError {
             kind: ErrorKind::InvalidIpcCommand,
-            msg: msg,
+            msg,
+        }
+    }
+    pub fn invalid_argument(msg: String) -> MozimError {
+        MozimError {
+            kind: ErrorKind::InvalidArgument,
+            msg,
+        }
+    }
+    pub fn socket_error(msg: String) -> MozimError {
+        MozimError {
+            kind: ErrorKind::SocketError,
+            msg,
+        }
+    }
+    pub fn invalid_dhcp_message(msg: String) -> MozimError {
+        MozimError {
+            kind: ErrorKind::InvalidDhcpMessage,
+            msg,
+        }
+    }
+    pub fn netlink_error(msg: String) -> MozimError {
+        MozimError {
+            kind: ErrorKind::NetlinkError,
+            msg,
+        }
+    }
+    pub fn hook_error(msg: String) -> MozimError {
+        MozimError {
+            kind: ErrorKind::HookError,
+            msg,
+        }
+    }
+    pub fn file_error(msg: String) -> MozimError {
+        MozimError {
+            kind: ErrorKind::FileError,
+            msg,
+        }
+    }
+    pub fn dbus_error(msg: String) -> MozimError {
+        MozimError {
+            kind: ErrorKind::DbusError,
+            msg,
         }
     }
 }
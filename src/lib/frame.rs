@@ -0,0 +1,158 @@
+// Copyright 2020 Red Hat, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+// Ethernet frames of DHCP message for the raw socket, and the BPF filter
+// picking them.
+
+use std::net::Ipv4Addr;
+
+use crate::socket::{inet_checksum, DHCP_CLIENT_PORT};
+
+pub(crate) const ETH_HDR_LEN: usize = 14;
+const IPV4_HDR_LEN: usize = 20;
+const UDP_HDR_LEN: usize = 8;
+pub(crate) const ETH_P_IP: u16 = 0x0800;
+pub(crate) const ETH_P_ARP: u16 = 0x0806;
+pub(crate) const ETH_BROADCAST: [u8; 6] = [0xff; 6];
+const IP_PROTO_UDP: u8 = 17;
+const IP_DEFAULT_TTL: u8 = 64;
+// Offset of DHCP xid in the UDP payload
+const DHCP_XID_OFFSET: u32 = 4;
+
+// Classic BPF opcodes from linux/filter.h
+const BPF_LD_H_ABS: u16 = 0x28;
+const BPF_LD_B_ABS: u16 = 0x30;
+const BPF_LD_H_IND: u16 = 0x48;
+const BPF_LD_W_IND: u16 = 0x40;
+const BPF_LDX_B_MSH: u16 = 0xb1;
+const BPF_JEQ_K: u16 = 0x15;
+const BPF_JSET_K: u16 = 0x45;
+const BPF_RET_K: u16 = 0x06;
+
+fn bpf_stmt(code: u16, k: u32) -> libc::sock_filter {
+    bpf_jump(code, k, 0, 0)
+}
+
+fn bpf_jump(code: u16, k: u32, jt: u8, jf: u8) -> libc::sock_filter {
+    libc::sock_filter { code, jt, jf, k }
+}
+
+// Accept only UDP to DHCP client port holding the transaction ID.
+pub(crate) fn gen_bpf_filter(xid: u32) -> Vec<libc::sock_filter> {
+    let ip_offset = ETH_HDR_LEN as u32;
+    vec![
+        // Ethernet type is IPv4
+        bpf_stmt(BPF_LD_H_ABS, 12),
+        bpf_jump(BPF_JEQ_K, ETH_P_IP as u32, 0, 10),
+        // IP protocol is UDP
+        bpf_stmt(BPF_LD_B_ABS, ip_offset + 9),
+        bpf_jump(BPF_JEQ_K, IP_PROTO_UDP as u32, 0, 8),
+        // Not a fragment
+        bpf_stmt(BPF_LD_H_ABS, ip_offset + 6),
+        bpf_jump(BPF_JSET_K, 0x1fff, 6, 0),
+        // X = IP header length
+        bpf_stmt(BPF_LDX_B_MSH, ip_offset),
+        // UDP destination port
+        bpf_stmt(BPF_LD_H_IND, ip_offset + 2),
+        bpf_jump(BPF_JEQ_K, DHCP_CLIENT_PORT as u32, 0, 3),
+        // DHCP xid
+        bpf_stmt(
+            BPF_LD_W_IND,
+            ip_offset + UDP_HDR_LEN as u32 + DHCP_XID_OFFSET,
+        ),
+        bpf_jump(BPF_JEQ_K, xid, 0, 1),
+        bpf_stmt(BPF_RET_K, u32::MAX),
+        bpf_stmt(BPF_RET_K, 0),
+    ]
+}
+
+// Broadcast Ethernet frame holding UDP payload.
+pub(crate) fn gen_frame(
+    src_mac: &[u8; 6],
+    src_ip: Ipv4Addr,
+    dst_ip: Ipv4Addr,
+    src_port: u16,
+    dst_port: u16,
+    payload: &[u8],
+) -> Vec<u8> {
+    let udp_len = UDP_HDR_LEN + payload.len();
+    let ip_len = IPV4_HDR_LEN + udp_len;
+    let mut frame = Vec::with_capacity(ETH_HDR_LEN + ip_len);
+
+    frame.extend_from_slice(&ETH_BROADCAST);
+    frame.extend_from_slice(src_mac);
+    frame.extend_from_slice(&ETH_P_IP.to_be_bytes());
+
+    let mut ip_hdr = [0u8; IPV4_HDR_LEN];
+    ip_hdr[0] = 0x45;
+    ip_hdr[2..4].copy_from_slice(&(ip_len as u16).to_be_bytes());
+    ip_hdr[8] = IP_DEFAULT_TTL;
+    ip_hdr[9] = IP_PROTO_UDP;
+    ip_hdr[12..16].copy_from_slice(&src_ip.octets());
+    ip_hdr[16..20].copy_from_slice(&dst_ip.octets());
+    let checksum = inet_checksum(&ip_hdr, 0);
+    ip_hdr[10..12].copy_from_slice(&checksum.to_be_bytes());
+    frame.extend_from_slice(&ip_hdr);
+
+    let mut udp = Vec::with_capacity(udp_len);
+    udp.extend_from_slice(&src_port.to_be_bytes());
+    udp.extend_from_slice(&dst_port.to_be_bytes());
+    udp.extend_from_slice(&(udp_len as u16).to_be_bytes());
+    udp.extend_from_slice(&[0, 0]);
+    udp.extend_from_slice(payload);
+    // Pseudo header: source, destination, protocol and UDP length
+    let mut pseudo_sum = IP_PROTO_UDP as u32 + udp_len as u32;
+    for addr in &[src_ip, dst_ip] {
+        let o = addr.octets();
+        pseudo_sum += u16::from_be_bytes([o[0], o[1]]) as u32;
+        pseudo_sum += u16::from_be_bytes([o[2], o[3]]) as u32;
+    }
+    let checksum = match inet_checksum(&udp, pseudo_sum) {
+        // Zero means no checksum in UDP
+        0 => 0xffff,
+        c => c,
+    };
+    udp[6..8].copy_from_slice(&checksum.to_be_bytes());
+    frame.extend_from_slice(&udp);
+    frame
+}
+
+// Return the UDP payload of Ethernet frame sent to DHCP client port.
+pub(crate) fn parse_frame(frame: &[u8]) -> Option<&[u8]> {
+    if frame.len() < ETH_HDR_LEN + IPV4_HDR_LEN + UDP_HDR_LEN
+        || frame[12..14] != ETH_P_IP.to_be_bytes()
+    {
+        return None;
+    }
+    let ip = &frame[ETH_HDR_LEN..];
+    let ihl = ((ip[0] & 0x0f) as usize) * 4;
+    let ip_len = u16::from_be_bytes([ip[2], ip[3]]) as usize;
+    if ip[0] >> 4 != 4
+        || ihl < IPV4_HDR_LEN
+        || ip[9] != IP_PROTO_UDP
+        || ip_len > ip.len()
+        || ihl + UDP_HDR_LEN > ip_len
+    {
+        return None;
+    }
+    let udp = &ip[ihl..ip_len];
+    let udp_len = u16::from_be_bytes([udp[4], udp[5]]) as usize;
+    if u16::from_be_bytes([udp[2], udp[3]]) != DHCP_CLIENT_PORT
+        || udp_len < UDP_HDR_LEN
+        || udp_len > udp.len()
+    {
+        return None;
+    }
+    Some(&udp[UDP_HDR_LEN..udp_len])
+}
@@ -0,0 +1,67 @@
+// Copyright 2020 Red Hat, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::ffi::CString;
+
+use crate::MozimError;
+
+pub fn get_iface_index(iface_name: &str) -> Result<u32, MozimError> {
+    let c_name = CString::new(iface_name).map_err(|e| {
+        MozimError::invalid_argument(format!(
+            "Invalid interface name {}: {}",
+            iface_name, e
+        ))
+    })?;
+    match unsafe { libc::if_nametoindex(c_name.as_ptr()) } {
+        0 => Err(MozimError::invalid_argument(format!(
+            "Failed to find interface {}: {}",
+            iface_name,
+            std::io::Error::last_os_error()
+        ))),
+        index => Ok(index),
+    }
+}
+
+pub fn get_iface_mac(iface_name: &str) -> Result<[u8; 6], MozimError> {
+    let path = format!("/sys/class/net/{}/address", iface_name);
+    let content = std::fs::read_to_string(&path).map_err(|e| {
+        MozimError::invalid_argument(format!(
+            "Failed to read MAC address of interface {}: {}",
+            iface_name, e
+        ))
+    })?;
+    let bytes = content
+        .trim()
+        .split(':')
+        .map(|b| u8::from_str_radix(b, 16))
+        .collect::<Result<Vec<u8>, _>>()
+        .map_err(|e| {
+            MozimError::invalid_argument(format!(
+                "Invalid MAC address {} of interface {}: {}",
+                content.trim(),
+                iface_name,
+                e
+            ))
+        })?;
+    if bytes.len() != 6 {
+        return Err(MozimError::invalid_argument(format!(
+            "Interface {} is not Ethernet, MAC address {}",
+            iface_name,
+            content.trim()
+        )));
+    }
+    let mut mac = [0u8; 6];
+    mac.copy_from_slice(&bytes);
+    Ok(mac)
+}
@@ -0,0 +1,218 @@
+// Copyright 2020 Red Hat, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::net::Ipv4Addr;
+use std::time::{Duration, SystemTime};
+
+use serde_derive::{Deserialize, Serialize};
+
+use crate::dhcp::to_timestamp;
+use crate::{DhcpClasslessRoute, DhcpOption, DhcpV4Message};
+
+// RFC 2131 section 4.4.5: T1 defaults to 0.5 * lease time and T2 to
+// 0.875 * lease time.
+const DEFAULT_T1_RATIO: f64 = 0.5;
+const DEFAULT_T2_RATIO: f64 = 0.875;
+// Lease time 0xffffffff means infinity
+pub const DHCP_INFINITE_LEASE_TIME: u32 = u32::MAX;
+
+// Lease information of DHCPACK. The times are in seconds, the timestamps
+// are seconds since UNIX epoch.
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct DhcpLease {
+    pub address: Ipv4Addr,
+    pub prefix_length: u8,
+    #[serde(skip_serializing_if = "Vec::is_empty", default)]
+    pub gateways: Vec<Ipv4Addr>,
+    #[serde(skip_serializing_if = "Vec::is_empty", default)]
+    pub dns_servers: Vec<Ipv4Addr>,
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub domain: Option<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty", default)]
+    pub search: Vec<String>,
+    pub server_id: Ipv4Addr,
+    // DHCP_INFINITE_LEASE_TIME for infinite lease, T1 and T2 are zero in
+    // that case.
+    pub lease_time: u32,
+    pub t1: u32,
+    pub t2: u32,
+    pub acquired_at: u64,
+    // Seconds till lease expires, only set in `DhcpStatus`
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub remaining: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub mtu: Option<u16>,
+    #[serde(skip_serializing_if = "Vec::is_empty", default)]
+    pub ntp_servers: Vec<Ipv4Addr>,
+    // Option 121, or option 249 if 121 is absent. When not empty, the
+    // `gateways` should not be used for routing as RFC 3442 requires.
+    #[serde(skip_serializing_if = "Vec::is_empty", default)]
+    pub classless_routes: Vec<DhcpClasslessRoute>,
+    // All options of DHCPACK
+    pub options: Vec<DhcpOption>,
+}
+
+impl DhcpLease {
+    // The `server_id` is used when DHCPACK has no server identifier.
+    pub fn new_from_ack(
+        ack: &DhcpV4Message,
+        server_id: Option<Ipv4Addr>,
+        acquired_at: SystemTime,
+    ) -> Self {
+        let mut lease = DhcpLease {
+            address: ack.yiaddr,
+            prefix_length: default_prefix_length(ack.yiaddr),
+            gateways: Vec::new(),
+            dns_servers: Vec::new(),
+            domain: None,
+            search: Vec::new(),
+            server_id: ack
+                .server_identifier()
+                .or(server_id)
+                .unwrap_or(ack.siaddr),
+            lease_time: DHCP_INFINITE_LEASE_TIME,
+            t1: 0,
+            t2: 0,
+            acquired_at: to_timestamp(acquired_at),
+            remaining: None,
+            mtu: None,
+            ntp_servers: Vec::new(),
+            classless_routes: Vec::new(),
+            options: ack.options.clone(),
+        };
+        let mut t1 = None;
+        let mut t2 = None;
+        for opt in &ack.options {
+            match opt {
+                DhcpOption::SubnetMask(mask) => {
+                    lease.prefix_length = u32::from(*mask).count_ones() as u8
+                }
+                DhcpOption::Router(addrs) => lease.gateways = addrs.clone(),
+                DhcpOption::DnsServer(addrs) => {
+                    lease.dns_servers = addrs.clone()
+                }
+                DhcpOption::DomainName(d) => lease.domain = Some(d.clone()),
+                DhcpOption::DomainSearch(names) => lease.search = names.clone(),
+                DhcpOption::LeaseTime(t) => lease.lease_time = *t,
+                DhcpOption::RenewalTime(t) => t1 = Some(*t),
+                DhcpOption::RebindingTime(t) => t2 = Some(*t),
+                DhcpOption::InterfaceMtu(m) => lease.mtu = Some(*m),
+                DhcpOption::NtpServer(addrs) => {
+                    lease.ntp_servers = addrs.clone()
+                }
+                DhcpOption::ClasslessStaticRoute(routes) => {
+                    lease.classless_routes = routes.clone()
+                }
+                DhcpOption::MsClasslessStaticRoute(routes)
+                    if !has_classless_route(&ack.options) =>
+                {
+                    lease.classless_routes = routes.clone()
+                }
+                _ => (),
+            }
+        }
+        if lease.lease_time != DHCP_INFINITE_LEASE_TIME {
+            let (t1, t2) = gen_t1_t2(lease.lease_time, t1, t2);
+            lease.t1 = t1;
+            lease.t2 = t2;
+        }
+        lease
+    }
+
+    pub fn subnet_mask(&self) -> Ipv4Addr {
+        Ipv4Addr::from(prefix_to_mask(self.prefix_length))
+    }
+
+    pub fn network_address(&self) -> Ipv4Addr {
+        Ipv4Addr::from(
+            u32::from(self.address) & prefix_to_mask(self.prefix_length),
+        )
+    }
+
+    // The broadcast address option of DHCPACK, or the one calculated from
+    // prefix length.
+    pub fn broadcast_address(&self) -> Ipv4Addr {
+        for opt in &self.options {
+            if let DhcpOption::BroadcastAddress(addr) = opt {
+                return *addr;
+            }
+        }
+        Ipv4Addr::from(
+            u32::from(self.address) | !prefix_to_mask(self.prefix_length),
+        )
+    }
+
+    pub fn is_infinite(&self) -> bool {
+        self.lease_time == DHCP_INFINITE_LEASE_TIME
+    }
+
+    // Return the time of T1, T2 and expiry, None for infinite lease.
+    pub fn timers(&self) -> Option<(SystemTime, SystemTime, SystemTime)> {
+        if self.is_infinite() {
+            None
+        } else {
+            let acquired_at =
+                SystemTime::UNIX_EPOCH + Duration::from_secs(self.acquired_at);
+            Some((
+                acquired_at + Duration::from_secs(self.t1 as u64),
+                acquired_at + Duration::from_secs(self.t2 as u64),
+                acquired_at + Duration::from_secs(self.lease_time as u64),
+            ))
+        }
+    }
+
+    pub fn is_expired(&self, now: SystemTime) -> bool {
+        match self.timers() {
+            Some((_, _, expiry)) => expiry <= now,
+            None => false,
+        }
+    }
+}
+
+// The T1 and T2 from server are ignored if not `T1 < T2 < lease time`.
+fn gen_t1_t2(lease_time: u32, t1: Option<u32>, t2: Option<u32>) -> (u32, u32) {
+    let default_t1 = (lease_time as f64 * DEFAULT_T1_RATIO) as u32;
+    let default_t2 = (lease_time as f64 * DEFAULT_T2_RATIO) as u32;
+    let t2 = match t2 {
+        Some(t2) if t2 < lease_time => t2,
+        _ => default_t2,
+    };
+    let t1 = match t1 {
+        Some(t1) if t1 < t2 => t1,
+        _ => std::cmp::min(default_t1, t2),
+    };
+    (t1, t2)
+}
+
+pub(crate) fn prefix_to_mask(prefix_length: u8) -> u32 {
+    match prefix_length {
+        0 => 0,
+        p => u32::MAX << (32 - std::cmp::min(p, 32) as u32),
+    }
+}
+
+fn has_classless_route(options: &[DhcpOption]) -> bool {
+    options
+        .iter()
+        .any(|o| matches!(o, DhcpOption::ClasslessStaticRoute(_)))
+}
+
+// Classful prefix length used when server did not send subnet mask.
+fn default_prefix_length(addr: Ipv4Addr) -> u8 {
+    match addr.octets()[0] {
+        0..=127 => 8,
+        128..=191 => 16,
+        _ => 24,
+    }
+}
@@ -12,17 +12,104 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+mod applier;
+mod arp;
+mod client;
 mod dhcp;
+mod dns;
+mod duid;
 mod error;
+mod frame;
+mod iface;
 mod ipc;
+mod lease;
+mod link_local;
+mod msg;
+mod netlink;
+mod option;
+mod raw_socket;
+mod socket;
+mod udp_socket;
+mod vendor;
 
-pub use dhcp::DhcpStatus;
+pub use applier::DhcpApplierType;
+pub use applier::DhcpHookApplier;
+pub use applier::DhcpLeaseApplier;
+pub use applier::DhcpNetlinkApplier;
+pub use applier::DhcpNoneApplier;
+pub use applier::DHCP_DEFAULT_HOOK_TIMEOUT;
+pub use applier::DHCP_DEFAULT_ROUTE_METRIC;
+pub use arp::DhcpV4ArpPacket;
+pub use arp::ARP_OP_REPLY;
+pub use arp::ARP_OP_REQUEST;
+pub use client::DhcpV4Client;
+pub use client::DhcpV4Config;
+pub use client::DhcpV4Event;
+pub use client::DhcpV4Transmit;
+pub use client::DHCP_DEFAULT_ARP_PROBE_TIMEOUT;
+pub use client::DHCP_DEFAULT_INFORM_INTERVAL;
+pub use client::DHCP_DEFAULT_PARAMETER_REQUEST_LIST;
+pub use client::DHCP_DEFAULT_TIMEOUT;
+pub use dhcp::join_addrs;
+pub use dhcp::to_hex;
 pub use dhcp::DhcpState;
+pub use dhcp::DhcpStatus;
+pub use duid::gen_client_id_mac;
+pub use duid::gen_client_id_rfc4361;
+pub use duid::DhcpDuid;
+pub use duid::DhcpDuidType;
 pub use error::ErrorKind;
 pub use error::MozimError;
+pub use iface::get_iface_index;
+pub use iface::get_iface_mac;
 pub use ipc::ipc_bind;
 pub use ipc::ipc_connect;
 pub use ipc::ipc_exec;
 pub use ipc::ipc_recv;
 pub use ipc::ipc_send;
 pub use ipc::MozimResult;
+pub use lease::DhcpLease;
+pub use lease::DHCP_INFINITE_LEASE_TIME;
+pub use msg::DhcpV4Message;
+pub use msg::DhcpV4Op;
+pub use msg::DHCP_FLAG_BROADCAST;
+pub use msg::DHCP_MAGIC_COOKIE;
+pub use option::DhcpClasslessRoute;
+pub use option::DhcpClientFqdn;
+pub use option::DhcpFqdnFlags;
+pub use option::DhcpOption;
+pub use option::DhcpV4MessageType;
+pub use option::DhcpViVendorSpecific;
+pub use option::DHCP_OPTION_BROADCAST_ADDRESS;
+pub use option::DHCP_OPTION_CLASSLESS_STATIC_ROUTE;
+pub use option::DHCP_OPTION_CLIENT_FQDN;
+pub use option::DHCP_OPTION_CLIENT_IDENTIFIER;
+pub use option::DHCP_OPTION_DNS_SERVER;
+pub use option::DHCP_OPTION_DOMAIN_NAME;
+pub use option::DHCP_OPTION_DOMAIN_SEARCH;
+pub use option::DHCP_OPTION_HOST_NAME;
+pub use option::DHCP_OPTION_INTERFACE_MTU;
+pub use option::DHCP_OPTION_LEASE_TIME;
+pub use option::DHCP_OPTION_MAX_MESSAGE_SIZE;
+pub use option::DHCP_OPTION_MESSAGE;
+pub use option::DHCP_OPTION_MESSAGE_TYPE;
+pub use option::DHCP_OPTION_MS_CLASSLESS_STATIC_ROUTE;
+pub use option::DHCP_OPTION_NTP_SERVER;
+pub use option::DHCP_OPTION_PARAMETER_REQUEST_LIST;
+pub use option::DHCP_OPTION_RAPID_COMMIT;
+pub use option::DHCP_OPTION_REBINDING_TIME;
+pub use option::DHCP_OPTION_RENEWAL_TIME;
+pub use option::DHCP_OPTION_REQUESTED_IP;
+pub use option::DHCP_OPTION_ROUTER;
+pub use option::DHCP_OPTION_SERVER_IDENTIFIER;
+pub use option::DHCP_OPTION_SUBNET_MASK;
+pub use option::DHCP_OPTION_USER_CLASS;
+pub use option::DHCP_OPTION_VENDOR_CLASS;
+pub use option::DHCP_OPTION_VENDOR_SPECIFIC;
+pub use option::DHCP_OPTION_VI_VENDOR_SPECIFIC;
+pub use raw_socket::DhcpV4ArpSocket;
+pub use raw_socket::DhcpV4RawSocket;
+pub use udp_socket::DhcpV4UdpSocket;
+pub use vendor::decode_vendor_sub_options;
+pub use vendor::DhcpVendorDecoder;
+pub use vendor::DhcpVendorInfo;
@@ -0,0 +1,212 @@
+// Copyright 2020 Red Hat, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+// I/O free RFC 3927 IPv4 link-local address selection, driven by
+// `DhcpV4Client` while no DHCP server answers.
+
+use std::collections::VecDeque;
+use std::net::Ipv4Addr;
+use std::time::{Duration, SystemTime};
+
+use crate::client::XorShift64;
+use crate::DhcpV4ArpPacket;
+
+// RFC 3927 section 9
+const PROBE_WAIT_MS: u32 = 1000;
+const PROBE_NUM: u32 = 3;
+const PROBE_MIN_MS: u32 = 1000;
+const PROBE_MAX_MS: u32 = 2000;
+const ANNOUNCE_WAIT: Duration = Duration::from_secs(2);
+const ANNOUNCE_NUM: u32 = 2;
+const ANNOUNCE_INTERVAL: Duration = Duration::from_secs(2);
+const MAX_CONFLICTS: u32 = 10;
+const RATE_LIMIT_INTERVAL: Duration = Duration::from_secs(60);
+const DEFEND_INTERVAL: Duration = Duration::from_secs(10);
+
+// RFC 3927 section 2.1: 169.254.1.0 to 169.254.254.255
+const LINK_LOCAL_FIRST: u32 = 0xa9fe_0100;
+const LINK_LOCAL_COUNT: u32 = 0xfe00;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum LinkLocalPhase {
+    // Probes left to send
+    Probing(u32),
+    // Announcements left to send, the address is usable already
+    Announcing(u32),
+    Claimed,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum LinkLocalEvent {
+    Claimed(Ipv4Addr),
+    // Another host insists on using the address, a new one is being probed
+    Lost(Ipv4Addr),
+}
+
+#[derive(Debug, Clone)]
+pub(crate) struct LinkLocal {
+    mac: [u8; 6],
+    address: Ipv4Addr,
+    phase: LinkLocalPhase,
+    conflicts: u32,
+    next_at: Option<SystemTime>,
+    last_defend: Option<SystemTime>,
+    arp_transmits: VecDeque<DhcpV4ArpPacket>,
+}
+
+impl LinkLocal {
+    pub(crate) fn new(
+        mac: &[u8; 6],
+        rand: &mut XorShift64,
+        now: SystemTime,
+    ) -> Self {
+        let mut ll = LinkLocal {
+            mac: *mac,
+            address: Ipv4Addr::UNSPECIFIED,
+            phase: LinkLocalPhase::Probing(PROBE_NUM),
+            conflicts: 0,
+            next_at: None,
+            last_defend: None,
+            arp_transmits: VecDeque::new(),
+        };
+        ll.select_address(rand, now);
+        ll
+    }
+
+    // The address usable by host, None while probing.
+    pub(crate) fn address(&self) -> Option<Ipv4Addr> {
+        match self.phase {
+            LinkLocalPhase::Probing(_) => None,
+            _ => Some(self.address),
+        }
+    }
+
+    pub(crate) fn poll_timeout(&self) -> Option<SystemTime> {
+        self.next_at
+    }
+
+    pub(crate) fn poll_arp_transmit(&mut self) -> Option<DhcpV4ArpPacket> {
+        self.arp_transmits.pop_front()
+    }
+
+    pub(crate) fn handle_timeout(
+        &mut self,
+        rand: &mut XorShift64,
+        now: SystemTime,
+    ) -> Option<LinkLocalEvent> {
+        if self.next_at.map(|t| t <= now) != Some(true) {
+            return None;
+        }
+        match self.phase {
+            LinkLocalPhase::Probing(0) => {
+                self.announce(ANNOUNCE_NUM, now);
+                return Some(LinkLocalEvent::Claimed(self.address));
+            }
+            LinkLocalPhase::Probing(left) => {
+                self.arp_transmits.push_back(DhcpV4ArpPacket::new_probe(
+                    &self.mac,
+                    self.address,
+                ));
+                self.phase = LinkLocalPhase::Probing(left - 1);
+                self.next_at = Some(if left > 1 {
+                    now + random_duration(rand, PROBE_MIN_MS, PROBE_MAX_MS)
+                } else {
+                    now + ANNOUNCE_WAIT
+                });
+            }
+            LinkLocalPhase::Announcing(left) => self.announce(left, now),
+            LinkLocalPhase::Claimed => self.next_at = None,
+        }
+        None
+    }
+
+    pub(crate) fn handle_arp(
+        &mut self,
+        packet: &DhcpV4ArpPacket,
+        rand: &mut XorShift64,
+        now: SystemTime,
+    ) -> Option<LinkLocalEvent> {
+        if !packet.is_conflict(&self.mac, self.address) {
+            return None;
+        }
+        match self.phase {
+            LinkLocalPhase::Probing(_) => {
+                self.conflicts += 1;
+                self.select_address(rand, now);
+                None
+            }
+            // RFC 3927 section 2.5: defend the address once, give it up on
+            // another conflict within DEFEND_INTERVAL.
+            _ if packet.sender_ip != self.address => None,
+            _ => match self.last_defend {
+                Some(t) if t + DEFEND_INTERVAL > now => {
+                    let lost = self.address;
+                    self.conflicts += 1;
+                    self.last_defend = None;
+                    self.select_address(rand, now);
+                    Some(LinkLocalEvent::Lost(lost))
+                }
+                _ => {
+                    self.last_defend = Some(now);
+                    self.arp_transmits.push_back(
+                        DhcpV4ArpPacket::new_announcement(
+                            &self.mac,
+                            self.address,
+                        ),
+                    );
+                    None
+                }
+            },
+        }
+    }
+
+    // Pick a random address and probe it after random delay, slow down
+    // after too many conflicts.
+    fn select_address(&mut self, rand: &mut XorShift64, now: SystemTime) {
+        self.address = Ipv4Addr::from(
+            LINK_LOCAL_FIRST + rand.next_u32() % LINK_LOCAL_COUNT,
+        );
+        self.phase = LinkLocalPhase::Probing(PROBE_NUM);
+        self.next_at = Some(if self.conflicts >= MAX_CONFLICTS {
+            now + RATE_LIMIT_INTERVAL
+        } else {
+            now + random_duration(rand, 0, PROBE_WAIT_MS)
+        });
+    }
+
+    fn announce(&mut self, left: u32, now: SystemTime) {
+        self.arp_transmits
+            .push_back(DhcpV4ArpPacket::new_announcement(
+                &self.mac,
+                self.address,
+            ));
+        if left > 1 {
+            self.phase = LinkLocalPhase::Announcing(left - 1);
+            self.next_at = Some(now + ANNOUNCE_INTERVAL);
+        } else {
+            self.phase = LinkLocalPhase::Claimed;
+            self.next_at = None;
+        }
+    }
+}
+
+fn random_duration(
+    rand: &mut XorShift64,
+    min_ms: u32,
+    max_ms: u32,
+) -> Duration {
+    Duration::from_millis(
+        (min_ms + rand.next_u32() % (max_ms - min_ms + 1)) as u64,
+    )
+}
@@ -0,0 +1,224 @@
+// Copyright 2020 Red Hat, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::convert::TryFrom;
+use std::net::Ipv4Addr;
+
+use serde_derive::{Deserialize, Serialize};
+
+use crate::option::{emit_options, parse_options};
+use crate::{DhcpOption, DhcpV4MessageType, MozimError};
+
+pub const DHCP_MAGIC_COOKIE: [u8; 4] = [99, 130, 83, 99];
+pub const DHCP_CHADDR_LEN: usize = 16;
+pub const DHCP_SNAME_LEN: usize = 64;
+pub const DHCP_FILE_LEN: usize = 128;
+// op(1) htype(1) hlen(1) hops(1) xid(4) secs(2) flags(2) ciaddr(4)
+// yiaddr(4) siaddr(4) giaddr(4) chaddr(16) sname(64) file(128)
+pub const DHCP_BOOTP_HEADER_LEN: usize = 236;
+// BOOTP header plus the magic cookie, the smallest valid DHCP message.
+pub const DHCP_MIN_MSG_LEN: usize = DHCP_BOOTP_HEADER_LEN + 4;
+pub const DHCP_FLAG_BROADCAST: u16 = 0x8000;
+pub const ARP_HW_TYPE_ETHERNET: u8 = 1;
+pub const ETHERNET_HW_ADDR_LEN: u8 = 6;
+
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum DhcpV4Op {
+    BootRequest = 1,
+    BootReply = 2,
+}
+
+impl TryFrom<u8> for DhcpV4Op {
+    type Error = MozimError;
+    fn try_from(d: u8) -> Result<Self, MozimError> {
+        match d {
+            1 => Ok(DhcpV4Op::BootRequest),
+            2 => Ok(DhcpV4Op::BootReply),
+            _ => Err(MozimError::invalid_dhcp_message(format!(
+                "Invalid BOOTP op code {}",
+                d
+            ))),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DhcpV4Message {
+    pub op: DhcpV4Op,
+    pub htype: u8,
+    pub hlen: u8,
+    pub hops: u8,
+    pub xid: u32,
+    pub secs: u16,
+    pub flags: u16,
+    pub ciaddr: Ipv4Addr,
+    pub yiaddr: Ipv4Addr,
+    pub siaddr: Ipv4Addr,
+    pub giaddr: Ipv4Addr,
+    pub chaddr: [u8; DHCP_CHADDR_LEN],
+    // Server host name, NULL terminated on the wire.
+    pub sname: String,
+    // Boot file name, NULL terminated on the wire.
+    pub file: String,
+    // Options in wire order, PAD, END and option overload excluded.
+    pub options: Vec<DhcpOption>,
+}
+
+impl DhcpV4Message {
+    // Create an empty BOOTREQUEST for an Ethernet hardware address.
+    pub fn new_request(xid: u32, mac: &[u8; 6]) -> Self {
+        let mut chaddr = [0u8; DHCP_CHADDR_LEN];
+        chaddr[..mac.len()].copy_from_slice(mac);
+        DhcpV4Message {
+            op: DhcpV4Op::BootRequest,
+            htype: ARP_HW_TYPE_ETHERNET,
+            hlen: ETHERNET_HW_ADDR_LEN,
+            hops: 0,
+            xid,
+            secs: 0,
+            flags: 0,
+            ciaddr: Ipv4Addr::UNSPECIFIED,
+            yiaddr: Ipv4Addr::UNSPECIFIED,
+            siaddr: Ipv4Addr::UNSPECIFIED,
+            giaddr: Ipv4Addr::UNSPECIFIED,
+            chaddr,
+            sname: String::new(),
+            file: String::new(),
+            options: Vec::new(),
+        }
+    }
+
+    pub fn parse(data: &[u8]) -> Result<Self, MozimError> {
+        if data.len() < DHCP_MIN_MSG_LEN {
+            return Err(MozimError::invalid_dhcp_message(format!(
+                "DHCP message too short: {} bytes, expecting at least {}",
+                data.len(),
+                DHCP_MIN_MSG_LEN
+            )));
+        }
+        if data[DHCP_BOOTP_HEADER_LEN..DHCP_MIN_MSG_LEN] != DHCP_MAGIC_COOKIE {
+            return Err(MozimError::invalid_dhcp_message(format!(
+                "Invalid DHCP magic cookie {:?}",
+                &data[DHCP_BOOTP_HEADER_LEN..DHCP_MIN_MSG_LEN]
+            )));
+        }
+        let hlen = data[2];
+        if hlen as usize > DHCP_CHADDR_LEN {
+            return Err(MozimError::invalid_dhcp_message(format!(
+                "Invalid hardware address length {}",
+                hlen
+            )));
+        }
+        let mut chaddr = [0u8; DHCP_CHADDR_LEN];
+        chaddr.copy_from_slice(&data[28..44]);
+        let sname = &data[44..108];
+        let file = &data[108..DHCP_BOOTP_HEADER_LEN];
+        let (options, file_overloaded, sname_overloaded) =
+            parse_options(&data[DHCP_MIN_MSG_LEN..], file, sname)?;
+
+        Ok(DhcpV4Message {
+            op: DhcpV4Op::try_from(data[0])?,
+            htype: data[1],
+            hlen,
+            hops: data[3],
+            xid: u32::from_be_bytes([data[4], data[5], data[6], data[7]]),
+            secs: u16::from_be_bytes([data[8], data[9]]),
+            flags: u16::from_be_bytes([data[10], data[11]]),
+            ciaddr: parse_ipv4(&data[12..16]),
+            yiaddr: parse_ipv4(&data[16..20]),
+            siaddr: parse_ipv4(&data[20..24]),
+            giaddr: parse_ipv4(&data[24..28]),
+            chaddr,
+            sname: if sname_overloaded {
+                String::new()
+            } else {
+                parse_c_string(sname)
+            },
+            file: if file_overloaded {
+                String::new()
+            } else {
+                parse_c_string(file)
+            },
+            options,
+        })
+    }
+
+    pub fn emit(&self) -> Vec<u8> {
+        let mut buf = Vec::with_capacity(DHCP_MIN_MSG_LEN + 64);
+        buf.push(self.op as u8);
+        buf.push(self.htype);
+        buf.push(self.hlen);
+        buf.push(self.hops);
+        buf.extend_from_slice(&self.xid.to_be_bytes());
+        buf.extend_from_slice(&self.secs.to_be_bytes());
+        buf.extend_from_slice(&self.flags.to_be_bytes());
+        buf.extend_from_slice(&self.ciaddr.octets());
+        buf.extend_from_slice(&self.yiaddr.octets());
+        buf.extend_from_slice(&self.siaddr.octets());
+        buf.extend_from_slice(&self.giaddr.octets());
+        buf.extend_from_slice(&self.chaddr);
+        emit_c_string(&mut buf, &self.sname, DHCP_SNAME_LEN);
+        emit_c_string(&mut buf, &self.file, DHCP_FILE_LEN);
+        buf.extend_from_slice(&DHCP_MAGIC_COOKIE);
+        emit_options(&mut buf, &self.options);
+        buf
+    }
+
+    pub fn get_option(&self, code: u8) -> Option<&DhcpOption> {
+        self.options.iter().find(|o| o.code() == code)
+    }
+
+    pub fn message_type(&self) -> Option<DhcpV4MessageType> {
+        self.options.iter().find_map(|o| match o {
+            DhcpOption::MessageType(t) => Some(*t),
+            _ => None,
+        })
+    }
+
+    pub fn server_identifier(&self) -> Option<Ipv4Addr> {
+        self.options.iter().find_map(|o| match o {
+            DhcpOption::ServerIdentifier(a) => Some(*a),
+            _ => None,
+        })
+    }
+
+    // The hardware address trimmed to `hlen`.
+    pub fn hw_addr(&self) -> &[u8] {
+        &self.chaddr[..self.hlen as usize]
+    }
+
+    pub fn is_broadcast(&self) -> bool {
+        self.flags & DHCP_FLAG_BROADCAST > 0
+    }
+}
+
+fn parse_ipv4(data: &[u8]) -> Ipv4Addr {
+    Ipv4Addr::new(data[0], data[1], data[2], data[3])
+}
+
+fn parse_c_string(data: &[u8]) -> String {
+    let end = data.iter().position(|c| *c == 0).unwrap_or(data.len());
+    String::from_utf8_lossy(&data[..end]).to_string()
+}
+
+// Write string into fixed size field with NULL terminator, truncating
+// if required.
+fn emit_c_string(buf: &mut Vec<u8>, s: &str, field_len: usize) {
+    let mut field = vec![0u8; field_len];
+    let bytes = s.as_bytes();
+    let len = std::cmp::min(bytes.len(), field_len - 1);
+    field[..len].copy_from_slice(&bytes[..len]);
+    buf.extend_from_slice(&field);
+}
@@ -0,0 +1,537 @@
+// Copyright 2020 Red Hat, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+// Minimal rtnetlink client applying DHCP lease to the interface.
+
+use std::net::Ipv4Addr;
+use std::os::unix::io::RawFd;
+use std::time::{Duration, SystemTime};
+
+use crate::iface::get_iface_index;
+use crate::lease::prefix_to_mask;
+use crate::{DhcpLease, MozimError};
+
+const NLMSG_HDR_LEN: usize = 16;
+const NLMSG_ALIGN: usize = 4;
+const NLMSG_ERROR: u16 = 2;
+const RTA_HDR_LEN: usize = 4;
+const NETLINK_BUFFER_SIZE: usize = 8192;
+// Kernel acknowledges immediately, wait longer only if it is stuck.
+const NETLINK_REPLY_TIMEOUT: Duration = Duration::from_secs(5);
+// Routes installed by DHCP client, see /etc/iproute2/rt_protos
+const RTPROT_DHCP: u8 = 16;
+const ADDR_LIFETIME_INFINITE: u32 = u32::MAX;
+// RFC 3927: 169.254.0.0/16
+const LINK_LOCAL_PREFIX_LENGTH: u8 = 16;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct DhcpRoute {
+    pub(crate) dst: Ipv4Addr,
+    pub(crate) prefix_length: u8,
+    // None for route to directly connected destination
+    pub(crate) gateway: Option<Ipv4Addr>,
+}
+
+// Add the leased address with lifetimes set to the remaining lease time and
+// the routes of lease. Applying the same lease again refreshes the address
+// lifetimes.
+pub(crate) fn apply_lease(
+    iface_name: &str,
+    lease: &DhcpLease,
+    metric: u32,
+    now: SystemTime,
+) -> Result<(), MozimError> {
+    let iface_index = get_iface_index(iface_name)?;
+    let mut nl = NetlinkSocket::new()?;
+    nl.add_address(
+        iface_index,
+        &DhcpAddress::from(lease),
+        lease_lifetime(lease, now),
+    )?;
+    for route in gen_lease_routes(lease) {
+        nl.add_route(iface_index, &route, lease.address, metric)?;
+    }
+    Ok(())
+}
+
+// Remove the routes and address of lease, those already removed are
+// ignored.
+pub(crate) fn remove_lease(
+    iface_name: &str,
+    lease: &DhcpLease,
+    metric: u32,
+) -> Result<(), MozimError> {
+    let iface_index = get_iface_index(iface_name)?;
+    let mut nl = NetlinkSocket::new()?;
+    for route in gen_lease_routes(lease).iter().rev() {
+        nl.del_route(iface_index, route, metric)?;
+    }
+    nl.del_address(iface_index, &DhcpAddress::from(lease))
+}
+
+// Add the IPv4 link-local address without expiry, no route is needed as
+// the whole 169.254.0.0/16 is on-link.
+pub(crate) fn apply_link_local(
+    iface_name: &str,
+    address: Ipv4Addr,
+) -> Result<(), MozimError> {
+    let iface_index = get_iface_index(iface_name)?;
+    NetlinkSocket::new()?.add_address(
+        iface_index,
+        &DhcpAddress::new_link_local(address),
+        ADDR_LIFETIME_INFINITE,
+    )
+}
+
+pub(crate) fn remove_link_local(
+    iface_name: &str,
+    address: Ipv4Addr,
+) -> Result<(), MozimError> {
+    let iface_index = get_iface_index(iface_name)?;
+    NetlinkSocket::new()?
+        .del_address(iface_index, &DhcpAddress::new_link_local(address))
+}
+
+// Address to add or remove through rtnetlink
+struct DhcpAddress {
+    address: Ipv4Addr,
+    prefix_length: u8,
+    broadcast: Ipv4Addr,
+    scope: u8,
+}
+
+impl From<&DhcpLease> for DhcpAddress {
+    fn from(lease: &DhcpLease) -> Self {
+        DhcpAddress {
+            address: lease.address,
+            prefix_length: lease.prefix_length,
+            broadcast: lease.broadcast_address(),
+            scope: libc::RT_SCOPE_UNIVERSE,
+        }
+    }
+}
+
+impl DhcpAddress {
+    fn new_link_local(address: Ipv4Addr) -> Self {
+        DhcpAddress {
+            address,
+            prefix_length: LINK_LOCAL_PREFIX_LENGTH,
+            broadcast: Ipv4Addr::from(
+                u32::from(address) | !prefix_to_mask(LINK_LOCAL_PREFIX_LENGTH),
+            ),
+            scope: libc::RT_SCOPE_LINK,
+        }
+    }
+}
+
+// The classless static routes of lease if any, otherwise default route
+// through the first router. Directly connected routes come first, and a
+// route to the router not in leased subnet is added before using it.
+pub(crate) fn gen_lease_routes(lease: &DhcpLease) -> Vec<DhcpRoute> {
+    let wanted: Vec<(Ipv4Addr, u8, Ipv4Addr)> =
+        if lease.classless_routes.is_empty() {
+            lease
+                .gateways
+                .first()
+                .map(|gw| (Ipv4Addr::UNSPECIFIED, 0, *gw))
+                .into_iter()
+                .collect()
+        } else {
+            lease
+                .classless_routes
+                .iter()
+                .map(|r| (r.dst, r.prefix_length, r.gateway))
+                .collect()
+        };
+    let mut routes = Vec::new();
+    for (dst, prefix_length, gateway) in wanted.iter() {
+        if gateway.is_unspecified() {
+            routes.push(DhcpRoute {
+                dst: *dst,
+                prefix_length: *prefix_length,
+                gateway: None,
+            });
+        }
+    }
+    for (_, _, gateway) in wanted.iter() {
+        let route = DhcpRoute {
+            dst: *gateway,
+            prefix_length: 32,
+            gateway: None,
+        };
+        if !gateway.is_unspecified()
+            && !is_same_subnet(lease.address, *gateway, lease.prefix_length)
+            && !routes.contains(&route)
+        {
+            routes.push(route);
+        }
+    }
+    for (dst, prefix_length, gateway) in wanted.iter() {
+        if !gateway.is_unspecified() {
+            routes.push(DhcpRoute {
+                dst: *dst,
+                prefix_length: *prefix_length,
+                gateway: Some(*gateway),
+            });
+        }
+    }
+    routes
+}
+
+fn lease_lifetime(lease: &DhcpLease, now: SystemTime) -> u32 {
+    match lease.timers() {
+        None => ADDR_LIFETIME_INFINITE,
+        Some((_, _, expiry)) => expiry
+            .duration_since(now)
+            .map(|d| d.as_secs() as u32)
+            .unwrap_or(0),
+    }
+}
+
+fn is_same_subnet(a: Ipv4Addr, b: Ipv4Addr, prefix_length: u8) -> bool {
+    let mask = prefix_to_mask(prefix_length);
+    u32::from(a) & mask == u32::from(b) & mask
+}
+
+struct NetlinkSocket {
+    fd: RawFd,
+    seq: u32,
+}
+
+impl Drop for NetlinkSocket {
+    fn drop(&mut self) {
+        unsafe {
+            libc::close(self.fd);
+        }
+    }
+}
+
+impl NetlinkSocket {
+    fn new() -> Result<Self, MozimError> {
+        let fd = unsafe {
+            libc::socket(
+                libc::AF_NETLINK,
+                libc::SOCK_RAW | libc::SOCK_CLOEXEC,
+                libc::NETLINK_ROUTE,
+            )
+        };
+        if fd < 0 {
+            return Err(MozimError::netlink_error(format!(
+                "Failed to create netlink socket: {}",
+                std::io::Error::last_os_error()
+            )));
+        }
+        let nl = NetlinkSocket { fd, seq: 0 };
+        let mut addr: libc::sockaddr_nl = unsafe { std::mem::zeroed() };
+        addr.nl_family = libc::AF_NETLINK as libc::sa_family_t;
+        let rc = unsafe {
+            libc::bind(
+                nl.fd,
+                &addr as *const libc::sockaddr_nl as *const libc::sockaddr,
+                std::mem::size_of::<libc::sockaddr_nl>() as libc::socklen_t,
+            )
+        };
+        if rc != 0 {
+            return Err(MozimError::netlink_error(format!(
+                "Failed to bind netlink socket: {}",
+                std::io::Error::last_os_error()
+            )));
+        }
+        // Prevent `request()` from blocking the DHCP thread forever when
+        // the acknowledgement never comes.
+        let timeout = libc::timeval {
+            tv_sec: NETLINK_REPLY_TIMEOUT.as_secs() as libc::time_t,
+            tv_usec: NETLINK_REPLY_TIMEOUT.subsec_micros() as libc::suseconds_t,
+        };
+        let rc = unsafe {
+            libc::setsockopt(
+                nl.fd,
+                libc::SOL_SOCKET,
+                libc::SO_RCVTIMEO,
+                &timeout as *const libc::timeval as *const libc::c_void,
+                std::mem::size_of::<libc::timeval>() as libc::socklen_t,
+            )
+        };
+        if rc != 0 {
+            return Err(MozimError::netlink_error(format!(
+                "Failed to set receive timeout of netlink socket: {}",
+                std::io::Error::last_os_error()
+            )));
+        }
+        Ok(nl)
+    }
+
+    fn add_address(
+        &mut self,
+        iface_index: u32,
+        addr: &DhcpAddress,
+        lifetime: u32,
+    ) -> Result<(), MozimError> {
+        let mut msg = gen_addr_msg(libc::RTM_NEWADDR, iface_index, addr);
+        msg.flags |= (libc::NLM_F_CREATE | libc::NLM_F_REPLACE) as u16;
+        if addr.prefix_length < 31 {
+            msg.add_attr(libc::IFA_BROADCAST, &addr.broadcast.octets());
+        }
+        // struct ifa_cacheinfo: preferred, valid, cstamp, tstamp
+        let mut cache_info = Vec::new();
+        cache_info.extend_from_slice(&lifetime.to_ne_bytes());
+        cache_info.extend_from_slice(&lifetime.to_ne_bytes());
+        cache_info.extend_from_slice(&[0u8; 8]);
+        msg.add_attr(libc::IFA_CACHEINFO, &cache_info);
+        self.request(msg).map_err(|e| {
+            MozimError::netlink_error(format!(
+                "Failed to add address {}/{}: {}",
+                addr.address, addr.prefix_length, e
+            ))
+        })
+    }
+
+    fn del_address(
+        &mut self,
+        iface_index: u32,
+        addr: &DhcpAddress,
+    ) -> Result<(), MozimError> {
+        let msg = gen_addr_msg(libc::RTM_DELADDR, iface_index, addr);
+        match self.request(msg) {
+            Err(e) if e.raw_os_error() == Some(libc::EADDRNOTAVAIL) => Ok(()),
+            Err(e) => Err(MozimError::netlink_error(format!(
+                "Failed to remove address {}/{}: {}",
+                addr.address, addr.prefix_length, e
+            ))),
+            Ok(()) => Ok(()),
+        }
+    }
+
+    fn add_route(
+        &mut self,
+        iface_index: u32,
+        route: &DhcpRoute,
+        src: Ipv4Addr,
+        metric: u32,
+    ) -> Result<(), MozimError> {
+        let scope = if route.gateway.is_some() {
+            libc::RT_SCOPE_UNIVERSE
+        } else {
+            libc::RT_SCOPE_LINK
+        };
+        let mut msg = gen_route_msg(
+            libc::RTM_NEWROUTE,
+            iface_index,
+            route,
+            metric,
+            scope,
+        );
+        msg.flags |= (libc::NLM_F_CREATE | libc::NLM_F_REPLACE) as u16;
+        msg.add_attr(libc::RTA_PREFSRC, &src.octets());
+        self.request(msg).map_err(|e| {
+            MozimError::netlink_error(format!(
+                "Failed to add route {}: {}",
+                route_to_string(route),
+                e
+            ))
+        })
+    }
+
+    fn del_route(
+        &mut self,
+        iface_index: u32,
+        route: &DhcpRoute,
+        metric: u32,
+    ) -> Result<(), MozimError> {
+        let msg = gen_route_msg(
+            libc::RTM_DELROUTE,
+            iface_index,
+            route,
+            metric,
+            libc::RT_SCOPE_NOWHERE,
+        );
+        match self.request(msg) {
+            Err(e) if e.raw_os_error() == Some(libc::ESRCH) => Ok(()),
+            Err(e) => Err(MozimError::netlink_error(format!(
+                "Failed to remove route {}: {}",
+                route_to_string(route),
+                e
+            ))),
+            Ok(()) => Ok(()),
+        }
+    }
+
+    // Send the request and wait for the kernel acknowledgement.
+    fn request(&mut self, mut msg: NetlinkMsg) -> std::io::Result<()> {
+        self.seq = self.seq.wrapping_add(1);
+        msg.seq = self.seq;
+        let data = msg.emit();
+        let rc = unsafe {
+            libc::send(
+                self.fd,
+                data.as_ptr() as *const libc::c_void,
+                data.len(),
+                0,
+            )
+        };
+        if rc < 0 {
+            return Err(std::io::Error::last_os_error());
+        }
+        let mut buf = vec![0u8; NETLINK_BUFFER_SIZE];
+        loop {
+            let len = unsafe {
+                libc::recv(
+                    self.fd,
+                    buf.as_mut_ptr() as *mut libc::c_void,
+                    buf.len(),
+                    0,
+                )
+            };
+            if len < 0 {
+                let e = std::io::Error::last_os_error();
+                return Err(if e.kind() == std::io::ErrorKind::WouldBlock {
+                    std::io::Error::new(
+                        std::io::ErrorKind::TimedOut,
+                        "no acknowledgement from kernel",
+                    )
+                } else {
+                    e
+                });
+            }
+            if let Some(errno) = parse_ack(&buf[..len as usize], self.seq) {
+                return match errno {
+                    0 => Ok(()),
+                    e => Err(std::io::Error::from_raw_os_error(-e)),
+                };
+            }
+        }
+    }
+}
+
+// Return the error code of NLMSG_ERROR message holding the sequence
+// number, 0 for success.
+fn parse_ack(data: &[u8], seq: u32) -> Option<i32> {
+    let mut pos = 0;
+    while pos + NLMSG_HDR_LEN <= data.len() {
+        let len = u32::from_ne_bytes(read_4(data, pos)) as usize;
+        let msg_type = u16::from_ne_bytes([data[pos + 4], data[pos + 5]]);
+        let msg_seq = u32::from_ne_bytes(read_4(data, pos + 8));
+        if len < NLMSG_HDR_LEN || pos + len > data.len() {
+            return None;
+        }
+        if msg_type == NLMSG_ERROR && msg_seq == seq && len >= NLMSG_HDR_LEN + 4
+        {
+            return Some(i32::from_ne_bytes(read_4(data, pos + NLMSG_HDR_LEN)));
+        }
+        pos += align(len);
+    }
+    None
+}
+
+fn read_4(data: &[u8], pos: usize) -> [u8; 4] {
+    [data[pos], data[pos + 1], data[pos + 2], data[pos + 3]]
+}
+
+fn align(len: usize) -> usize {
+    (len + NLMSG_ALIGN - 1) & !(NLMSG_ALIGN - 1)
+}
+
+fn route_to_string(route: &DhcpRoute) -> String {
+    match route.gateway {
+        Some(gw) => format!("{}/{} via {}", route.dst, route.prefix_length, gw),
+        None => format!("{}/{}", route.dst, route.prefix_length),
+    }
+}
+
+fn gen_addr_msg(
+    msg_type: u16,
+    iface_index: u32,
+    addr: &DhcpAddress,
+) -> NetlinkMsg {
+    // struct ifaddrmsg: family, prefixlen, flags, scope, index
+    let mut payload =
+        vec![libc::AF_INET as u8, addr.prefix_length, 0, addr.scope];
+    payload.extend_from_slice(&iface_index.to_ne_bytes());
+    let mut msg = NetlinkMsg::new(msg_type, payload);
+    msg.add_attr(libc::IFA_LOCAL, &addr.address.octets());
+    msg.add_attr(libc::IFA_ADDRESS, &addr.address.octets());
+    msg
+}
+
+fn gen_route_msg(
+    msg_type: u16,
+    iface_index: u32,
+    route: &DhcpRoute,
+    metric: u32,
+    scope: u8,
+) -> NetlinkMsg {
+    // struct rtmsg: family, dst_len, src_len, tos, table, protocol, scope,
+    // type, flags
+    let mut payload = vec![
+        libc::AF_INET as u8,
+        route.prefix_length,
+        0,
+        0,
+        libc::RT_TABLE_MAIN,
+        RTPROT_DHCP,
+        scope,
+        libc::RTN_UNICAST,
+    ];
+    payload.extend_from_slice(&0u32.to_ne_bytes());
+    let mut msg = NetlinkMsg::new(msg_type, payload);
+    if route.prefix_length > 0 {
+        msg.add_attr(libc::RTA_DST, &route.dst.octets());
+    }
+    if let Some(gateway) = route.gateway {
+        msg.add_attr(libc::RTA_GATEWAY, &gateway.octets());
+    }
+    msg.add_attr(libc::RTA_OIF, &iface_index.to_ne_bytes());
+    msg.add_attr(libc::RTA_PRIORITY, &metric.to_ne_bytes());
+    msg
+}
+
+struct NetlinkMsg {
+    msg_type: u16,
+    flags: u16,
+    seq: u32,
+    payload: Vec<u8>,
+}
+
+impl NetlinkMsg {
+    fn new(msg_type: u16, payload: Vec<u8>) -> Self {
+        NetlinkMsg {
+            msg_type,
+            flags: (libc::NLM_F_REQUEST | libc::NLM_F_ACK) as u16,
+            seq: 0,
+            payload,
+        }
+    }
+
+    fn add_attr(&mut self, attr_type: u16, data: &[u8]) {
+        let len = RTA_HDR_LEN + data.len();
+        self.payload.resize(align(self.payload.len()), 0);
+        self.payload.extend_from_slice(&(len as u16).to_ne_bytes());
+        self.payload.extend_from_slice(&attr_type.to_ne_bytes());
+        self.payload.extend_from_slice(data);
+    }
+
+    fn emit(&self) -> Vec<u8> {
+        let len = NLMSG_HDR_LEN + self.payload.len();
+        let mut buf = Vec::with_capacity(align(len));
+        buf.extend_from_slice(&(len as u32).to_ne_bytes());
+        buf.extend_from_slice(&self.msg_type.to_ne_bytes());
+        buf.extend_from_slice(&self.flags.to_ne_bytes());
+        buf.extend_from_slice(&self.seq.to_ne_bytes());
+        // Port ID 0 means kernel
+        buf.extend_from_slice(&0u32.to_ne_bytes());
+        buf.extend_from_slice(&self.payload);
+        buf.resize(align(len), 0);
+        buf
+    }
+}
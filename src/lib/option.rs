@@ -0,0 +1,654 @@
+// Copyright 2020 Red Hat, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::convert::TryFrom;
+use std::net::Ipv4Addr;
+
+use serde_derive::{Deserialize, Serialize};
+
+use crate::dns::{
+    emit_dns_name, emit_fqdn_name, parse_dns_names, parse_fqdn_name,
+};
+use crate::lease::prefix_to_mask;
+use crate::MozimError;
+
+const DHCP_OPTION_PAD: u8 = 0;
+pub const DHCP_OPTION_SUBNET_MASK: u8 = 1;
+pub const DHCP_OPTION_ROUTER: u8 = 3;
+pub const DHCP_OPTION_DNS_SERVER: u8 = 6;
+pub const DHCP_OPTION_HOST_NAME: u8 = 12;
+pub const DHCP_OPTION_DOMAIN_NAME: u8 = 15;
+pub const DHCP_OPTION_INTERFACE_MTU: u8 = 26;
+pub const DHCP_OPTION_BROADCAST_ADDRESS: u8 = 28;
+pub const DHCP_OPTION_NTP_SERVER: u8 = 42;
+pub const DHCP_OPTION_VENDOR_SPECIFIC: u8 = 43;
+pub const DHCP_OPTION_REQUESTED_IP: u8 = 50;
+pub const DHCP_OPTION_LEASE_TIME: u8 = 51;
+const DHCP_OPTION_OVERLOAD: u8 = 52;
+pub const DHCP_OPTION_MESSAGE_TYPE: u8 = 53;
+pub const DHCP_OPTION_SERVER_IDENTIFIER: u8 = 54;
+pub const DHCP_OPTION_PARAMETER_REQUEST_LIST: u8 = 55;
+pub const DHCP_OPTION_MESSAGE: u8 = 56;
+pub const DHCP_OPTION_MAX_MESSAGE_SIZE: u8 = 57;
+pub const DHCP_OPTION_RENEWAL_TIME: u8 = 58;
+pub const DHCP_OPTION_REBINDING_TIME: u8 = 59;
+pub const DHCP_OPTION_VENDOR_CLASS: u8 = 60;
+pub const DHCP_OPTION_CLIENT_IDENTIFIER: u8 = 61;
+pub const DHCP_OPTION_USER_CLASS: u8 = 77;
+pub const DHCP_OPTION_RAPID_COMMIT: u8 = 80;
+pub const DHCP_OPTION_CLIENT_FQDN: u8 = 81;
+pub const DHCP_OPTION_DOMAIN_SEARCH: u8 = 119;
+pub const DHCP_OPTION_CLASSLESS_STATIC_ROUTE: u8 = 121;
+pub const DHCP_OPTION_VI_VENDOR_SPECIFIC: u8 = 125;
+// Pre-standard code of option 121 used by Microsoft
+pub const DHCP_OPTION_MS_CLASSLESS_STATIC_ROUTE: u8 = 249;
+const DHCP_OPTION_END: u8 = 255;
+
+// Values of option 52, RFC 2132 section 9.3
+const DHCP_OVERLOAD_FILE: u8 = 1;
+const DHCP_OVERLOAD_SNAME: u8 = 2;
+const DHCP_OVERLOAD_BOTH: u8 = 3;
+
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum DhcpV4MessageType {
+    Discover = 1,
+    Offer = 2,
+    Request = 3,
+    Decline = 4,
+    Ack = 5,
+    Nak = 6,
+    Release = 7,
+    Inform = 8,
+}
+
+impl TryFrom<u8> for DhcpV4MessageType {
+    type Error = MozimError;
+    fn try_from(d: u8) -> Result<Self, MozimError> {
+        match d {
+            1 => Ok(DhcpV4MessageType::Discover),
+            2 => Ok(DhcpV4MessageType::Offer),
+            3 => Ok(DhcpV4MessageType::Request),
+            4 => Ok(DhcpV4MessageType::Decline),
+            5 => Ok(DhcpV4MessageType::Ack),
+            6 => Ok(DhcpV4MessageType::Nak),
+            7 => Ok(DhcpV4MessageType::Release),
+            8 => Ok(DhcpV4MessageType::Inform),
+            _ => Err(MozimError::invalid_dhcp_message(format!(
+                "Invalid DHCP message type {}",
+                d
+            ))),
+        }
+    }
+}
+
+impl std::fmt::Display for DhcpV4MessageType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:?}", self)
+    }
+}
+
+// Route of RFC 3442 classless static route option, gateway 0.0.0.0 means
+// the destination is directly connected.
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DhcpClasslessRoute {
+    pub dst: Ipv4Addr,
+    pub prefix_length: u8,
+    pub gateway: Ipv4Addr,
+}
+
+// Data of one enterprise in RFC 3925 vendor-identifying vendor-specific
+// information option, the `data` is usually encapsulated sub-options.
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct DhcpViVendorSpecific {
+    pub enterprise_number: u32,
+    pub data: Vec<u8>,
+}
+
+// RFC 4702 section 2.1: the flags field of client FQDN option
+const FQDN_FLAG_S: u8 = 0x01;
+const FQDN_FLAG_O: u8 = 0x02;
+const FQDN_FLAG_E: u8 = 0x04;
+const FQDN_FLAG_N: u8 = 0x08;
+
+#[derive(
+    Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq, Default,
+)]
+pub struct DhcpFqdnFlags {
+    // S: Server should update, or in reply has updated, the A RR
+    pub server_update: bool,
+    // O: Server overrode the `server_update` of client, only in reply
+    pub server_override: bool,
+    // E: Name is in DNS wire format instead of ASCII
+    pub encoded: bool,
+    // N: Server should not do any DNS update
+    pub no_update: bool,
+}
+
+impl From<u8> for DhcpFqdnFlags {
+    fn from(d: u8) -> Self {
+        DhcpFqdnFlags {
+            server_update: d & FQDN_FLAG_S > 0,
+            server_override: d & FQDN_FLAG_O > 0,
+            encoded: d & FQDN_FLAG_E > 0,
+            no_update: d & FQDN_FLAG_N > 0,
+        }
+    }
+}
+
+impl From<DhcpFqdnFlags> for u8 {
+    fn from(f: DhcpFqdnFlags) -> Self {
+        let mut d = 0;
+        for (set, flag) in [
+            (f.server_update, FQDN_FLAG_S),
+            (f.server_override, FQDN_FLAG_O),
+            (f.encoded, FQDN_FLAG_E),
+            (f.no_update, FQDN_FLAG_N),
+        ] {
+            if set {
+                d |= flag;
+            }
+        }
+        d
+    }
+}
+
+// RFC 4702 client FQDN option. Name with single label is partial, server
+// appends its domain.
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct DhcpClientFqdn {
+    pub flags: DhcpFqdnFlags,
+    // Deprecated, client sends 0, server 255
+    pub rcode1: u8,
+    pub rcode2: u8,
+    pub name: String,
+}
+
+impl DhcpClientFqdn {
+    // Name in DNS wire format. With `server_update`, server is asked to
+    // update both A and PTR RR, otherwise only PTR RR.
+    pub fn new(name: &str, server_update: bool) -> Self {
+        DhcpClientFqdn {
+            flags: DhcpFqdnFlags {
+                server_update,
+                encoded: true,
+                ..Default::default()
+            },
+            rcode1: 0,
+            rcode2: 0,
+            name: name.trim_end_matches('.').to_string(),
+        }
+    }
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum DhcpOption {
+    SubnetMask(Ipv4Addr),
+    Router(Vec<Ipv4Addr>),
+    DnsServer(Vec<Ipv4Addr>),
+    HostName(String),
+    DomainName(String),
+    InterfaceMtu(u16),
+    BroadcastAddress(Ipv4Addr),
+    NtpServer(Vec<Ipv4Addr>),
+    // Format defined by the vendor of `VendorClass`
+    VendorSpecific(Vec<u8>),
+    RequestedIp(Ipv4Addr),
+    LeaseTime(u32),
+    MessageType(DhcpV4MessageType),
+    ServerIdentifier(Ipv4Addr),
+    ParameterRequestList(Vec<u8>),
+    Message(String),
+    MaxMessageSize(u16),
+    RenewalTime(u32),
+    RebindingTime(u32),
+    VendorClass(String),
+    // The type byte followed by the identifier, RFC 4361 uses type 255
+    // with IAID and DUID.
+    ClientIdentifier(Vec<u8>),
+    // RFC 3004, each class is a length prefixed instance
+    UserClass(Vec<String>),
+    // RFC 4039, no data
+    RapidCommit,
+    ClientFqdn(DhcpClientFqdn),
+    DomainSearch(Vec<String>),
+    ClasslessStaticRoute(Vec<DhcpClasslessRoute>),
+    MsClasslessStaticRoute(Vec<DhcpClasslessRoute>),
+    ViVendorSpecific(Vec<DhcpViVendorSpecific>),
+    // Option mozim does not understand or failed to decode, kept
+    // untouched so it survives a decode/encode round trip.
+    Raw(u8, Vec<u8>),
+}
+
+impl DhcpOption {
+    pub fn code(&self) -> u8 {
+        match self {
+            DhcpOption::SubnetMask(_) => DHCP_OPTION_SUBNET_MASK,
+            DhcpOption::Router(_) => DHCP_OPTION_ROUTER,
+            DhcpOption::DnsServer(_) => DHCP_OPTION_DNS_SERVER,
+            DhcpOption::HostName(_) => DHCP_OPTION_HOST_NAME,
+            DhcpOption::DomainName(_) => DHCP_OPTION_DOMAIN_NAME,
+            DhcpOption::InterfaceMtu(_) => DHCP_OPTION_INTERFACE_MTU,
+            DhcpOption::BroadcastAddress(_) => DHCP_OPTION_BROADCAST_ADDRESS,
+            DhcpOption::NtpServer(_) => DHCP_OPTION_NTP_SERVER,
+            DhcpOption::VendorSpecific(_) => DHCP_OPTION_VENDOR_SPECIFIC,
+            DhcpOption::RequestedIp(_) => DHCP_OPTION_REQUESTED_IP,
+            DhcpOption::LeaseTime(_) => DHCP_OPTION_LEASE_TIME,
+            DhcpOption::MessageType(_) => DHCP_OPTION_MESSAGE_TYPE,
+            DhcpOption::ServerIdentifier(_) => DHCP_OPTION_SERVER_IDENTIFIER,
+            DhcpOption::ParameterRequestList(_) => {
+                DHCP_OPTION_PARAMETER_REQUEST_LIST
+            }
+            DhcpOption::Message(_) => DHCP_OPTION_MESSAGE,
+            DhcpOption::MaxMessageSize(_) => DHCP_OPTION_MAX_MESSAGE_SIZE,
+            DhcpOption::RenewalTime(_) => DHCP_OPTION_RENEWAL_TIME,
+            DhcpOption::RebindingTime(_) => DHCP_OPTION_REBINDING_TIME,
+            DhcpOption::VendorClass(_) => DHCP_OPTION_VENDOR_CLASS,
+            DhcpOption::ClientIdentifier(_) => DHCP_OPTION_CLIENT_IDENTIFIER,
+            DhcpOption::UserClass(_) => DHCP_OPTION_USER_CLASS,
+            DhcpOption::RapidCommit => DHCP_OPTION_RAPID_COMMIT,
+            DhcpOption::ClientFqdn(_) => DHCP_OPTION_CLIENT_FQDN,
+            DhcpOption::DomainSearch(_) => DHCP_OPTION_DOMAIN_SEARCH,
+            DhcpOption::ClasslessStaticRoute(_) => {
+                DHCP_OPTION_CLASSLESS_STATIC_ROUTE
+            }
+            DhcpOption::MsClasslessStaticRoute(_) => {
+                DHCP_OPTION_MS_CLASSLESS_STATIC_ROUTE
+            }
+            DhcpOption::ViVendorSpecific(_) => DHCP_OPTION_VI_VENDOR_SPECIFIC,
+            DhcpOption::Raw(code, _) => *code,
+        }
+    }
+
+    // Decode the concatenated data of a single option. Data which does not
+    // fit the typed format is preserved as `DhcpOption::Raw`.
+    pub fn parse(code: u8, data: &[u8]) -> Self {
+        parse_typed(code, data)
+            .unwrap_or_else(|| DhcpOption::Raw(code, data.to_vec()))
+    }
+
+    // The option data without code and length.
+    pub fn emit_data(&self) -> Vec<u8> {
+        match self {
+            DhcpOption::SubnetMask(a)
+            | DhcpOption::BroadcastAddress(a)
+            | DhcpOption::RequestedIp(a)
+            | DhcpOption::ServerIdentifier(a) => a.octets().to_vec(),
+            DhcpOption::Router(addrs)
+            | DhcpOption::DnsServer(addrs)
+            | DhcpOption::NtpServer(addrs) => {
+                addrs.iter().flat_map(|a| a.octets().to_vec()).collect()
+            }
+            DhcpOption::HostName(s)
+            | DhcpOption::DomainName(s)
+            | DhcpOption::Message(s)
+            | DhcpOption::VendorClass(s) => s.as_bytes().to_vec(),
+            DhcpOption::InterfaceMtu(d) | DhcpOption::MaxMessageSize(d) => {
+                d.to_be_bytes().to_vec()
+            }
+            DhcpOption::LeaseTime(d)
+            | DhcpOption::RenewalTime(d)
+            | DhcpOption::RebindingTime(d) => d.to_be_bytes().to_vec(),
+            DhcpOption::MessageType(t) => vec![*t as u8],
+            DhcpOption::ParameterRequestList(codes) => codes.clone(),
+            DhcpOption::ClientIdentifier(id) => id.clone(),
+            DhcpOption::VendorSpecific(data) => data.clone(),
+            DhcpOption::UserClass(classes) => classes
+                .iter()
+                .flat_map(|c| {
+                    let mut data = vec![c.len() as u8];
+                    data.extend_from_slice(c.as_bytes());
+                    data
+                })
+                .collect(),
+            DhcpOption::ViVendorSpecific(vendors) => vendors
+                .iter()
+                .flat_map(|v| {
+                    let mut data = v.enterprise_number.to_be_bytes().to_vec();
+                    data.push(v.data.len() as u8);
+                    data.extend_from_slice(&v.data);
+                    data
+                })
+                .collect(),
+            DhcpOption::RapidCommit => Vec::new(),
+            DhcpOption::ClientFqdn(fqdn) => {
+                let mut data =
+                    vec![fqdn.flags.into(), fqdn.rcode1, fqdn.rcode2];
+                if fqdn.flags.encoded {
+                    data.extend_from_slice(&emit_fqdn_name(&fqdn.name));
+                } else {
+                    data.extend_from_slice(fqdn.name.as_bytes());
+                }
+                data
+            }
+            DhcpOption::DomainSearch(names) => {
+                names.iter().flat_map(|n| emit_dns_name(n)).collect()
+            }
+            DhcpOption::ClasslessStaticRoute(routes)
+            | DhcpOption::MsClasslessStaticRoute(routes) => {
+                emit_classless_routes(routes)
+            }
+            DhcpOption::Raw(_, data) => data.clone(),
+        }
+    }
+}
+
+fn parse_typed(code: u8, data: &[u8]) -> Option<DhcpOption> {
+    Some(match code {
+        DHCP_OPTION_SUBNET_MASK => DhcpOption::SubnetMask(parse_ipv4(data)?),
+        DHCP_OPTION_ROUTER => DhcpOption::Router(parse_ipv4_list(data)?),
+        DHCP_OPTION_DNS_SERVER => DhcpOption::DnsServer(parse_ipv4_list(data)?),
+        DHCP_OPTION_HOST_NAME => DhcpOption::HostName(parse_string(data)?),
+        DHCP_OPTION_DOMAIN_NAME => DhcpOption::DomainName(parse_string(data)?),
+        DHCP_OPTION_INTERFACE_MTU => DhcpOption::InterfaceMtu(parse_u16(data)?),
+        DHCP_OPTION_BROADCAST_ADDRESS => {
+            DhcpOption::BroadcastAddress(parse_ipv4(data)?)
+        }
+        DHCP_OPTION_NTP_SERVER => DhcpOption::NtpServer(parse_ipv4_list(data)?),
+        DHCP_OPTION_VENDOR_SPECIFIC => {
+            DhcpOption::VendorSpecific(data.to_vec())
+        }
+        DHCP_OPTION_REQUESTED_IP => DhcpOption::RequestedIp(parse_ipv4(data)?),
+        DHCP_OPTION_LEASE_TIME => DhcpOption::LeaseTime(parse_u32(data)?),
+        DHCP_OPTION_MESSAGE_TYPE => {
+            if data.len() != 1 {
+                return None;
+            }
+            DhcpOption::MessageType(DhcpV4MessageType::try_from(data[0]).ok()?)
+        }
+        DHCP_OPTION_SERVER_IDENTIFIER => {
+            DhcpOption::ServerIdentifier(parse_ipv4(data)?)
+        }
+        DHCP_OPTION_PARAMETER_REQUEST_LIST => {
+            DhcpOption::ParameterRequestList(data.to_vec())
+        }
+        DHCP_OPTION_MESSAGE => DhcpOption::Message(parse_string(data)?),
+        DHCP_OPTION_MAX_MESSAGE_SIZE => {
+            DhcpOption::MaxMessageSize(parse_u16(data)?)
+        }
+        DHCP_OPTION_RENEWAL_TIME => DhcpOption::RenewalTime(parse_u32(data)?),
+        DHCP_OPTION_REBINDING_TIME => {
+            DhcpOption::RebindingTime(parse_u32(data)?)
+        }
+        DHCP_OPTION_VENDOR_CLASS => {
+            DhcpOption::VendorClass(parse_string(data)?)
+        }
+        DHCP_OPTION_USER_CLASS => {
+            DhcpOption::UserClass(parse_user_class(data)?)
+        }
+        // RFC 2132 section 9.14: minimum length is 2
+        DHCP_OPTION_CLIENT_IDENTIFIER if data.len() >= 2 => {
+            DhcpOption::ClientIdentifier(data.to_vec())
+        }
+        DHCP_OPTION_RAPID_COMMIT if data.is_empty() => DhcpOption::RapidCommit,
+        DHCP_OPTION_CLIENT_FQDN if data.len() >= 3 => {
+            let flags = DhcpFqdnFlags::from(data[0]);
+            DhcpOption::ClientFqdn(DhcpClientFqdn {
+                flags,
+                rcode1: data[1],
+                rcode2: data[2],
+                name: if flags.encoded {
+                    parse_fqdn_name(&data[3..])?
+                } else {
+                    parse_string(&data[3..])?
+                },
+            })
+        }
+        DHCP_OPTION_DOMAIN_SEARCH => {
+            DhcpOption::DomainSearch(parse_dns_names(data)?)
+        }
+        DHCP_OPTION_CLASSLESS_STATIC_ROUTE => {
+            DhcpOption::ClasslessStaticRoute(parse_classless_routes(data)?)
+        }
+        DHCP_OPTION_MS_CLASSLESS_STATIC_ROUTE => {
+            DhcpOption::MsClasslessStaticRoute(parse_classless_routes(data)?)
+        }
+        DHCP_OPTION_VI_VENDOR_SPECIFIC => {
+            DhcpOption::ViVendorSpecific(parse_vi_vendor_specific(data)?)
+        }
+        _ => return None,
+    })
+}
+
+fn parse_ipv4(data: &[u8]) -> Option<Ipv4Addr> {
+    if data.len() == 4 {
+        Some(Ipv4Addr::new(data[0], data[1], data[2], data[3]))
+    } else {
+        None
+    }
+}
+
+fn parse_ipv4_list(data: &[u8]) -> Option<Vec<Ipv4Addr>> {
+    if data.is_empty() || !data.len().is_multiple_of(4) {
+        None
+    } else {
+        data.chunks(4).map(parse_ipv4).collect()
+    }
+}
+
+fn parse_u16(data: &[u8]) -> Option<u16> {
+    if data.len() == 2 {
+        Some(u16::from_be_bytes([data[0], data[1]]))
+    } else {
+        None
+    }
+}
+
+fn parse_u32(data: &[u8]) -> Option<u32> {
+    if data.len() == 4 {
+        Some(u32::from_be_bytes([data[0], data[1], data[2], data[3]]))
+    } else {
+        None
+    }
+}
+
+// RFC 3442 section 3: prefix length, the significant octets of
+// destination, then the router. Host bits of destination are cleared.
+fn parse_classless_routes(data: &[u8]) -> Option<Vec<DhcpClasslessRoute>> {
+    let mut routes = Vec::new();
+    let mut i = 0;
+    while i < data.len() {
+        let prefix_length = data[i];
+        if prefix_length > 32 {
+            return None;
+        }
+        let dst_len = (prefix_length as usize).div_ceil(8);
+        let dst_end = i + 1 + dst_len;
+        let gateway = parse_ipv4(data.get(dst_end..dst_end + 4)?)?;
+        let mut dst = [0u8; 4];
+        dst[..dst_len].copy_from_slice(&data[i + 1..dst_end]);
+        routes.push(DhcpClasslessRoute {
+            dst: Ipv4Addr::from(
+                u32::from_be_bytes(dst) & prefix_to_mask(prefix_length),
+            ),
+            prefix_length,
+            gateway,
+        });
+        i = dst_end + 4;
+    }
+    if routes.is_empty() {
+        None
+    } else {
+        Some(routes)
+    }
+}
+
+fn emit_classless_routes(routes: &[DhcpClasslessRoute]) -> Vec<u8> {
+    let mut data = Vec::new();
+    for route in routes {
+        let dst_len = (route.prefix_length as usize).div_ceil(8);
+        data.push(route.prefix_length);
+        data.extend_from_slice(&route.dst.octets()[..dst_len]);
+        data.extend_from_slice(&route.gateway.octets());
+    }
+    data
+}
+
+// RFC 3004 section 4: instances of 1 byte length and non-empty data.
+fn parse_user_class(data: &[u8]) -> Option<Vec<String>> {
+    let mut classes = Vec::new();
+    let mut i = 0;
+    while i < data.len() {
+        let len = data[i] as usize;
+        if len == 0 {
+            return None;
+        }
+        let class = data.get(i + 1..i + 1 + len)?;
+        classes.push(std::str::from_utf8(class).ok()?.to_string());
+        i += 1 + len;
+    }
+    Some(classes)
+}
+
+// RFC 3925 section 4: 4 bytes enterprise number, 1 byte length and data,
+// repeated.
+fn parse_vi_vendor_specific(data: &[u8]) -> Option<Vec<DhcpViVendorSpecific>> {
+    let mut vendors = Vec::new();
+    let mut i = 0;
+    while i < data.len() {
+        let header = data.get(i..i + 5)?;
+        let len = header[4] as usize;
+        vendors.push(DhcpViVendorSpecific {
+            enterprise_number: u32::from_be_bytes([
+                header[0], header[1], header[2], header[3],
+            ]),
+            data: data.get(i + 5..i + 5 + len)?.to_vec(),
+        });
+        i += 5 + len;
+    }
+    Some(vendors)
+}
+
+// Some servers include trailing NULL in string options.
+fn parse_string(data: &[u8]) -> Option<String> {
+    let end = data.iter().position(|c| *c == 0).unwrap_or(data.len());
+    std::str::from_utf8(&data[..end])
+        .ok()
+        .map(|s| s.to_string())
+}
+
+// Split the options area into (code, data) pairs, PAD skipped and
+// stopping at END.
+fn parse_raw_options(
+    data: &[u8],
+    field: &str,
+) -> Result<Vec<(u8, Vec<u8>)>, MozimError> {
+    let mut options = Vec::new();
+    let mut i = 0;
+    while i < data.len() {
+        let code = data[i];
+        match code {
+            DHCP_OPTION_PAD => {
+                i += 1;
+                continue;
+            }
+            DHCP_OPTION_END => return Ok(options),
+            _ => (),
+        }
+        if i + 1 >= data.len() {
+            return Err(MozimError::invalid_dhcp_message(format!(
+                "DHCP option {} in {} is missing length field",
+                code, field
+            )));
+        }
+        let len = data[i + 1] as usize;
+        let start = i + 2;
+        if start + len > data.len() {
+            return Err(MozimError::invalid_dhcp_message(format!(
+                "DHCP option {} length {} exceeds the {} boundary",
+                code, len, field
+            )));
+        }
+        options.push((code, data[start..start + len].to_vec()));
+        i = start + len;
+    }
+    // Some servers omit the END option, tolerate it.
+    Ok(options)
+}
+
+// Parse options from the options area and, when option 52 is found, also
+// from the `file` and `sname` fields in that order as RFC 2131 section 4.1
+// required. Multiple instances of the same option are concatenated as
+// RFC 3396 required. Return the options and whether the `file` and `sname`
+// fields were used for options.
+pub(crate) fn parse_options(
+    options_area: &[u8],
+    file: &[u8],
+    sname: &[u8],
+) -> Result<(Vec<DhcpOption>, bool, bool), MozimError> {
+    let mut raw_opts = parse_raw_options(options_area, "options")?;
+
+    let overload = raw_opts
+        .iter()
+        .find(|(code, _)| *code == DHCP_OPTION_OVERLOAD)
+        .and_then(|(_, data)| data.first().copied());
+    let (file_overloaded, sname_overloaded) = match overload {
+        None => (false, false),
+        Some(DHCP_OVERLOAD_FILE) => (true, false),
+        Some(DHCP_OVERLOAD_SNAME) => (false, true),
+        Some(DHCP_OVERLOAD_BOTH) => (true, true),
+        Some(v) => {
+            return Err(MozimError::invalid_dhcp_message(format!(
+                "Invalid option overload value {}",
+                v
+            )))
+        }
+    };
+    if file_overloaded {
+        raw_opts.extend(parse_raw_options(file, "file")?);
+    }
+    if sname_overloaded {
+        raw_opts.extend(parse_raw_options(sname, "sname")?);
+    }
+
+    let mut merged: Vec<(u8, Vec<u8>)> = Vec::new();
+    for (code, data) in raw_opts {
+        if code == DHCP_OPTION_OVERLOAD {
+            continue;
+        }
+        match merged.iter_mut().find(|(c, _)| *c == code) {
+            Some((_, existing)) => existing.extend_from_slice(&data),
+            None => merged.push((code, data)),
+        }
+    }
+
+    Ok((
+        merged
+            .iter()
+            .map(|(code, data)| DhcpOption::parse(*code, data))
+            .collect(),
+        file_overloaded,
+        sname_overloaded,
+    ))
+}
+
+// Option longer than 255 bytes is split into multiple instances as
+// RFC 3396 required.
+pub(crate) fn emit_options(buf: &mut Vec<u8>, options: &[DhcpOption]) {
+    for opt in options {
+        let code = opt.code();
+        let data = opt.emit_data();
+        if data.is_empty() {
+            buf.push(code);
+            buf.push(0);
+        }
+        for chunk in data.chunks(u8::MAX as usize) {
+            buf.push(code);
+            buf.push(chunk.len() as u8);
+            buf.extend_from_slice(chunk);
+        }
+    }
+    buf.push(DHCP_OPTION_END);
+}
@@ -0,0 +1,192 @@
+// Copyright 2020 Red Hat, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::net::Ipv4Addr;
+use std::time::Duration;
+
+use crate::arp::ARP_PACKET_LEN;
+use crate::frame::{
+    gen_bpf_filter, gen_frame, parse_frame, ETH_BROADCAST, ETH_HDR_LEN,
+    ETH_P_ARP, ETH_P_IP,
+};
+use crate::iface::{get_iface_index, get_iface_mac};
+use crate::socket::{
+    bind, new_socket, poll_readable, recv, send_to, set_sock_opt, Fd,
+    DHCP_CLIENT_PORT, DHCP_SERVER_PORT, MAX_FRAME_SIZE,
+};
+use crate::{DhcpV4ArpPacket, DhcpV4Message, MozimError};
+
+// AF_PACKET socket sending and receiving the whole Ethernet frame, so DHCP
+// could work on interface without IPv4 address. A BPF filter is attached so
+// only UDP to port 68 holding our transaction ID reaches userspace.
+#[derive(Debug)]
+pub struct DhcpV4RawSocket {
+    fd: Fd,
+    iface_index: u32,
+    mac: [u8; 6],
+}
+
+impl DhcpV4RawSocket {
+    pub fn new(iface_name: &str, xid: u32) -> Result<Self, MozimError> {
+        let iface_index = get_iface_index(iface_name)?;
+        let mac = get_iface_mac(iface_name)?;
+        let fd = new_socket(
+            libc::AF_PACKET,
+            libc::SOCK_RAW,
+            (ETH_P_IP.to_be()) as libc::c_int,
+        )?;
+        let socket = DhcpV4RawSocket {
+            fd,
+            iface_index,
+            mac,
+        };
+        socket.set_xid(xid)?;
+        bind(
+            &socket.fd,
+            &gen_sockaddr_ll(iface_index, ETH_P_IP, &[0u8; 6]),
+        )?;
+        // Frames of any interface are queued since the socket creation,
+        // before the filter is attached.
+        socket.drain()?;
+        Ok(socket)
+    }
+
+    fn drain(&self) -> Result<(), MozimError> {
+        let mut buf = [0u8; MAX_FRAME_SIZE];
+        while poll_readable(&self.fd, Duration::from_secs(0))? {
+            recv(&self.fd, &mut buf)?;
+        }
+        Ok(())
+    }
+
+    // Only accept DHCP message with specified transaction ID afterwards.
+    pub fn set_xid(&self, xid: u32) -> Result<(), MozimError> {
+        let mut filter = gen_bpf_filter(xid);
+        let prog = libc::sock_fprog {
+            len: filter.len() as libc::c_ushort,
+            filter: filter.as_mut_ptr(),
+        };
+        set_sock_opt(&self.fd, libc::SOL_SOCKET, libc::SO_ATTACH_FILTER, &prog)
+    }
+
+    // Broadcast the DHCP message in Ethernet frame using the `ciaddr` of
+    // message as source IP.
+    pub fn send(
+        &self,
+        msg: &DhcpV4Message,
+        dst: Ipv4Addr,
+    ) -> Result<(), MozimError> {
+        let frame = gen_frame(
+            &self.mac,
+            msg.ciaddr,
+            dst,
+            DHCP_CLIENT_PORT,
+            DHCP_SERVER_PORT,
+            &msg.emit(),
+        );
+        send_to(
+            &self.fd,
+            &frame,
+            &gen_sockaddr_ll(self.iface_index, ETH_P_IP, &ETH_BROADCAST),
+        )
+    }
+
+    // Wait up to `timeout` for a DHCP message, return None on timeout.
+    // Malformed frames are ignored.
+    pub fn recv(
+        &self,
+        timeout: Duration,
+    ) -> Result<Option<DhcpV4Message>, MozimError> {
+        if !poll_readable(&self.fd, timeout)? {
+            return Ok(None);
+        }
+        let mut buf = [0u8; MAX_FRAME_SIZE];
+        let len = recv(&self.fd, &mut buf)?;
+        Ok(parse_frame(&buf[..len])
+            .and_then(|payload| DhcpV4Message::parse(payload).ok()))
+    }
+}
+
+// AF_PACKET socket for ARP probing the offered address before using it.
+// Every ARP packet on the interface is received, including the ones sent by
+// ourselves.
+#[derive(Debug)]
+pub struct DhcpV4ArpSocket {
+    fd: Fd,
+    iface_index: u32,
+    mac: [u8; 6],
+}
+
+impl DhcpV4ArpSocket {
+    pub fn new(iface_name: &str) -> Result<Self, MozimError> {
+        let iface_index = get_iface_index(iface_name)?;
+        let mac = get_iface_mac(iface_name)?;
+        let fd = new_socket(
+            libc::AF_PACKET,
+            libc::SOCK_RAW,
+            (ETH_P_ARP.to_be()) as libc::c_int,
+        )?;
+        bind(&fd, &gen_sockaddr_ll(iface_index, ETH_P_ARP, &[0u8; 6]))?;
+        Ok(DhcpV4ArpSocket {
+            fd,
+            iface_index,
+            mac,
+        })
+    }
+
+    // Broadcast the ARP packet.
+    pub fn send(&self, packet: &DhcpV4ArpPacket) -> Result<(), MozimError> {
+        let mut frame = Vec::with_capacity(ETH_HDR_LEN + ARP_PACKET_LEN);
+        frame.extend_from_slice(&ETH_BROADCAST);
+        frame.extend_from_slice(&self.mac);
+        frame.extend_from_slice(&ETH_P_ARP.to_be_bytes());
+        frame.extend_from_slice(&packet.emit());
+        send_to(
+            &self.fd,
+            &frame,
+            &gen_sockaddr_ll(self.iface_index, ETH_P_ARP, &ETH_BROADCAST),
+        )
+    }
+
+    // Wait up to `timeout` for an ARP packet, return None on timeout.
+    // Malformed frames are ignored.
+    pub fn recv(
+        &self,
+        timeout: Duration,
+    ) -> Result<Option<DhcpV4ArpPacket>, MozimError> {
+        if !poll_readable(&self.fd, timeout)? {
+            return Ok(None);
+        }
+        let mut buf = [0u8; MAX_FRAME_SIZE];
+        let len = recv(&self.fd, &mut buf)?;
+        if len < ETH_HDR_LEN || buf[12..14] != ETH_P_ARP.to_be_bytes() {
+            return Ok(None);
+        }
+        Ok(DhcpV4ArpPacket::parse(&buf[ETH_HDR_LEN..len]).ok())
+    }
+}
+
+fn gen_sockaddr_ll(
+    iface_index: u32,
+    protocol: u16,
+    dst_mac: &[u8; 6],
+) -> libc::sockaddr_ll {
+    let mut addr: libc::sockaddr_ll = unsafe { std::mem::zeroed() };
+    addr.sll_family = libc::AF_PACKET as libc::c_ushort;
+    addr.sll_protocol = protocol.to_be();
+    addr.sll_ifindex = iface_index as libc::c_int;
+    addr.sll_halen = dst_mac.len() as libc::c_uchar;
+    addr.sll_addr[..dst_mac.len()].copy_from_slice(dst_mac);
+    addr
+}
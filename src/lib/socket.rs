@@ -0,0 +1,218 @@
+// Copyright 2020 Red Hat, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::net::Ipv4Addr;
+use std::os::unix::io::RawFd;
+use std::time::Duration;
+
+use crate::MozimError;
+
+pub(crate) const DHCP_CLIENT_PORT: u16 = 68;
+pub(crate) const DHCP_SERVER_PORT: u16 = 67;
+// Large enough for DHCP message in a standard Ethernet frame.
+pub(crate) const MAX_FRAME_SIZE: usize = 1514;
+
+// Owned file descriptor, closed on drop.
+#[derive(Debug)]
+pub(crate) struct Fd(pub(crate) RawFd);
+
+impl Drop for Fd {
+    fn drop(&mut self) {
+        unsafe {
+            libc::close(self.0);
+        }
+    }
+}
+
+pub(crate) fn new_socket(
+    domain: libc::c_int,
+    sock_type: libc::c_int,
+    protocol: libc::c_int,
+) -> Result<Fd, MozimError> {
+    let fd = unsafe {
+        libc::socket(domain, sock_type | libc::SOCK_CLOEXEC, protocol)
+    };
+    if fd < 0 {
+        Err(MozimError::socket_error(format!(
+            "Failed to create socket: {}",
+            std::io::Error::last_os_error()
+        )))
+    } else {
+        Ok(Fd(fd))
+    }
+}
+
+pub(crate) fn set_sock_opt<T>(
+    fd: &Fd,
+    level: libc::c_int,
+    name: libc::c_int,
+    value: &T,
+) -> Result<(), MozimError> {
+    let rc = unsafe {
+        libc::setsockopt(
+            fd.0,
+            level,
+            name,
+            value as *const T as *const libc::c_void,
+            std::mem::size_of::<T>() as libc::socklen_t,
+        )
+    };
+    if rc != 0 {
+        Err(MozimError::socket_error(format!(
+            "setsockopt({}, {}) failed: {}",
+            level,
+            name,
+            std::io::Error::last_os_error()
+        )))
+    } else {
+        Ok(())
+    }
+}
+
+pub(crate) fn bind_to_device(
+    fd: &Fd,
+    iface_name: &str,
+) -> Result<(), MozimError> {
+    let rc = unsafe {
+        libc::setsockopt(
+            fd.0,
+            libc::SOL_SOCKET,
+            libc::SO_BINDTODEVICE,
+            iface_name.as_ptr() as *const libc::c_void,
+            iface_name.len() as libc::socklen_t,
+        )
+    };
+    if rc != 0 {
+        Err(MozimError::socket_error(format!(
+            "Failed to bind socket to interface {}: {}",
+            iface_name,
+            std::io::Error::last_os_error()
+        )))
+    } else {
+        Ok(())
+    }
+}
+
+pub(crate) fn gen_sockaddr_in(addr: Ipv4Addr, port: u16) -> libc::sockaddr_in {
+    libc::sockaddr_in {
+        sin_family: libc::AF_INET as libc::sa_family_t,
+        sin_port: port.to_be(),
+        sin_addr: libc::in_addr {
+            s_addr: u32::from(addr).to_be(),
+        },
+        sin_zero: [0; 8],
+    }
+}
+
+pub(crate) fn bind<T>(fd: &Fd, addr: &T) -> Result<(), MozimError> {
+    let rc = unsafe {
+        libc::bind(
+            fd.0,
+            addr as *const T as *const libc::sockaddr,
+            std::mem::size_of::<T>() as libc::socklen_t,
+        )
+    };
+    if rc != 0 {
+        Err(MozimError::socket_error(format!(
+            "Failed to bind socket: {}",
+            std::io::Error::last_os_error()
+        )))
+    } else {
+        Ok(())
+    }
+}
+
+// Wait till socket is readable, return false on timeout.
+pub(crate) fn poll_readable(
+    fd: &Fd,
+    timeout: Duration,
+) -> Result<bool, MozimError> {
+    let mut poll_fd = libc::pollfd {
+        fd: fd.0,
+        events: libc::POLLIN,
+        revents: 0,
+    };
+    let rc = unsafe {
+        libc::poll(&mut poll_fd, 1, timeout.as_millis() as libc::c_int)
+    };
+    if rc < 0 {
+        let e = std::io::Error::last_os_error();
+        if e.kind() == std::io::ErrorKind::Interrupted {
+            Ok(false)
+        } else {
+            Err(MozimError::socket_error(format!(
+                "Failed to poll socket: {}",
+                e
+            )))
+        }
+    } else {
+        Ok(rc > 0)
+    }
+}
+
+pub(crate) fn recv(fd: &Fd, buf: &mut [u8]) -> Result<usize, MozimError> {
+    let rc = unsafe {
+        libc::recv(fd.0, buf.as_mut_ptr() as *mut libc::c_void, buf.len(), 0)
+    };
+    if rc < 0 {
+        Err(MozimError::socket_error(format!(
+            "Failed to receive from socket: {}",
+            std::io::Error::last_os_error()
+        )))
+    } else {
+        Ok(rc as usize)
+    }
+}
+
+pub(crate) fn send_to<T>(
+    fd: &Fd,
+    data: &[u8],
+    addr: &T,
+) -> Result<(), MozimError> {
+    let rc = unsafe {
+        libc::sendto(
+            fd.0,
+            data.as_ptr() as *const libc::c_void,
+            data.len(),
+            0,
+            addr as *const T as *const libc::sockaddr,
+            std::mem::size_of::<T>() as libc::socklen_t,
+        )
+    };
+    if rc < 0 {
+        Err(MozimError::socket_error(format!(
+            "Failed to send to socket: {}",
+            std::io::Error::last_os_error()
+        )))
+    } else {
+        Ok(())
+    }
+}
+
+// The one's complement checksum used by IPv4 and UDP header.
+pub(crate) fn inet_checksum(data: &[u8], initial: u32) -> u16 {
+    let mut sum = initial;
+    for chunk in data.chunks(2) {
+        let word = if chunk.len() == 2 {
+            u16::from_be_bytes([chunk[0], chunk[1]])
+        } else {
+            u16::from_be_bytes([chunk[0], 0])
+        };
+        sum += word as u32;
+    }
+    while sum >> 16 != 0 {
+        sum = (sum & 0xffff) + (sum >> 16);
+    }
+    !(sum as u16)
+}
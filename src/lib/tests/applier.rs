@@ -0,0 +1,264 @@
+// Copyright 2020 Red Hat, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::net::Ipv4Addr;
+use std::os::unix::fs::PermissionsExt;
+use std::os::unix::io::AsRawFd;
+use std::process::Command;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use mozim::{
+    DhcpApplierType, DhcpClasslessRoute, DhcpHookApplier, DhcpLease,
+    DhcpLeaseApplier, DhcpNetlinkApplier, DhcpOption, DhcpV4Message, DhcpV4Op,
+};
+
+const TEST_MAC: [u8; 6] = [0x00, 0x23, 0x45, 0x67, 0x89, 0x1a];
+const TEST_NETNS: &str = "mozim_test_applier";
+const TEST_IFACE: &str = "mozim_veth0";
+const TEST_PEER: &str = "mozim_veth1";
+const TEST_METRIC: u32 = 500;
+
+fn gen_lease() -> DhcpLease {
+    gen_lease_with_options(
+        Vec::new(),
+        UNIX_EPOCH + Duration::from_secs(1_600_000_000),
+    )
+}
+
+fn gen_lease_with_options(
+    extra_options: Vec<DhcpOption>,
+    acquired_at: SystemTime,
+) -> DhcpLease {
+    let mut ack = DhcpV4Message::new_request(1, &TEST_MAC);
+    ack.op = DhcpV4Op::BootReply;
+    ack.yiaddr = Ipv4Addr::new(192, 0, 2, 100);
+    ack.options = vec![
+        DhcpOption::SubnetMask(Ipv4Addr::new(255, 255, 255, 0)),
+        DhcpOption::Router(vec![Ipv4Addr::new(192, 0, 2, 1)]),
+        DhcpOption::DnsServer(vec![
+            Ipv4Addr::new(192, 0, 2, 2),
+            Ipv4Addr::new(192, 0, 2, 3),
+        ]),
+        DhcpOption::LeaseTime(3600),
+    ];
+    ack.options.extend(extra_options);
+    DhcpLease::new_from_ack(
+        &ack,
+        Some(Ipv4Addr::new(192, 0, 2, 1)),
+        acquired_at,
+    )
+}
+
+#[test]
+fn test_dhcp_applier_type_parse() {
+    assert_eq!("netlink".parse().ok(), Some(DhcpApplierType::Netlink));
+    assert_eq!("none".parse().ok(), Some(DhcpApplierType::None));
+    assert_eq!(
+        "hook:/usr/bin/true".parse().ok(),
+        Some(DhcpApplierType::Hook("/usr/bin/true".into()))
+    );
+    assert!("hook:".parse::<DhcpApplierType>().is_err());
+    assert!("nmstate".parse::<DhcpApplierType>().is_err());
+}
+
+#[test]
+fn test_dhcp_hook_applier() {
+    let dir = std::env::temp_dir()
+        .join(format!("mozim_test_hook_{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let script = dir.join("hook.sh");
+    let output = dir.join("output");
+    std::fs::write(
+        &script,
+        format!(
+            "#!/bin/sh\necho \"$1 $2 $MOZIM_ADDRESS/$MOZIM_PREFIX_LENGTH \
+             $MOZIM_DNS_SERVERS\" >> {}\n[ \"$1\" != remove ]\n",
+            output.display()
+        ),
+    )
+    .unwrap();
+    std::fs::set_permissions(&script, std::fs::Permissions::from_mode(0o755))
+        .unwrap();
+
+    let lease = gen_lease();
+    let mut applier = DhcpHookApplier::new(script.to_str().unwrap());
+    applier.apply_lease("eth1", &lease).unwrap();
+    applier.update_lease("eth1", &lease, &lease).unwrap();
+    // Non-zero exit is failure
+    assert!(applier.remove_lease("eth1", &lease).is_err());
+    assert_eq!(
+        std::fs::read_to_string(&output).unwrap(),
+        "apply eth1 192.0.2.100/24 192.0.2.2 192.0.2.3\n\
+         update eth1 192.0.2.100/24 192.0.2.2 192.0.2.3\n\
+         remove eth1 192.0.2.100/24 192.0.2.2 192.0.2.3\n"
+    );
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_dhcp_hook_applier_timeout() {
+    let dir = std::env::temp_dir()
+        .join(format!("mozim_test_hook_timeout_{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let script = dir.join("hook.sh");
+    std::fs::write(
+        &script,
+        "#!/bin/sh
+sleep 100 &
+sleep 100
+",
+    )
+    .unwrap();
+    std::fs::set_permissions(&script, std::fs::Permissions::from_mode(0o755))
+        .unwrap();
+
+    let mut applier = DhcpHookApplier::new(script.to_str().unwrap())
+        .with_timeout(Duration::from_secs(1));
+    let start = std::time::Instant::now();
+    assert!(applier.apply_lease("eth1", &gen_lease()).is_err());
+    assert!(start.elapsed() < Duration::from_secs(10));
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+fn ip(args: &[&str]) -> String {
+    let output = Command::new("ip")
+        .arg("-n")
+        .arg(TEST_NETNS)
+        .args(args)
+        .output()
+        .unwrap();
+    assert!(
+        output.status.success(),
+        "ip {:?} failed: {:?}",
+        args,
+        output
+    );
+    String::from_utf8(output.stdout).unwrap()
+}
+
+// Return `(<address>/<prefix_length>, valid_lft, preferred_lft)` of each
+// IPv4 address on test interface.
+fn get_addresses() -> Vec<(String, u32, u32)> {
+    let output = ip(&["-4", "addr", "show", "dev", TEST_IFACE]);
+    let mut addresses = Vec::new();
+    let mut lines = output.lines();
+    while let Some(line) = lines.next() {
+        let words: Vec<&str> = line.split_whitespace().collect();
+        if words.first() != Some(&"inet") {
+            continue;
+        }
+        let lifetimes: Vec<u32> = lines
+            .next()
+            .unwrap()
+            .split_whitespace()
+            .filter_map(|w| w.strip_suffix("sec"))
+            .map(|w| w.parse().unwrap())
+            .collect();
+        addresses.push((words[1].to_string(), lifetimes[0], lifetimes[1]));
+    }
+    addresses
+}
+
+fn get_dhcp_routes() -> Vec<String> {
+    ip(&["-4", "route", "show", "dev", TEST_IFACE, "proto", "dhcp"])
+        .lines()
+        .map(|l| l.trim().to_string())
+        .collect()
+}
+
+fn check_netlink_applier(lease: &DhcpLease, expected_routes: &[&str]) {
+    let mut applier = DhcpNetlinkApplier::new(TEST_METRIC);
+    applier.apply_lease(TEST_IFACE, lease).unwrap();
+    let addresses = get_addresses();
+    assert_eq!(addresses.len(), 1);
+    let (address, valid_lft, preferred_lft) = &addresses[0];
+    assert_eq!(address, "192.0.2.100/24");
+    assert!((3590..=3600).contains(valid_lft));
+    assert!((3590..=3600).contains(preferred_lft));
+    assert_eq!(get_dhcp_routes(), expected_routes);
+
+    applier.remove_lease(TEST_IFACE, lease).unwrap();
+    assert!(get_addresses().is_empty());
+    assert!(get_dhcp_routes().is_empty());
+    // Already removed ones are ignored
+    applier.remove_lease(TEST_IFACE, lease).unwrap();
+}
+
+// Needs root to create network namespace, run by
+// `sudo cargo test -- --ignored`.
+#[test]
+#[ignore]
+fn test_dhcp_netlink_applier() {
+    let status = Command::new("ip")
+        .args(["netns", "add", TEST_NETNS])
+        .status()
+        .unwrap();
+    assert!(status.success());
+    // Only the spawned thread enters the network namespace, so it could be
+    // removed whatever the result is.
+    let result = std::thread::spawn(|| {
+        ip(&[
+            "link", "add", TEST_IFACE, "type", "veth", "peer", "name",
+            TEST_PEER,
+        ]);
+        ip(&["link", "set", TEST_IFACE, "up"]);
+        ip(&["link", "set", TEST_PEER, "up"]);
+        let netns =
+            std::fs::File::open(format!("/run/netns/{}", TEST_NETNS)).unwrap();
+        assert_eq!(
+            unsafe { libc::setns(netns.as_raw_fd(), libc::CLONE_NEWNET) },
+            0
+        );
+
+        let now = SystemTime::now();
+        check_netlink_applier(
+            &gen_lease_with_options(Vec::new(), now),
+            &["default via 192.0.2.1 src 192.0.2.100 metric 500"],
+        );
+        // Classless routes take precedence over the router option
+        check_netlink_applier(
+            &gen_lease_with_options(
+                vec![DhcpOption::ClasslessStaticRoute(vec![
+                    DhcpClasslessRoute {
+                        dst: Ipv4Addr::new(203, 0, 113, 0),
+                        prefix_length: 24,
+                        gateway: Ipv4Addr::UNSPECIFIED,
+                    },
+                    DhcpClasslessRoute {
+                        dst: Ipv4Addr::new(198, 51, 100, 0),
+                        prefix_length: 24,
+                        gateway: Ipv4Addr::new(192, 0, 2, 254),
+                    },
+                    DhcpClasslessRoute {
+                        dst: Ipv4Addr::UNSPECIFIED,
+                        prefix_length: 0,
+                        gateway: Ipv4Addr::new(192, 0, 2, 254),
+                    },
+                ])],
+                now,
+            ),
+            &[
+                "default via 192.0.2.254 src 192.0.2.100 metric 500",
+                "198.51.100.0/24 via 192.0.2.254 src 192.0.2.100 metric 500",
+                "203.0.113.0/24 scope link src 192.0.2.100 metric 500",
+            ],
+        );
+    })
+    .join();
+    Command::new("ip")
+        .args(["netns", "del", TEST_NETNS])
+        .status()
+        .unwrap();
+    result.unwrap();
+}
@@ -0,0 +1,61 @@
+// Copyright 2020 Red Hat, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::net::Ipv4Addr;
+
+use mozim::{DhcpV4ArpPacket, ARP_OP_REQUEST};
+
+const TEST_MAC: [u8; 6] = [0x00, 0x23, 0x45, 0x67, 0x89, 0x1a];
+const OTHER_MAC: [u8; 6] = [0x00, 0x23, 0x45, 0x67, 0x89, 0x1b];
+const TEST_IP: Ipv4Addr = Ipv4Addr::new(192, 0, 2, 100);
+
+#[test]
+fn test_arp_probe_emit_parse() {
+    let probe = DhcpV4ArpPacket::new_probe(&TEST_MAC, TEST_IP);
+    let data = probe.emit();
+    assert_eq!(
+        data,
+        vec![
+            0x00, 0x01, 0x08, 0x00, 0x06, 0x04, 0x00, 0x01, 0x00, 0x23, 0x45,
+            0x67, 0x89, 0x1a, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 192, 0, 2, 100
+        ]
+    );
+    assert_eq!(DhcpV4ArpPacket::parse(&data).unwrap(), probe);
+    assert!(DhcpV4ArpPacket::parse(&data[..27]).is_err());
+}
+
+#[test]
+fn test_arp_conflict() {
+    // Own probe
+    let probe = DhcpV4ArpPacket::new_probe(&TEST_MAC, TEST_IP);
+    assert!(!probe.is_conflict(&TEST_MAC, TEST_IP));
+    // Other host probing the same address
+    let probe = DhcpV4ArpPacket::new_probe(&OTHER_MAC, TEST_IP);
+    assert!(probe.is_conflict(&TEST_MAC, TEST_IP));
+    // Other host asking for the address
+    let request = DhcpV4ArpPacket {
+        op: ARP_OP_REQUEST,
+        sender_mac: OTHER_MAC,
+        sender_ip: Ipv4Addr::new(192, 0, 2, 1),
+        target_mac: [0u8; 6],
+        target_ip: TEST_IP,
+    };
+    assert!(!request.is_conflict(&TEST_MAC, TEST_IP));
+    // Other host using the address
+    let announce = DhcpV4ArpPacket {
+        sender_ip: TEST_IP,
+        ..request
+    };
+    assert!(announce.is_conflict(&TEST_MAC, TEST_IP));
+}
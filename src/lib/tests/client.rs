@@ -0,0 +1,916 @@
+// Copyright 2020 Red Hat, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::BTreeMap;
+use std::net::Ipv4Addr;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use mozim::{
+    DhcpClientFqdn, DhcpLease, DhcpOption, DhcpState, DhcpV4ArpPacket,
+    DhcpV4Client, DhcpV4Config, DhcpV4Event, DhcpV4Message, DhcpV4MessageType,
+    DhcpV4Op, DhcpV4Transmit, DhcpVendorInfo, DhcpViVendorSpecific, ErrorKind,
+    ARP_OP_REPLY,
+};
+
+const TEST_MAC: [u8; 6] = [0x00, 0x23, 0x45, 0x67, 0x89, 0x1a];
+const SERVER_ID: Ipv4Addr = Ipv4Addr::new(192, 0, 2, 1);
+const LEASE_IP: Ipv4Addr = Ipv4Addr::new(192, 0, 2, 100);
+
+fn time(secs: u64) -> SystemTime {
+    UNIX_EPOCH + Duration::from_secs(1_600_000_000 + secs)
+}
+
+fn gen_reply(
+    request: &DhcpV4Message,
+    msg_type: DhcpV4MessageType,
+) -> DhcpV4Message {
+    gen_reply_with_lease(request, msg_type, 1000, Some((500, 800)))
+}
+
+fn gen_reply_with_lease(
+    request: &DhcpV4Message,
+    msg_type: DhcpV4MessageType,
+    lease_time: u32,
+    t1_t2: Option<(u32, u32)>,
+) -> DhcpV4Message {
+    let mut reply = request.clone();
+    reply.op = DhcpV4Op::BootReply;
+    reply.yiaddr = LEASE_IP;
+    reply.options = vec![
+        DhcpOption::MessageType(msg_type),
+        DhcpOption::ServerIdentifier(SERVER_ID),
+        DhcpOption::LeaseTime(lease_time),
+    ];
+    if let Some((t1, t2)) = t1_t2 {
+        reply.options.push(DhcpOption::RenewalTime(t1));
+        reply.options.push(DhcpOption::RebindingTime(t2));
+    }
+    reply
+}
+
+fn expect_transmit(
+    client: &mut DhcpV4Client,
+    msg_type: DhcpV4MessageType,
+    dst: Ipv4Addr,
+) -> DhcpV4Message {
+    let DhcpV4Transmit { msg, dst: real_dst } = client.poll_transmit().unwrap();
+    assert_eq!(msg.message_type(), Some(msg_type));
+    assert_eq!(real_dst, dst);
+    assert!(client.poll_transmit().is_none());
+    msg
+}
+
+// Run DISCOVER/OFFER/REQUEST/ACK and return the bound client.
+fn gen_bound_client() -> DhcpV4Client {
+    let mut client = DhcpV4Client::new(DhcpV4Config::new("eth1", &TEST_MAC), 1);
+    assert_eq!(client.state(), DhcpState::Stopped);
+    client.start(time(0));
+    assert_eq!(client.state(), DhcpState::Selecting);
+    let discover = expect_transmit(
+        &mut client,
+        DhcpV4MessageType::Discover,
+        Ipv4Addr::BROADCAST,
+    );
+
+    client.handle_message(
+        &gen_reply(&discover, DhcpV4MessageType::Offer),
+        time(1),
+    );
+    assert_eq!(client.state(), DhcpState::Requesting);
+    let request = expect_transmit(
+        &mut client,
+        DhcpV4MessageType::Request,
+        Ipv4Addr::BROADCAST,
+    );
+    assert!(request.options.contains(&DhcpOption::RequestedIp(LEASE_IP)));
+    assert!(request
+        .options
+        .contains(&DhcpOption::ServerIdentifier(SERVER_ID)));
+
+    let ack = gen_reply(&request, DhcpV4MessageType::Ack);
+    client.handle_message(&ack, time(2));
+    assert_eq!(client.state(), DhcpState::Bound);
+    assert_eq!(
+        client.poll_event(),
+        Some(DhcpV4Event::LeaseAcquired(DhcpLease::new_from_ack(
+            &ack,
+            None,
+            time(2)
+        )))
+    );
+    assert_eq!(client.leased_address(), Some(LEASE_IP));
+    assert_eq!(client.poll_timeout(), Some(time(502)));
+    client
+}
+
+#[test]
+fn test_client_bound() {
+    gen_bound_client();
+}
+
+#[test]
+fn test_client_ignore_other_xid() {
+    let mut client = DhcpV4Client::new(DhcpV4Config::new("eth1", &TEST_MAC), 1);
+    client.start(time(0));
+    let discover = client.poll_transmit().unwrap().msg;
+    let mut offer = gen_reply(&discover, DhcpV4MessageType::Offer);
+    offer.xid = offer.xid.wrapping_add(1);
+    client.handle_message(&offer, time(1));
+    assert_eq!(client.state(), DhcpState::Selecting);
+    assert!(client.poll_transmit().is_none());
+}
+
+#[test]
+fn test_client_ignore_invalid_ack() {
+    let mut client = DhcpV4Client::new(DhcpV4Config::new("eth1", &TEST_MAC), 1);
+    client.start(time(0));
+    let discover = client.poll_transmit().unwrap().msg;
+    client.handle_message(
+        &gen_reply(&discover, DhcpV4MessageType::Offer),
+        time(1),
+    );
+    let request = client.poll_transmit().unwrap().msg;
+    let ack = gen_reply(&request, DhcpV4MessageType::Ack);
+
+    let mut other_server = ack.clone();
+    other_server.options[1] =
+        DhcpOption::ServerIdentifier(Ipv4Addr::new(192, 0, 2, 2));
+    let mut no_server_id = ack.clone();
+    no_server_id.options.remove(1);
+    let mut other_address = ack.clone();
+    other_address.yiaddr = Ipv4Addr::new(192, 0, 2, 101);
+    let mut no_address = ack.clone();
+    no_address.yiaddr = Ipv4Addr::UNSPECIFIED;
+    for invalid in [other_server, no_server_id, other_address, no_address] {
+        client.handle_message(&invalid, time(2));
+        assert_eq!(client.state(), DhcpState::Requesting);
+        assert!(client.poll_event().is_none());
+    }
+    // Still retransmitting DHCPREQUEST
+    assert!(client.poll_timeout().is_some());
+
+    client.handle_message(&ack, time(2));
+    assert_eq!(client.state(), DhcpState::Bound);
+    assert_eq!(client.lease().unwrap().server_id, SERVER_ID);
+}
+
+#[test]
+fn test_client_retransmit_discover() {
+    let mut client = DhcpV4Client::new(DhcpV4Config::new("eth1", &TEST_MAC), 1);
+    client.start(time(0));
+    let discover = client.poll_transmit().unwrap().msg;
+    let deadline = client.poll_timeout().unwrap();
+    client.handle_timeout(deadline);
+    assert_eq!(client.poll_transmit().unwrap().msg, discover);
+    assert!(client.poll_timeout().unwrap() > deadline);
+}
+
+#[test]
+fn test_client_renew_and_rebind() {
+    let mut client = gen_bound_client();
+
+    client.handle_timeout(time(502));
+    assert_eq!(client.state(), DhcpState::Renewing);
+    let renew =
+        expect_transmit(&mut client, DhcpV4MessageType::Request, SERVER_ID);
+    assert_eq!(renew.ciaddr, LEASE_IP);
+
+    // Server is gone, rebind at T2 by broadcast
+    client.handle_timeout(time(802));
+    assert_eq!(client.state(), DhcpState::Rebinding);
+    let rebind = client.poll_transmit().unwrap();
+    assert_eq!(rebind.dst, Ipv4Addr::BROADCAST);
+
+    let ack = gen_reply(&rebind.msg, DhcpV4MessageType::Ack);
+    client.handle_message(&ack, time(803));
+    assert_eq!(client.state(), DhcpState::Bound);
+    assert_eq!(
+        client.poll_event(),
+        Some(DhcpV4Event::LeaseRebound(DhcpLease::new_from_ack(
+            &ack,
+            None,
+            time(803)
+        )))
+    );
+}
+
+#[test]
+fn test_client_nak_while_renewing() {
+    let mut client = gen_bound_client();
+    let lease = client.lease().cloned().unwrap();
+    client.handle_timeout(time(502));
+    let renew = client.poll_transmit().unwrap().msg;
+    client
+        .handle_message(&gen_reply(&renew, DhcpV4MessageType::Nak), time(503));
+    assert_eq!(client.poll_event(), Some(DhcpV4Event::LeaseLost(lease)));
+    assert_eq!(client.state(), DhcpState::Selecting);
+    expect_transmit(
+        &mut client,
+        DhcpV4MessageType::Discover,
+        Ipv4Addr::BROADCAST,
+    );
+}
+
+#[test]
+fn test_client_ignore_nak_of_other_server() {
+    let other_server =
+        DhcpOption::ServerIdentifier(Ipv4Addr::new(192, 0, 2, 2));
+
+    // Requesting
+    let mut client = DhcpV4Client::new(DhcpV4Config::new("eth1", &TEST_MAC), 1);
+    client.start(time(0));
+    let discover = client.poll_transmit().unwrap().msg;
+    client.handle_message(
+        &gen_reply(&discover, DhcpV4MessageType::Offer),
+        time(1),
+    );
+    let request = client.poll_transmit().unwrap().msg;
+    let mut nak = gen_reply(&request, DhcpV4MessageType::Nak);
+    nak.options[1] = other_server.clone();
+    client.handle_message(&nak, time(2));
+    assert_eq!(client.state(), DhcpState::Requesting);
+    assert!(client.poll_transmit().is_none());
+
+    // Renewing
+    let mut client = gen_bound_client();
+    client.handle_timeout(time(502));
+    let renew = client.poll_transmit().unwrap().msg;
+    let mut nak = gen_reply(&renew, DhcpV4MessageType::Nak);
+    nak.options[1] = other_server;
+    client.handle_message(&nak, time(503));
+    assert_eq!(client.state(), DhcpState::Renewing);
+    assert!(client.poll_event().is_none());
+    assert!(client.lease().is_some());
+}
+
+#[test]
+fn test_client_lease_expired() {
+    let mut client = gen_bound_client();
+    let lease = client.lease().cloned().unwrap();
+    client.handle_timeout(time(1002));
+    assert_eq!(client.poll_event(), Some(DhcpV4Event::LeaseLost(lease)));
+    assert_eq!(client.state(), DhcpState::Selecting);
+    assert!(client.lease().is_none());
+}
+
+#[test]
+fn test_client_failed_after_timeout() {
+    let mut config = DhcpV4Config::new("eth1", &TEST_MAC);
+    config.timeout = Duration::from_secs(30);
+    let mut client = DhcpV4Client::new(config, 1);
+    client.start(time(0));
+    client.handle_timeout(time(29));
+    assert!(client.poll_event().is_none());
+    client.handle_timeout(time(30));
+    assert_eq!(client.poll_event(), Some(DhcpV4Event::Failed));
+    assert!(client.poll_event().is_none());
+    // Still trying
+    assert_eq!(client.state(), DhcpState::Selecting);
+}
+
+#[test]
+fn test_client_default_t1_t2() {
+    let mut client = DhcpV4Client::new(DhcpV4Config::new("eth1", &TEST_MAC), 1);
+    client.start(time(0));
+    let discover = client.poll_transmit().unwrap().msg;
+    client.handle_message(
+        &gen_reply_with_lease(&discover, DhcpV4MessageType::Offer, 1000, None),
+        time(0),
+    );
+    let request = client.poll_transmit().unwrap().msg;
+    client.handle_message(
+        &gen_reply_with_lease(&request, DhcpV4MessageType::Ack, 1000, None),
+        time(0),
+    );
+    let status = client.status(time(100));
+    assert_eq!(status.state, DhcpState::Bound);
+    assert_eq!(status.renew_at, Some(1_600_000_500));
+    assert_eq!(status.rebind_at, Some(1_600_000_875));
+    assert_eq!(status.expire_at, Some(1_600_001_000));
+    let lease = status.lease.unwrap();
+    assert_eq!((lease.t1, lease.t2, lease.lease_time), (500, 875, 1000));
+    assert_eq!(lease.remaining, Some(900));
+}
+
+#[test]
+fn test_client_discover_backoff() {
+    let mut config = DhcpV4Config::new("eth1", &TEST_MAC);
+    // Keep the failure timer out of the retransmission schedule
+    config.timeout = Duration::from_secs(3600);
+    let mut client = DhcpV4Client::new(config, 1);
+    client.start(time(0));
+    let mut now = time(0);
+    for base in &[4u64, 8, 16, 32, 64, 64] {
+        let next = client.poll_timeout().unwrap();
+        let wait = next.duration_since(now).unwrap();
+        assert!(wait >= Duration::from_secs(base - 1));
+        assert!(wait <= Duration::from_secs(base + 1));
+        client.handle_timeout(next);
+        assert!(client.poll_transmit().is_some());
+        now = next;
+    }
+}
+
+#[test]
+fn test_client_renew_retransmit_halving() {
+    let mut client = gen_bound_client();
+    // T1 at 502, T2 at 802: retry after 150 seconds
+    client.handle_timeout(time(502));
+    assert_eq!(client.status(time(502)).renew_at, Some(1_600_000_652));
+    client.poll_transmit().unwrap();
+    client.handle_timeout(time(652));
+    assert!(client.poll_transmit().is_some());
+    assert_eq!(client.state(), DhcpState::Renewing);
+    // 150 seconds to T2, the half is 75 seconds
+    assert_eq!(client.status(time(652)).renew_at, Some(1_600_000_727));
+    client.handle_timeout(time(727));
+    client.poll_transmit().unwrap();
+    // 75 seconds to T2, wait at least 60 seconds, then T2 kicks in
+    assert_eq!(client.poll_timeout(), Some(time(787)));
+    client.handle_timeout(time(787));
+    client.poll_transmit().unwrap();
+    assert_eq!(client.poll_timeout(), Some(time(802)));
+    client.handle_timeout(time(802));
+    assert_eq!(client.state(), DhcpState::Rebinding);
+    // 200 seconds to expiry
+    assert_eq!(client.status(time(802)).renew_at, Some(1_600_000_902));
+}
+
+#[test]
+fn test_client_init_reboot() {
+    let mut client = DhcpV4Client::new(DhcpV4Config::new("eth1", &TEST_MAC), 1);
+    client.start_init_reboot(time(0), LEASE_IP);
+    assert_eq!(client.state(), DhcpState::Rebooting);
+    let request = expect_transmit(
+        &mut client,
+        DhcpV4MessageType::Request,
+        Ipv4Addr::BROADCAST,
+    );
+    assert_eq!(request.ciaddr, Ipv4Addr::UNSPECIFIED);
+    assert!(request.options.contains(&DhcpOption::RequestedIp(LEASE_IP)));
+    assert!(request.server_identifier().is_none());
+
+    client
+        .handle_message(&gen_reply(&request, DhcpV4MessageType::Ack), time(1));
+    assert_eq!(client.state(), DhcpState::Bound);
+    assert!(matches!(
+        client.poll_event(),
+        Some(DhcpV4Event::LeaseAcquired(_))
+    ));
+    assert_eq!(client.status(time(1)).lease_reused, Some(true));
+    assert_eq!(gen_bound_client().status(time(2)).lease_reused, Some(false));
+}
+
+#[test]
+fn test_client_init_reboot_ignore_other_address() {
+    let mut client = DhcpV4Client::new(DhcpV4Config::new("eth1", &TEST_MAC), 1);
+    client.start_init_reboot(time(0), LEASE_IP);
+    let request = client.poll_transmit().unwrap().msg;
+    let mut ack = gen_reply(&request, DhcpV4MessageType::Ack);
+    ack.yiaddr = Ipv4Addr::new(192, 0, 2, 101);
+    client.handle_message(&ack, time(1));
+    assert_eq!(client.state(), DhcpState::Rebooting);
+    assert!(client.poll_event().is_none());
+
+    client
+        .handle_message(&gen_reply(&request, DhcpV4MessageType::Ack), time(1));
+    assert_eq!(client.state(), DhcpState::Bound);
+    assert_eq!(client.lease().unwrap().address, LEASE_IP);
+    assert!(client.lease_reused());
+}
+
+#[test]
+fn test_client_init_reboot_with_arp_probe() {
+    let mut config = DhcpV4Config::new("eth1", &TEST_MAC);
+    config.arp_probe_count = 1;
+    let mut client = DhcpV4Client::new(config, 1);
+    client.start_init_reboot(time(0), LEASE_IP);
+    let request = client.poll_transmit().unwrap().msg;
+    client
+        .handle_message(&gen_reply(&request, DhcpV4MessageType::Ack), time(1));
+    assert_eq!(client.state(), DhcpState::Probing);
+    assert!(client.poll_arp_transmit().is_some());
+    assert!(client.poll_event().is_none());
+
+    let next = client.poll_timeout().unwrap();
+    client.handle_timeout(next);
+    assert_eq!(client.state(), DhcpState::Bound);
+    assert!(matches!(
+        client.poll_event(),
+        Some(DhcpV4Event::LeaseAcquired(_))
+    ));
+    // Still reported as reused although the state before binding was
+    // Probing instead of Rebooting.
+    assert!(client.lease_reused());
+    assert_eq!(client.status(next).lease_reused, Some(true));
+}
+
+#[test]
+fn test_client_init_reboot_fallback() {
+    // Refused by DHCPNAK
+    let mut client = DhcpV4Client::new(DhcpV4Config::new("eth1", &TEST_MAC), 1);
+    client.start_init_reboot(time(0), LEASE_IP);
+    let request = client.poll_transmit().unwrap().msg;
+    client
+        .handle_message(&gen_reply(&request, DhcpV4MessageType::Nak), time(1));
+    assert_eq!(client.state(), DhcpState::Selecting);
+    expect_transmit(
+        &mut client,
+        DhcpV4MessageType::Discover,
+        Ipv4Addr::BROADCAST,
+    );
+    assert!(client.poll_event().is_none());
+
+    // No reply
+    let mut client = DhcpV4Client::new(DhcpV4Config::new("eth1", &TEST_MAC), 1);
+    client.start_init_reboot(time(0), LEASE_IP);
+    client.poll_transmit().unwrap();
+    while client.state() == DhcpState::Rebooting {
+        let next = client.poll_timeout().unwrap();
+        assert!(next <= time(10));
+        client.handle_timeout(next);
+        if client.state() == DhcpState::Rebooting {
+            client.poll_transmit().unwrap();
+        }
+    }
+    assert_eq!(client.state(), DhcpState::Selecting);
+    expect_transmit(
+        &mut client,
+        DhcpV4MessageType::Discover,
+        Ipv4Addr::BROADCAST,
+    );
+}
+
+// Run DISCOVER/OFFER/REQUEST/ACK with 2 ARP probes and return the client in
+// Probing state.
+fn gen_probing_client() -> DhcpV4Client {
+    let mut config = DhcpV4Config::new("eth1", &TEST_MAC);
+    config.arp_probe_count = 2;
+    let mut client = DhcpV4Client::new(config, 1);
+    client.start(time(0));
+    let discover = client.poll_transmit().unwrap().msg;
+    client.handle_message(
+        &gen_reply(&discover, DhcpV4MessageType::Offer),
+        time(1),
+    );
+    let request = client.poll_transmit().unwrap().msg;
+    client
+        .handle_message(&gen_reply(&request, DhcpV4MessageType::Ack), time(2));
+    assert_eq!(client.state(), DhcpState::Probing);
+    assert!(client.lease().is_none());
+    assert!(client.poll_event().is_none());
+    assert_eq!(
+        client.poll_arp_transmit(),
+        Some(DhcpV4ArpPacket::new_probe(&TEST_MAC, LEASE_IP))
+    );
+    assert!(client.poll_arp_transmit().is_none());
+    client
+}
+
+#[test]
+fn test_client_arp_probe_no_conflict() {
+    let mut client = gen_probing_client();
+    // Our own probe echoed back is not conflict
+    client
+        .handle_arp(&DhcpV4ArpPacket::new_probe(&TEST_MAC, LEASE_IP), time(2));
+    assert_eq!(client.poll_timeout(), Some(time(3)));
+    client.handle_timeout(time(3));
+    assert!(client.poll_arp_transmit().is_some());
+    assert_eq!(client.state(), DhcpState::Probing);
+    client.handle_timeout(time(4));
+    assert!(client.poll_arp_transmit().is_none());
+    assert_eq!(client.state(), DhcpState::Bound);
+    assert!(matches!(
+        client.poll_event(),
+        Some(DhcpV4Event::LeaseAcquired(_))
+    ));
+    assert!(client.poll_transmit().is_none());
+}
+
+#[test]
+fn test_client_arp_probe_conflict() {
+    let mut client = gen_probing_client();
+    let reply = DhcpV4ArpPacket {
+        op: ARP_OP_REPLY,
+        sender_mac: [0x00, 0x23, 0x45, 0x67, 0x89, 0x1b],
+        sender_ip: LEASE_IP,
+        target_mac: TEST_MAC,
+        target_ip: Ipv4Addr::UNSPECIFIED,
+    };
+    client.handle_arp(&reply, time(2));
+    assert_eq!(client.state(), DhcpState::Init);
+    assert_eq!(
+        client.poll_event(),
+        Some(DhcpV4Event::AddressDeclined(LEASE_IP))
+    );
+    let decline = expect_transmit(
+        &mut client,
+        DhcpV4MessageType::Decline,
+        Ipv4Addr::BROADCAST,
+    );
+    assert!(decline.options.contains(&DhcpOption::RequestedIp(LEASE_IP)));
+    assert_eq!(decline.server_identifier(), Some(SERVER_ID));
+
+    // Backoff 10 seconds before DHCPDISCOVER
+    assert_eq!(client.poll_timeout(), Some(time(12)));
+    client.handle_timeout(time(12));
+    assert_eq!(client.state(), DhcpState::Selecting);
+    expect_transmit(
+        &mut client,
+        DhcpV4MessageType::Discover,
+        Ipv4Addr::BROADCAST,
+    );
+}
+
+#[test]
+fn test_client_link_local_fallback() {
+    let mut config = DhcpV4Config::new("eth1", &TEST_MAC);
+    config.timeout = Duration::from_secs(3600);
+    config.link_local_timeout = Some(Duration::from_secs(5));
+    let mut client = DhcpV4Client::new(config, 1);
+    client.start(time(0));
+    let mut discover = client.poll_transmit().unwrap().msg;
+    let mut probes = Vec::new();
+    let address = loop {
+        let now = client.poll_timeout().unwrap();
+        client.handle_timeout(now);
+        while let Some(transmit) = client.poll_transmit() {
+            discover = transmit.msg;
+        }
+        while let Some(packet) = client.poll_arp_transmit() {
+            probes.push(packet);
+        }
+        if let Some(DhcpV4Event::LinkLocalAcquired(a)) = client.poll_event() {
+            break a;
+        }
+        assert!(now < time(30));
+    };
+    assert!(address.is_link_local());
+    // 3 probes then the first announcement
+    assert_eq!(probes.len(), 4);
+    assert!(probes[..3]
+        .iter()
+        .all(|p| p == &DhcpV4ArpPacket::new_probe(&TEST_MAC, address)));
+    assert_eq!(
+        probes[3],
+        DhcpV4ArpPacket::new_announcement(&TEST_MAC, address)
+    );
+    assert_eq!(client.state(), DhcpState::LinkLocal);
+    assert_eq!(client.status(time(30)).link_local, Some(address));
+    assert!(client.needs_arp());
+
+    // DHCP is still retrying, the lease replaces link-local address
+    client.handle_message(
+        &gen_reply(&discover, DhcpV4MessageType::Offer),
+        time(30),
+    );
+    let request = client.poll_transmit().unwrap().msg;
+    client
+        .handle_message(&gen_reply(&request, DhcpV4MessageType::Ack), time(31));
+    assert_eq!(
+        client.poll_event(),
+        Some(DhcpV4Event::LinkLocalLost(address))
+    );
+    assert!(matches!(
+        client.poll_event(),
+        Some(DhcpV4Event::LeaseAcquired(_))
+    ));
+    assert_eq!(client.state(), DhcpState::Bound);
+    assert!(!client.needs_arp());
+}
+
+#[test]
+fn test_client_release() {
+    let mut client = gen_bound_client();
+    client.release();
+    assert_eq!(client.state(), DhcpState::Stopped);
+    let release =
+        expect_transmit(&mut client, DhcpV4MessageType::Release, SERVER_ID);
+    assert_eq!(release.ciaddr, LEASE_IP);
+    assert_eq!(release.server_identifier(), Some(SERVER_ID));
+    assert!(client.lease().is_none());
+    assert!(client.poll_timeout().is_none());
+}
+
+#[test]
+fn test_client_inform() {
+    let static_ip = Ipv4Addr::new(192, 0, 2, 10);
+    let mut client = DhcpV4Client::new(DhcpV4Config::new("eth1", &TEST_MAC), 1);
+    client.start_inform(time(0), static_ip);
+    assert_eq!(client.state(), DhcpState::Inform);
+    let inform = expect_transmit(
+        &mut client,
+        DhcpV4MessageType::Inform,
+        Ipv4Addr::BROADCAST,
+    );
+    assert_eq!(inform.ciaddr, static_ip);
+    assert!(client.lease().is_none());
+
+    let mut ack = gen_reply(&inform, DhcpV4MessageType::Ack);
+    ack.yiaddr = Ipv4Addr::UNSPECIFIED;
+    ack.options = vec![
+        DhcpOption::MessageType(DhcpV4MessageType::Ack),
+        DhcpOption::ServerIdentifier(SERVER_ID),
+        DhcpOption::DnsServer(vec![SERVER_ID]),
+    ];
+    client.handle_message(&ack, time(1));
+    assert_eq!(client.state(), DhcpState::Inform);
+    let lease = match client.poll_event() {
+        Some(DhcpV4Event::OptionsAcquired(l)) => l,
+        e => panic!("Expecting OptionsAcquired, got {:?}", e),
+    };
+    assert_eq!(lease.address, static_ip);
+    assert_eq!(lease.dns_servers, vec![SERVER_ID]);
+    assert_eq!(client.leased_address(), None);
+
+    // Refreshed after one hour, no DHCPRELEASE on release
+    assert_eq!(client.poll_timeout(), Some(time(3601)));
+    client.handle_timeout(time(3601));
+    expect_transmit(
+        &mut client,
+        DhcpV4MessageType::Inform,
+        Ipv4Addr::BROADCAST,
+    );
+    client.release();
+    assert!(client.poll_transmit().is_none());
+}
+
+#[test]
+fn test_client_rapid_commit() {
+    let mut config = DhcpV4Config::new("eth1", &TEST_MAC);
+    config.rapid_commit = true;
+    let mut client = DhcpV4Client::new(config, 1);
+    client.start(time(0));
+    let discover = expect_transmit(
+        &mut client,
+        DhcpV4MessageType::Discover,
+        Ipv4Addr::BROADCAST,
+    );
+    assert!(discover.options.contains(&DhcpOption::RapidCommit));
+
+    // DHCPACK without rapid commit option is ignored
+    let mut ack = gen_reply(&discover, DhcpV4MessageType::Ack);
+    client.handle_message(&ack, time(1));
+    assert_eq!(client.state(), DhcpState::Selecting);
+
+    ack.options.push(DhcpOption::RapidCommit);
+    client.handle_message(&ack, time(1));
+    assert_eq!(client.state(), DhcpState::Bound);
+    assert!(matches!(
+        client.poll_event(),
+        Some(DhcpV4Event::LeaseAcquired(_))
+    ));
+    assert!(client.poll_transmit().is_none());
+    assert_eq!(client.status(time(1)).rapid_commit, Some(true));
+}
+
+#[test]
+fn test_client_client_id() {
+    let client_id = vec![0xff, 0, 0, 0, 1, 0, 3, 0, 1, 1, 2, 3, 4, 5, 6];
+    let mut config = DhcpV4Config::new("eth1", &TEST_MAC);
+    config.client_id = Some(client_id.clone());
+    let mut client = DhcpV4Client::new(config, 1);
+    client.start(time(0));
+    let discover = expect_transmit(
+        &mut client,
+        DhcpV4MessageType::Discover,
+        Ipv4Addr::BROADCAST,
+    );
+    assert!(discover
+        .options
+        .contains(&DhcpOption::ClientIdentifier(client_id.clone())));
+    client.handle_message(
+        &gen_reply(&discover, DhcpV4MessageType::Offer),
+        time(1),
+    );
+    let request = expect_transmit(
+        &mut client,
+        DhcpV4MessageType::Request,
+        Ipv4Addr::BROADCAST,
+    );
+    assert!(request
+        .options
+        .contains(&DhcpOption::ClientIdentifier(client_id.clone())));
+    client
+        .handle_message(&gen_reply(&request, DhcpV4MessageType::Ack), time(2));
+    assert_eq!(client.state(), DhcpState::Bound);
+    client.poll_event();
+    client.release();
+    let release =
+        expect_transmit(&mut client, DhcpV4MessageType::Release, SERVER_ID);
+    assert!(release
+        .options
+        .contains(&DhcpOption::ClientIdentifier(client_id)));
+}
+
+#[test]
+fn test_client_host_name_and_fqdn() {
+    let mut config = DhcpV4Config::new("eth1", &TEST_MAC);
+    config.host_name = Some("host1".to_string());
+    let mut client = DhcpV4Client::new(config.clone(), 1);
+    client.start(time(0));
+    let discover = expect_transmit(
+        &mut client,
+        DhcpV4MessageType::Discover,
+        Ipv4Addr::BROADCAST,
+    );
+    assert!(discover
+        .options
+        .contains(&DhcpOption::HostName("host1".to_string())));
+
+    // Host name is not sent along with FQDN
+    let fqdn = DhcpClientFqdn::new("host1.example.com", true);
+    config.fqdn = Some(fqdn.clone());
+    let mut client = DhcpV4Client::new(config, 1);
+    client.start(time(0));
+    let discover = expect_transmit(
+        &mut client,
+        DhcpV4MessageType::Discover,
+        Ipv4Addr::BROADCAST,
+    );
+    assert!(discover
+        .options
+        .contains(&DhcpOption::ClientFqdn(fqdn.clone())));
+    assert!(!discover
+        .options
+        .iter()
+        .any(|o| matches!(o, DhcpOption::HostName(_))));
+    client.handle_message(
+        &gen_reply(&discover, DhcpV4MessageType::Offer),
+        time(1),
+    );
+    let request = expect_transmit(
+        &mut client,
+        DhcpV4MessageType::Request,
+        Ipv4Addr::BROADCAST,
+    );
+    assert!(request
+        .options
+        .contains(&DhcpOption::ClientFqdn(fqdn.clone())));
+    let mut ack = gen_reply(&request, DhcpV4MessageType::Ack);
+    let mut reply_fqdn = fqdn;
+    reply_fqdn.flags.server_override = true;
+    reply_fqdn.rcode1 = 255;
+    reply_fqdn.rcode2 = 255;
+    ack.options.push(DhcpOption::ClientFqdn(reply_fqdn.clone()));
+    client.handle_message(&ack, time(2));
+    assert_eq!(client.state(), DhcpState::Bound);
+    assert_eq!(client.status(time(2)).fqdn_flags, Some(reply_fqdn.flags));
+}
+
+fn decode_test_vendor(data: &[u8]) -> Option<BTreeMap<String, String>> {
+    let mut values = BTreeMap::new();
+    values.insert("serial".to_string(), String::from_utf8(data.to_vec()).ok()?);
+    Some(values)
+}
+
+#[test]
+fn test_client_config_validate() {
+    let mut config = DhcpV4Config::new("eth1", &TEST_MAC);
+    config.user_class = vec!["lab".to_string(), "a".repeat(255)];
+    config.vi_vendor_specific = vec![DhcpViVendorSpecific {
+        enterprise_number: 4491,
+        data: vec![0; 255],
+    }];
+    assert!(config.validate().is_ok());
+
+    // Length of each item is a single byte in the option
+    let mut oversized = config.clone();
+    oversized.user_class.push("a".repeat(256));
+    assert!(matches!(
+        oversized.validate().unwrap_err().kind,
+        ErrorKind::InvalidArgument
+    ));
+    let mut empty = config.clone();
+    empty.user_class.push(String::new());
+    assert!(empty.validate().is_err());
+    let mut oversized = config;
+    oversized.vi_vendor_specific[0].data.push(0);
+    assert!(matches!(
+        oversized.validate().unwrap_err().kind,
+        ErrorKind::InvalidArgument
+    ));
+}
+
+#[test]
+fn test_client_vendor_info() {
+    let mut config = DhcpV4Config::new("eth1", &TEST_MAC);
+    config.vendor_class = Some("acme".to_string());
+    config.user_class = vec!["lab".to_string()];
+    config.vi_vendor_specific = vec![DhcpViVendorSpecific {
+        enterprise_number: 4491,
+        data: vec![1, 1, 7],
+    }];
+    let mut client = DhcpV4Client::new(config, 1);
+    client.register_vendor_decoder(32473, decode_test_vendor);
+    client.start(time(0));
+    let discover = expect_transmit(
+        &mut client,
+        DhcpV4MessageType::Discover,
+        Ipv4Addr::BROADCAST,
+    );
+    assert!(discover
+        .options
+        .contains(&DhcpOption::VendorClass("acme".to_string())));
+    assert!(discover
+        .options
+        .contains(&DhcpOption::UserClass(vec!["lab".to_string()])));
+    let codes = discover
+        .options
+        .iter()
+        .find_map(|o| match o {
+            DhcpOption::ParameterRequestList(c) => Some(c.clone()),
+            _ => None,
+        })
+        .unwrap();
+    assert!(codes.contains(&43) && codes.contains(&125));
+
+    client.handle_message(
+        &gen_reply(&discover, DhcpV4MessageType::Offer),
+        time(1),
+    );
+    let request = expect_transmit(
+        &mut client,
+        DhcpV4MessageType::Request,
+        Ipv4Addr::BROADCAST,
+    );
+    let mut ack = gen_reply(&request, DhcpV4MessageType::Ack);
+    ack.options
+        .push(DhcpOption::VendorSpecific(vec![1, 2, 0xab, 0xcd]));
+    ack.options.push(DhcpOption::ViVendorSpecific(vec![
+        DhcpViVendorSpecific {
+            enterprise_number: 32473,
+            data: b"SN1".to_vec(),
+        },
+        // Not in sub-option format
+        DhcpViVendorSpecific {
+            enterprise_number: 4491,
+            data: vec![1, 5],
+        },
+    ]));
+    client.handle_message(&ack, time(2));
+    let status = client.status(time(2));
+    let info = |enterprise_number, key: &str, value: &str| DhcpVendorInfo {
+        enterprise_number,
+        values: vec![(key.to_string(), value.to_string())]
+            .into_iter()
+            .collect(),
+    };
+    assert_eq!(
+        status.vendor_info,
+        vec![
+            info(None, "1", "ab:cd"),
+            info(Some(32473), "serial", "SN1"),
+            info(Some(4491), "raw", "01:05"),
+        ]
+    );
+}
+
+#[test]
+fn test_client_parameter_request_list_and_extra_options() {
+    let mut config = DhcpV4Config::new("eth1", &TEST_MAC);
+    config.parameter_request_list = vec![1, 3, 252];
+    config.vendor_class = Some("acme".to_string());
+    config.extra_options = vec![DhcpOption::Raw(224, vec![1, 2])];
+    let mut client = DhcpV4Client::new(config, 1);
+    client.start(time(0));
+    let discover = expect_transmit(
+        &mut client,
+        DhcpV4MessageType::Discover,
+        Ipv4Addr::BROADCAST,
+    );
+    assert!(discover
+        .options
+        .contains(&DhcpOption::ParameterRequestList(vec![1, 3, 252, 43])));
+    assert!(discover.options.contains(&DhcpOption::Raw(224, vec![1, 2])));
+
+    client.handle_message(
+        &gen_reply(&discover, DhcpV4MessageType::Offer),
+        time(1),
+    );
+    let request = expect_transmit(
+        &mut client,
+        DhcpV4MessageType::Request,
+        Ipv4Addr::BROADCAST,
+    );
+    assert!(request.options.contains(&DhcpOption::Raw(224, vec![1, 2])));
+    let mut ack = gen_reply(&request, DhcpV4MessageType::Ack);
+    ack.options
+        .push(DhcpOption::Raw(252, b"http://wpad/".to_vec()));
+    client.handle_message(&ack, time(2));
+    let lease = client.status(time(2)).lease.unwrap();
+    assert!(lease
+        .options
+        .contains(&DhcpOption::Raw(252, b"http://wpad/".to_vec())));
+}
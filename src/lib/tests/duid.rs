@@ -0,0 +1,65 @@
+// Copyright 2020 Red Hat, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::time::{Duration, SystemTime};
+
+use mozim::{gen_client_id_mac, gen_client_id_rfc4361, DhcpDuid, DhcpDuidType};
+
+const TEST_MAC: [u8; 6] = [0x00, 0x23, 0x45, 0x67, 0x89, 0x1a];
+
+#[test]
+fn test_duid_emit() {
+    // One hour after 2000-01-01 00:00:00 UTC
+    let duid = DhcpDuid::new_llt(
+        &TEST_MAC,
+        SystemTime::UNIX_EPOCH + Duration::from_secs(946_684_800 + 3600),
+    );
+    assert_eq!(duid.duid_type(), DhcpDuidType::Llt);
+    assert_eq!(
+        duid.emit(),
+        vec![
+            0x00, 0x01, 0x00, 0x01, 0x00, 0x00, 0x0e, 0x10, 0x00, 0x23, 0x45,
+            0x67, 0x89, 0x1a
+        ]
+    );
+    assert_eq!(
+        DhcpDuid::Ll { mac: TEST_MAC }.emit(),
+        vec![0x00, 0x03, 0x00, 0x01, 0x00, 0x23, 0x45, 0x67, 0x89, 0x1a]
+    );
+    assert_eq!(
+        DhcpDuid::En {
+            enterprise_number: 43793,
+            identifier: vec![0xab, 0xcd],
+        }
+        .emit(),
+        vec![0x00, 0x02, 0x00, 0x00, 0xab, 0x11, 0xab, 0xcd]
+    );
+    let uuid = DhcpDuid::Uuid([0x11; 16]).emit();
+    assert_eq!(uuid.len(), 18);
+    assert_eq!(&uuid[..3], &[0x00, 0x04, 0x11]);
+    assert_eq!("uuid".parse::<DhcpDuidType>().unwrap(), DhcpDuidType::Uuid);
+    assert!("foo".parse::<DhcpDuidType>().is_err());
+}
+
+#[test]
+fn test_client_id_format() {
+    let duid = DhcpDuid::Ll { mac: TEST_MAC }.emit();
+    let client_id = gen_client_id_rfc4361(0x0102_0304, &duid);
+    assert_eq!(&client_id[..5], &[0xff, 0x01, 0x02, 0x03, 0x04]);
+    assert_eq!(&client_id[5..], duid.as_slice());
+    assert_eq!(
+        gen_client_id_mac(&TEST_MAC),
+        vec![0x01, 0x00, 0x23, 0x45, 0x67, 0x89, 0x1a]
+    );
+}
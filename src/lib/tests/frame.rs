@@ -0,0 +1,174 @@
+// Copyright 2020 Red Hat, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+#[allow(dead_code)]
+#[path = "../frame.rs"]
+mod frame;
+#[allow(dead_code)]
+#[path = "../socket.rs"]
+mod socket;
+
+use std::net::Ipv4Addr;
+
+// Required by socket.rs
+use mozim::MozimError;
+
+use crate::frame::{gen_bpf_filter, gen_frame, parse_frame};
+
+const CLIENT_MAC: [u8; 6] = [0x00, 0x23, 0x45, 0x67, 0x89, 0x1a];
+const SERVER_MAC: [u8; 6] = [0x00, 0x23, 0x45, 0x67, 0x89, 0x1b];
+const CLIENT_IP: Ipv4Addr = Ipv4Addr::new(192, 0, 2, 100);
+const SERVER_IP: Ipv4Addr = Ipv4Addr::new(192, 0, 2, 1);
+const XID: u32 = 0x1234_5678;
+const IP_OFFSET: usize = 14;
+
+// Start of DHCP message: op, htype, hlen, hops and xid
+fn gen_payload(xid: u32) -> Vec<u8> {
+    let mut payload = vec![2, 1, 6, 0];
+    payload.extend_from_slice(&xid.to_be_bytes());
+    payload
+}
+
+fn gen_reply_frame(xid: u32) -> Vec<u8> {
+    gen_frame(
+        &SERVER_MAC,
+        SERVER_IP,
+        Ipv4Addr::BROADCAST,
+        67,
+        68,
+        &gen_payload(xid),
+    )
+}
+
+// Minimal classic BPF interpreter covering the opcodes used by the filter,
+// out of bound load drops the frame as kernel does.
+fn run_bpf(filter: &[libc::sock_filter], frame: &[u8]) -> u32 {
+    let load = |pos: usize, len: usize| -> Option<u32> {
+        let data = frame.get(pos..pos + len)?;
+        Some(data.iter().fold(0u32, |v, b| (v << 8) | *b as u32))
+    };
+    let mut a = 0u32;
+    let mut x = 0u32;
+    let mut pc = 0;
+    loop {
+        let ins = &filter[pc];
+        let k = ins.k as usize;
+        pc += 1;
+        let loaded = match ins.code {
+            0x28 => load(k, 2),
+            0x30 => load(k, 1),
+            0x48 => load(x as usize + k, 2),
+            0x40 => load(x as usize + k, 4),
+            0xb1 => {
+                match load(k, 1) {
+                    Some(b) => x = (b & 0x0f) * 4,
+                    None => return 0,
+                }
+                continue;
+            }
+            0x15 | 0x45 => {
+                let matched = if ins.code == 0x15 {
+                    a == ins.k
+                } else {
+                    a & ins.k != 0
+                };
+                pc += if matched { ins.jt } else { ins.jf } as usize;
+                continue;
+            }
+            0x06 => return ins.k,
+            code => panic!("Unexpected BPF opcode {:#x}", code),
+        };
+        match loaded {
+            Some(v) => a = v,
+            None => return 0,
+        }
+    }
+}
+
+#[test]
+fn test_frame_round_trip() {
+    let frame = gen_reply_frame(XID);
+    assert_eq!(&frame[..6], &[0xff; 6]);
+    assert_eq!(&frame[6..12], &SERVER_MAC);
+    assert_eq!(parse_frame(&frame), Some(gen_payload(XID).as_slice()));
+
+    // Ethernet padding of short frame is ignored
+    let mut padded = frame.clone();
+    padded.extend_from_slice(&[0u8; 10]);
+    assert_eq!(parse_frame(&padded), Some(gen_payload(XID).as_slice()));
+}
+
+#[test]
+fn test_frame_checksum() {
+    let frame = gen_frame(&CLIENT_MAC, CLIENT_IP, SERVER_IP, 68, 67, b"mozim");
+    assert_eq!(frame.len(), 14 + 20 + 8 + 5);
+    // Checked against an independent implementation
+    assert_eq!(&frame[IP_OFFSET + 10..IP_OFFSET + 12], &[0xf6, 0x66]);
+    assert_eq!(&frame[IP_OFFSET + 26..IP_OFFSET + 28], &[0x26, 0x0e]);
+    // Checksum over header including checksum is zero
+    assert_eq!(
+        socket::inet_checksum(&frame[IP_OFFSET..IP_OFFSET + 20], 0),
+        0
+    );
+}
+
+#[test]
+fn test_frame_parse_invalid() {
+    // Sent to server port
+    let frame = gen_frame(&CLIENT_MAC, CLIENT_IP, SERVER_IP, 68, 67, b"mozim");
+    assert!(parse_frame(&frame).is_none());
+
+    let frame = gen_reply_frame(XID);
+    let mut tcp = frame.clone();
+    tcp[IP_OFFSET + 9] = 6;
+    assert!(parse_frame(&tcp).is_none());
+    let mut arp = frame.clone();
+    arp[12..14].copy_from_slice(&[0x08, 0x06]);
+    assert!(parse_frame(&arp).is_none());
+    // Shorter than IP total length
+    assert!(parse_frame(&frame[..frame.len() - 1]).is_none());
+    assert!(parse_frame(&frame[..IP_OFFSET + 20]).is_none());
+    // UDP length beyond IP payload
+    let mut bad_udp_len = frame.clone();
+    bad_udp_len[IP_OFFSET + 24..IP_OFFSET + 26].copy_from_slice(&[0xff, 0xff]);
+    assert!(parse_frame(&bad_udp_len).is_none());
+}
+
+#[test]
+fn test_frame_bpf_filter() {
+    let filter = gen_bpf_filter(XID);
+    assert_ne!(run_bpf(&filter, &gen_reply_frame(XID)), 0);
+    assert_eq!(run_bpf(&filter, &gen_reply_frame(XID + 1)), 0);
+
+    let frame = gen_reply_frame(XID);
+    let mut to_server = frame.clone();
+    to_server[IP_OFFSET + 22..IP_OFFSET + 24].copy_from_slice(&[0, 67]);
+    assert_eq!(run_bpf(&filter, &to_server), 0);
+    let mut tcp = frame.clone();
+    tcp[IP_OFFSET + 9] = 6;
+    assert_eq!(run_bpf(&filter, &tcp), 0);
+    let mut arp = frame.clone();
+    arp[12..14].copy_from_slice(&[0x08, 0x06]);
+    assert_eq!(run_bpf(&filter, &arp), 0);
+    let mut fragment = frame.clone();
+    fragment[IP_OFFSET + 7] = 1;
+    assert_eq!(run_bpf(&filter, &fragment), 0);
+    assert_eq!(run_bpf(&filter, &frame[..IP_OFFSET + 20]), 0);
+
+    // UDP header located by IP header length holding options
+    let mut with_options = frame.clone();
+    with_options[IP_OFFSET] = 0x46;
+    with_options.splice(IP_OFFSET + 20..IP_OFFSET + 20, [1u8; 4]);
+    assert_ne!(run_bpf(&filter, &with_options), 0);
+}
@@ -0,0 +1,92 @@
+// Copyright 2020 Red Hat, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::net::Ipv4Addr;
+use std::time::{Duration, UNIX_EPOCH};
+
+use mozim::{
+    DhcpClasslessRoute, DhcpLease, DhcpOption, DhcpV4Message,
+    DhcpV4MessageType, DhcpV4Op,
+};
+
+const TEST_MAC: [u8; 6] = [0x00, 0x23, 0x45, 0x67, 0x89, 0x1a];
+
+#[test]
+fn test_dhcp_lease_from_ack() {
+    let mut ack = DhcpV4Message::new_request(1, &TEST_MAC);
+    ack.op = DhcpV4Op::BootReply;
+    ack.yiaddr = Ipv4Addr::new(192, 0, 2, 100);
+    ack.options = vec![
+        DhcpOption::MessageType(DhcpV4MessageType::Ack),
+        DhcpOption::SubnetMask(Ipv4Addr::new(255, 255, 255, 0)),
+        DhcpOption::Router(vec![Ipv4Addr::new(192, 0, 2, 1)]),
+        DhcpOption::DnsServer(vec![Ipv4Addr::new(192, 0, 2, 2)]),
+        DhcpOption::DomainName("example.com".into()),
+        DhcpOption::DomainSearch(vec!["example.org".into()]),
+        DhcpOption::LeaseTime(3600),
+        // Invalid T1 as it is larger than T2, default T1 should be used
+        DhcpOption::RenewalTime(3200),
+        DhcpOption::RebindingTime(3000),
+        DhcpOption::InterfaceMtu(1400),
+    ];
+    let now = UNIX_EPOCH + Duration::from_secs(1_600_000_000);
+    let lease =
+        DhcpLease::new_from_ack(&ack, Some(Ipv4Addr::new(192, 0, 2, 1)), now);
+    assert_eq!(lease.address, Ipv4Addr::new(192, 0, 2, 100));
+    assert_eq!(lease.prefix_length, 24);
+    assert_eq!(lease.gateways, vec![Ipv4Addr::new(192, 0, 2, 1)]);
+    assert_eq!(lease.dns_servers, vec![Ipv4Addr::new(192, 0, 2, 2)]);
+    assert_eq!(lease.domain.as_deref(), Some("example.com"));
+    assert_eq!(lease.search, vec!["example.org".to_string()]);
+    assert_eq!(lease.server_id, Ipv4Addr::new(192, 0, 2, 1));
+    assert_eq!((lease.t1, lease.t2, lease.lease_time), (1800, 3000, 3600));
+    assert_eq!(lease.mtu, Some(1400));
+    assert_eq!(lease.acquired_at, 1_600_000_000);
+    assert_eq!(
+        lease.timers(),
+        Some((
+            now + Duration::from_secs(1800),
+            now + Duration::from_secs(3000),
+            now + Duration::from_secs(3600)
+        ))
+    );
+}
+
+#[test]
+fn test_dhcp_lease_classless_routes_preference() {
+    let route_121 = DhcpClasslessRoute {
+        dst: Ipv4Addr::new(10, 0, 0, 0),
+        prefix_length: 8,
+        gateway: Ipv4Addr::new(192, 0, 2, 1),
+    };
+    let route_249 = DhcpClasslessRoute {
+        dst: Ipv4Addr::new(172, 16, 0, 0),
+        prefix_length: 12,
+        gateway: Ipv4Addr::new(192, 0, 2, 1),
+    };
+    let mut ack = DhcpV4Message::new_request(1, &TEST_MAC);
+    ack.op = DhcpV4Op::BootReply;
+    ack.yiaddr = Ipv4Addr::new(192, 0, 2, 100);
+    ack.options = vec![
+        DhcpOption::MsClasslessStaticRoute(vec![route_249]),
+        DhcpOption::ClasslessStaticRoute(vec![route_121]),
+    ];
+    let now = UNIX_EPOCH + Duration::from_secs(1_600_000_000);
+    let lease = DhcpLease::new_from_ack(&ack, None, now);
+    assert_eq!(lease.classless_routes, vec![route_121]);
+
+    ack.options = vec![DhcpOption::MsClasslessStaticRoute(vec![route_249])];
+    let lease = DhcpLease::new_from_ack(&ack, None, now);
+    assert_eq!(lease.classless_routes, vec![route_249]);
+}
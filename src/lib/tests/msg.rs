@@ -0,0 +1,82 @@
+// Copyright 2020 Red Hat, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::net::Ipv4Addr;
+
+use mozim::{
+    DhcpOption, DhcpV4Message, DhcpV4MessageType, DhcpV4Op, ErrorKind,
+    DHCP_MAGIC_COOKIE,
+};
+
+const TEST_MAC: [u8; 6] = [0x00, 0x23, 0x45, 0x67, 0x89, 0x1a];
+
+fn gen_reply() -> DhcpV4Message {
+    let mut msg = DhcpV4Message::new_request(0x1234_5678, &TEST_MAC);
+    msg.op = DhcpV4Op::BootReply;
+    msg.secs = 3;
+    msg.yiaddr = Ipv4Addr::new(192, 0, 2, 100);
+    msg.siaddr = Ipv4Addr::new(192, 0, 2, 1);
+    msg.sname = "dhcp-srv".into();
+    msg.file = "pxelinux.0".into();
+    msg.options = vec![
+        DhcpOption::MessageType(DhcpV4MessageType::Offer),
+        DhcpOption::ServerIdentifier(Ipv4Addr::new(192, 0, 2, 1)),
+    ];
+    msg
+}
+
+#[test]
+fn test_dhcp_msg_round_trip() {
+    let msg = gen_reply();
+    let data = msg.emit();
+    assert_eq!(data[236..240], DHCP_MAGIC_COOKIE);
+    assert_eq!(data[data.len() - 1], 255);
+
+    let parsed = DhcpV4Message::parse(&data).unwrap();
+    assert_eq!(parsed, msg);
+    assert_eq!(parsed.hw_addr(), &TEST_MAC);
+    assert_eq!(parsed.emit(), data);
+}
+
+#[test]
+fn test_dhcp_msg_long_option_split() {
+    let mut msg = gen_reply();
+    msg.options = vec![DhcpOption::Raw(43, vec![7u8; 300])];
+    let data = msg.emit();
+    assert_eq!(data[240], 43);
+    assert_eq!(data[241], 255);
+    assert_eq!(data[242 + 255], 43);
+    assert_eq!(data[243 + 255], 45);
+}
+
+#[test]
+fn test_dhcp_msg_malformed() {
+    let data = gen_reply().emit();
+
+    let e = DhcpV4Message::parse(&data[..100]).unwrap_err();
+    assert!(matches!(e.kind, ErrorKind::InvalidDhcpMessage));
+
+    let mut bad_cookie = data.clone();
+    bad_cookie[239] = 0;
+    assert!(DhcpV4Message::parse(&bad_cookie).is_err());
+
+    let mut bad_op = data.clone();
+    bad_op[0] = 3;
+    assert!(DhcpV4Message::parse(&bad_op).is_err());
+
+    // Option 54 claiming more data than the message holds
+    let mut truncated = data[..243].to_vec();
+    truncated.extend_from_slice(&[54, 4, 192, 0]);
+    assert!(DhcpV4Message::parse(&truncated).is_err());
+}
@@ -0,0 +1,273 @@
+// Copyright 2020 Red Hat, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::net::Ipv4Addr;
+
+use mozim::{
+    DhcpClasslessRoute, DhcpClientFqdn, DhcpFqdnFlags, DhcpOption,
+    DhcpV4Message, DhcpV4MessageType, DhcpV4Op, DhcpViVendorSpecific,
+};
+
+const TEST_MAC: [u8; 6] = [0x00, 0x23, 0x45, 0x67, 0x89, 0x1a];
+
+// Build a reply with the given bytes placed after the magic cookie.
+fn gen_raw_reply(options: &[u8]) -> Vec<u8> {
+    let mut msg = DhcpV4Message::new_request(1, &TEST_MAC);
+    msg.op = DhcpV4Op::BootReply;
+    let mut data = msg.emit();
+    data.truncate(240);
+    data.extend_from_slice(options);
+    data
+}
+
+#[test]
+fn test_dhcp_option_typed_round_trip() {
+    let options = vec![
+        DhcpOption::MessageType(DhcpV4MessageType::Ack),
+        DhcpOption::SubnetMask(Ipv4Addr::new(255, 255, 255, 0)),
+        DhcpOption::Router(vec![Ipv4Addr::new(192, 0, 2, 1)]),
+        DhcpOption::DnsServer(vec![
+            Ipv4Addr::new(192, 0, 2, 2),
+            Ipv4Addr::new(192, 0, 2, 3),
+        ]),
+        DhcpOption::DomainName("example.com".into()),
+        DhcpOption::HostName("host1".into()),
+        DhcpOption::LeaseTime(3600),
+        DhcpOption::RenewalTime(1800),
+        DhcpOption::RebindingTime(3150),
+        DhcpOption::ServerIdentifier(Ipv4Addr::new(192, 0, 2, 1)),
+        DhcpOption::InterfaceMtu(1500),
+        DhcpOption::NtpServer(vec![Ipv4Addr::new(192, 0, 2, 4)]),
+        DhcpOption::BroadcastAddress(Ipv4Addr::new(192, 0, 2, 255)),
+        DhcpOption::ParameterRequestList(vec![1, 3, 6]),
+        DhcpOption::ClientIdentifier(vec![1, 0, 0x23, 0x45, 0x67, 0x89, 0x1a]),
+        DhcpOption::RapidCommit,
+        DhcpOption::Raw(224, vec![1, 2, 3]),
+    ];
+    let mut msg = DhcpV4Message::new_request(1, &TEST_MAC);
+    msg.options = options.clone();
+    let parsed = DhcpV4Message::parse(&msg.emit()).unwrap();
+    assert_eq!(parsed.options, options);
+    assert_eq!(parsed.message_type(), Some(DhcpV4MessageType::Ack));
+    assert_eq!(
+        parsed.server_identifier(),
+        Some(Ipv4Addr::new(192, 0, 2, 1))
+    );
+}
+
+#[test]
+fn test_dhcp_option_malformed_kept_raw() {
+    // Subnet mask with 3 bytes and unknown vendor option
+    let data = gen_raw_reply(&[1, 3, 255, 255, 0, 250, 2, 9, 9, 255]);
+    let msg = DhcpV4Message::parse(&data).unwrap();
+    assert_eq!(
+        msg.options,
+        vec![
+            DhcpOption::Raw(1, vec![255, 255, 0]),
+            DhcpOption::Raw(250, vec![9, 9]),
+        ]
+    );
+    assert_eq!(&msg.emit()[240..], &data[240..]);
+}
+
+#[test]
+fn test_dhcp_option_concatenation() {
+    // RFC 3396: two instances of option 6 are one DNS server list
+    let data =
+        gen_raw_reply(&[6, 4, 192, 0, 2, 2, 53, 1, 5, 6, 4, 192, 0, 2, 3, 255]);
+    let msg = DhcpV4Message::parse(&data).unwrap();
+    assert_eq!(
+        msg.options,
+        vec![
+            DhcpOption::DnsServer(vec![
+                Ipv4Addr::new(192, 0, 2, 2),
+                Ipv4Addr::new(192, 0, 2, 3),
+            ]),
+            DhcpOption::MessageType(DhcpV4MessageType::Ack),
+        ]
+    );
+}
+
+#[test]
+fn test_dhcp_option_overload() {
+    let mut data =
+        gen_raw_reply(&[53, 1, 5, 52, 1, 3, 15, 3, b'f', b'o', b'o', 255]);
+    // `file` field at offset 108, `sname` at 44
+    data[108..119]
+        .copy_from_slice(&[15, 4, b'.', b'c', b'o', b'm', 51, 2, 0, 0, 255]);
+    data[44..51].copy_from_slice(&[51, 2, 0x0e, 0x10, 58, 0, 255]);
+    // Option 51 is split between `file` and `sname`, option 58 has zero
+    // length which is kept as raw.
+    let msg = DhcpV4Message::parse(&data).unwrap();
+    assert_eq!(msg.file, "");
+    assert_eq!(msg.sname, "");
+    assert_eq!(
+        msg.options,
+        vec![
+            DhcpOption::MessageType(DhcpV4MessageType::Ack),
+            DhcpOption::DomainName("foo.com".into()),
+            DhcpOption::LeaseTime(3600),
+            DhcpOption::Raw(58, vec![]),
+        ]
+    );
+}
+
+#[test]
+fn test_dhcp_option_invalid_overload() {
+    let data = gen_raw_reply(&[52, 1, 4, 255]);
+    assert!(DhcpV4Message::parse(&data).is_err());
+}
+
+#[test]
+fn test_dhcp_option_domain_search_compression() {
+    // RFC 3397 section 2 example: eng.apple.com and marketing.apple.com
+    let mut data = vec![119, 27];
+    data.extend_from_slice(b"\x03eng\x05apple\x03com\x00");
+    data.extend_from_slice(b"\x09marketing\xc0\x04");
+    data.push(255);
+    let msg = DhcpV4Message::parse(&gen_raw_reply(&data)).unwrap();
+    assert_eq!(
+        msg.options,
+        vec![DhcpOption::DomainSearch(vec![
+            "eng.apple.com".into(),
+            "marketing.apple.com".into()
+        ])]
+    );
+}
+
+#[test]
+fn test_dhcp_option_classless_static_route() {
+    // 10.0.0.0/8 via 192.0.2.1, 0.0.0.0/0 via 192.0.2.254, and option 249
+    // of 198.51.100.0/24 on-link
+    let data = gen_raw_reply(&[
+        121, 11, 8, 10, 192, 0, 2, 1, 0, 192, 0, 2, 254, 249, 8, 24, 198, 51,
+        100, 0, 0, 0, 0, 255,
+    ]);
+    let msg = DhcpV4Message::parse(&data).unwrap();
+    assert_eq!(
+        msg.options,
+        vec![
+            DhcpOption::ClasslessStaticRoute(vec![
+                DhcpClasslessRoute {
+                    dst: Ipv4Addr::new(10, 0, 0, 0),
+                    prefix_length: 8,
+                    gateway: Ipv4Addr::new(192, 0, 2, 1),
+                },
+                DhcpClasslessRoute {
+                    dst: Ipv4Addr::UNSPECIFIED,
+                    prefix_length: 0,
+                    gateway: Ipv4Addr::new(192, 0, 2, 254),
+                },
+            ]),
+            DhcpOption::MsClasslessStaticRoute(vec![DhcpClasslessRoute {
+                dst: Ipv4Addr::new(198, 51, 100, 0),
+                prefix_length: 24,
+                gateway: Ipv4Addr::UNSPECIFIED,
+            }]),
+        ]
+    );
+    assert_eq!(&msg.emit()[240..], &data[240..]);
+
+    // Truncated router address
+    let data = gen_raw_reply(&[121, 5, 8, 10, 192, 0, 2, 255]);
+    let msg = DhcpV4Message::parse(&data).unwrap();
+    assert_eq!(
+        msg.options,
+        vec![DhcpOption::Raw(121, vec![8, 10, 192, 0, 2])]
+    );
+}
+
+#[test]
+fn test_dhcp_option_client_fqdn() {
+    let fqdn = DhcpClientFqdn::new("host1.example.com.", true);
+    let opt = DhcpOption::ClientFqdn(fqdn);
+    let mut data = vec![0x05, 0, 0, 5];
+    data.extend_from_slice(b"host1");
+    data.push(7);
+    data.extend_from_slice(b"example");
+    data.push(3);
+    data.extend_from_slice(b"com");
+    data.push(0);
+    assert_eq!(opt.emit_data(), data);
+    assert_eq!(DhcpOption::parse(81, &data), opt);
+
+    // Partial name has no terminating zero label
+    let opt = DhcpOption::ClientFqdn(DhcpClientFqdn::new("host1", false));
+    let data = opt.emit_data();
+    assert_eq!(data, b"\x04\x00\x00\x05host1".to_vec());
+    assert_eq!(DhcpOption::parse(81, &data), opt);
+
+    // Server reply of ASCII name with S and O flags
+    let mut data = vec![0x03, 255, 255];
+    data.extend_from_slice(b"host1.example.com");
+    match DhcpOption::parse(81, &data) {
+        DhcpOption::ClientFqdn(fqdn) => {
+            assert_eq!(
+                fqdn.flags,
+                DhcpFqdnFlags {
+                    server_update: true,
+                    server_override: true,
+                    encoded: false,
+                    no_update: false,
+                }
+            );
+            assert_eq!(u8::from(fqdn.flags), 0x03);
+            assert_eq!(fqdn.name, "host1.example.com");
+        }
+        opt => panic!("Expecting ClientFqdn, got {:?}", opt),
+    }
+    assert_eq!(
+        DhcpOption::parse(81, &[0x04, 0]),
+        DhcpOption::Raw(81, vec![4, 0])
+    );
+}
+
+#[test]
+fn test_dhcp_option_vendor() {
+    let data = gen_raw_reply(&[
+        60, 4, b'a', b'c', b'm', b'e', 77, 6, 2, b'u', b'1', 2, b'u', b'2', 43,
+        3, 1, 1, 9, 125, 13, 0, 0, 0x11, 0x8b, 3, 1, 1, 7, 0, 0, 0, 9, 0, 255,
+    ]);
+    let msg = DhcpV4Message::parse(&data).unwrap();
+    assert_eq!(
+        msg.options,
+        vec![
+            DhcpOption::VendorClass("acme".into()),
+            DhcpOption::UserClass(vec!["u1".into(), "u2".into()]),
+            DhcpOption::VendorSpecific(vec![1, 1, 9]),
+            DhcpOption::ViVendorSpecific(vec![
+                DhcpViVendorSpecific {
+                    enterprise_number: 4491,
+                    data: vec![1, 1, 7],
+                },
+                DhcpViVendorSpecific {
+                    enterprise_number: 9,
+                    data: Vec::new(),
+                },
+            ]),
+        ]
+    );
+    assert_eq!(&msg.emit()[240..], &data[240..]);
+
+    // Zero length user class and truncated enterprise data
+    let data = gen_raw_reply(&[77, 2, 0, 1, 125, 6, 0, 0, 0, 9, 2, 1, 255]);
+    let msg = DhcpV4Message::parse(&data).unwrap();
+    assert_eq!(
+        msg.options,
+        vec![
+            DhcpOption::Raw(77, vec![0, 1]),
+            DhcpOption::Raw(125, vec![0, 0, 0, 9, 2, 1]),
+        ]
+    );
+}
@@ -0,0 +1,133 @@
+// Copyright 2020 Red Hat, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::net::{Ipv4Addr, SocketAddrV4, UdpSocket};
+use std::os::unix::io::AsRawFd;
+use std::process::Command;
+use std::time::Duration;
+
+use mozim::{DhcpV4Message, DhcpV4Op, DhcpV4UdpSocket};
+
+const TEST_MAC: [u8; 6] = [0x00, 0x23, 0x45, 0x67, 0x89, 0x1a];
+const TEST_CLIENT_NETNS: &str = "mozim_test_udp_c";
+const TEST_SERVER_NETNS: &str = "mozim_test_udp_s";
+const TEST_IFACE: &str = "mozim_veth0";
+const TEST_PEER: &str = "mozim_veth1";
+const TEST_CLIENT_IP: Ipv4Addr = Ipv4Addr::new(192, 0, 2, 100);
+const TEST_SERVER_IP: Ipv4Addr = Ipv4Addr::new(192, 0, 2, 1);
+const TEST_TIMEOUT: Duration = Duration::from_secs(5);
+
+fn ip(netns: &str, args: &[&str]) {
+    let status = Command::new("ip")
+        .arg("-n")
+        .arg(netns)
+        .args(args)
+        .status()
+        .unwrap();
+    assert!(status.success(), "ip -n {} {:?} failed", netns, args);
+}
+
+// Only the calling thread enters the network namespace.
+fn enter_netns(netns: &str) {
+    let netns = std::fs::File::open(format!("/run/netns/{}", netns)).unwrap();
+    assert_eq!(
+        unsafe { libc::setns(netns.as_raw_fd(), libc::CLONE_NEWNET) },
+        0
+    );
+}
+
+fn check_udp_socket() {
+    ip(
+        TEST_CLIENT_NETNS,
+        &[
+            "link",
+            "add",
+            TEST_IFACE,
+            "type",
+            "veth",
+            "peer",
+            "name",
+            TEST_PEER,
+            "netns",
+            TEST_SERVER_NETNS,
+        ],
+    );
+    ip(TEST_CLIENT_NETNS, &["link", "set", TEST_IFACE, "up"]);
+    ip(TEST_SERVER_NETNS, &["link", "set", TEST_PEER, "up"]);
+    ip(
+        TEST_SERVER_NETNS,
+        &["addr", "add", "192.0.2.1/24", "dev", TEST_PEER],
+    );
+
+    // Echo the first DHCP message back to where it came from
+    let (ready_sender, ready_receiver) = std::sync::mpsc::channel();
+    let server = std::thread::spawn(move || {
+        enter_netns(TEST_SERVER_NETNS);
+        let socket =
+            UdpSocket::bind(SocketAddrV4::new(TEST_SERVER_IP, 67)).unwrap();
+        socket.set_read_timeout(Some(TEST_TIMEOUT)).unwrap();
+        ready_sender.send(()).unwrap();
+        let mut buf = [0u8; 1500];
+        let (len, src) = socket.recv_from(&mut buf).unwrap();
+        let mut reply = DhcpV4Message::parse(&buf[..len]).unwrap();
+        reply.op = DhcpV4Op::BootReply;
+        socket.send_to(&reply.emit(), src).unwrap();
+        src
+    });
+    ready_receiver.recv().unwrap();
+
+    enter_netns(TEST_CLIENT_NETNS);
+    assert!(DhcpV4UdpSocket::new("mozim_none", TEST_CLIENT_IP).is_err());
+    // IP_FREEBIND allows binding before the address is assigned
+    let socket = DhcpV4UdpSocket::new(TEST_IFACE, TEST_CLIENT_IP).unwrap();
+    assert_eq!(socket.local_addr(), TEST_CLIENT_IP);
+    // SO_REUSEADDR allows new socket while the old one is not closed yet
+    DhcpV4UdpSocket::new(TEST_IFACE, TEST_CLIENT_IP).unwrap();
+    ip(
+        TEST_CLIENT_NETNS,
+        &["addr", "add", "192.0.2.100/24", "dev", TEST_IFACE],
+    );
+
+    let msg = DhcpV4Message::new_request(0x1234_5678, &TEST_MAC);
+    socket.send(&msg, TEST_SERVER_IP).unwrap();
+    let reply = socket.recv(TEST_TIMEOUT).unwrap().unwrap();
+    assert_eq!(reply.op, DhcpV4Op::BootReply);
+    assert_eq!(reply.xid, msg.xid);
+    assert_eq!(
+        server.join().unwrap(),
+        std::net::SocketAddr::V4(SocketAddrV4::new(TEST_CLIENT_IP, 68))
+    );
+}
+
+// Needs root to create network namespace, run by
+// `sudo cargo test -- --ignored`.
+#[test]
+#[ignore]
+fn test_udp_socket() {
+    for netns in [TEST_CLIENT_NETNS, TEST_SERVER_NETNS] {
+        let status = Command::new("ip")
+            .args(["netns", "add", netns])
+            .status()
+            .unwrap();
+        assert!(status.success());
+    }
+    let result = std::thread::spawn(check_udp_socket).join();
+    for netns in [TEST_CLIENT_NETNS, TEST_SERVER_NETNS] {
+        Command::new("ip")
+            .args(["netns", "del", netns])
+            .status()
+            .unwrap();
+    }
+    result.unwrap();
+}
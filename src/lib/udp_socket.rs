@@ -0,0 +1,87 @@
+// Copyright 2020 Red Hat, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::net::Ipv4Addr;
+use std::time::Duration;
+
+use crate::socket::{
+    bind, bind_to_device, gen_sockaddr_in, new_socket, poll_readable, recv,
+    send_to, set_sock_opt, Fd, DHCP_CLIENT_PORT, DHCP_SERVER_PORT,
+    MAX_FRAME_SIZE,
+};
+use crate::{DhcpV4Message, MozimError};
+
+// UDP socket bound to port 68 of the leased address and the interface, used
+// for unicast DHCPREQUEST, DHCPRELEASE and DHCPINFORM once an address is
+// assigned.
+#[derive(Debug)]
+pub struct DhcpV4UdpSocket {
+    fd: Fd,
+    local_addr: Ipv4Addr,
+}
+
+impl DhcpV4UdpSocket {
+    pub fn new(
+        iface_name: &str,
+        local_addr: Ipv4Addr,
+    ) -> Result<Self, MozimError> {
+        let fd =
+            new_socket(libc::AF_INET, libc::SOCK_DGRAM, libc::IPPROTO_UDP)?;
+        let enable: libc::c_int = 1;
+        set_sock_opt(&fd, libc::SOL_SOCKET, libc::SO_REUSEADDR, &enable)?;
+        set_sock_opt(&fd, libc::SOL_SOCKET, libc::SO_BROADCAST, &enable)?;
+        // The address might not be assigned to interface yet
+        set_sock_opt(&fd, libc::IPPROTO_IP, libc::IP_FREEBIND, &enable)?;
+        bind_to_device(&fd, iface_name)?;
+        bind(&fd, &gen_sockaddr_in(local_addr, DHCP_CLIENT_PORT)).map_err(
+            |e| {
+                MozimError::socket_error(format!(
+                    "Failed to bind UDP socket to {}:{} on {}: {}",
+                    local_addr, DHCP_CLIENT_PORT, iface_name, e.msg
+                ))
+            },
+        )?;
+        Ok(DhcpV4UdpSocket { fd, local_addr })
+    }
+
+    pub fn local_addr(&self) -> Ipv4Addr {
+        self.local_addr
+    }
+
+    pub fn send(
+        &self,
+        msg: &DhcpV4Message,
+        dst: Ipv4Addr,
+    ) -> Result<(), MozimError> {
+        send_to(
+            &self.fd,
+            &msg.emit(),
+            &gen_sockaddr_in(dst, DHCP_SERVER_PORT),
+        )
+    }
+
+    // Wait up to `timeout` for a DHCP message, return None on timeout.
+    // Malformed messages are ignored.
+    pub fn recv(
+        &self,
+        timeout: Duration,
+    ) -> Result<Option<DhcpV4Message>, MozimError> {
+        if !poll_readable(&self.fd, timeout)? {
+            return Ok(None);
+        }
+        let mut buf = [0u8; MAX_FRAME_SIZE];
+        let len = recv(&self.fd, &mut buf)?;
+        Ok(DhcpV4Message::parse(&buf[..len]).ok())
+    }
+}
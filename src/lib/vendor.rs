@@ -0,0 +1,103 @@
+// Copyright 2020 Red Hat, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+// Decoding of vendor-specific information in option 43 and 125 for
+// `DhcpStatus`. The content of both options is defined by vendors, so
+// library users could register their own decoder for an enterprise number
+// by `DhcpV4Client::register_vendor_decoder()`.
+
+use std::collections::BTreeMap;
+
+use serde_derive::{Deserialize, Serialize};
+
+use crate::dhcp::to_hex;
+use crate::DhcpOption;
+
+const SUB_OPTION_PAD: u8 = 0;
+const SUB_OPTION_END: u8 = 255;
+const RAW_KEY: &str = "raw";
+
+// Decode the data of one vendor into name and value pairs, None if the data
+// is malformed.
+pub type DhcpVendorDecoder = fn(&[u8]) -> Option<BTreeMap<String, String>>;
+
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct DhcpVendorInfo {
+    // Enterprise number of option 125, None for option 43
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub enterprise_number: Option<u32>,
+    pub values: BTreeMap<String, String>,
+}
+
+// The default decoder: RFC 2132 section 8.4 encapsulated sub-options as
+// sub-option code to colon separated hex data.
+pub fn decode_vendor_sub_options(
+    data: &[u8],
+) -> Option<BTreeMap<String, String>> {
+    let mut values = BTreeMap::new();
+    let mut i = 0;
+    while i < data.len() {
+        match data[i] {
+            SUB_OPTION_PAD => {
+                i += 1;
+                continue;
+            }
+            SUB_OPTION_END => break,
+            code => {
+                let len = *data.get(i + 1)? as usize;
+                let sub_data = data.get(i + 2..i + 2 + len)?;
+                values.insert(code.to_string(), to_hex(sub_data));
+                i += 2 + len;
+            }
+        }
+    }
+    Some(values)
+}
+
+// Data failed to decode is shown as hex under `raw` key.
+pub(crate) fn decode_vendor_info(
+    options: &[DhcpOption],
+    decoders: &BTreeMap<u32, DhcpVendorDecoder>,
+) -> Vec<DhcpVendorInfo> {
+    let decode = |decoder: DhcpVendorDecoder, data: &[u8]| {
+        decoder(data).unwrap_or_else(|| {
+            let mut values = BTreeMap::new();
+            values.insert(RAW_KEY.to_string(), to_hex(data));
+            values
+        })
+    };
+    let mut infos = Vec::new();
+    for opt in options {
+        match opt {
+            DhcpOption::VendorSpecific(data) => infos.push(DhcpVendorInfo {
+                enterprise_number: None,
+                values: decode(decode_vendor_sub_options, data),
+            }),
+            DhcpOption::ViVendorSpecific(vendors) => {
+                for vendor in vendors {
+                    let decoder = decoders
+                        .get(&vendor.enterprise_number)
+                        .copied()
+                        .unwrap_or(decode_vendor_sub_options);
+                    infos.push(DhcpVendorInfo {
+                        enterprise_number: Some(vendor.enterprise_number),
+                        values: decode(decoder, &vendor.data),
+                    });
+                }
+            }
+            _ => (),
+        }
+    }
+    infos
+}